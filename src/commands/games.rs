@@ -0,0 +1,97 @@
+use serenity::{
+    client::Context,
+    model::prelude::*,
+    framework::standard::{
+        Args, CommandResult, macros::command
+    },
+    utils::MessageBuilder,
+};
+
+use super::utils::dice::Roll;
+use super::utils::random::Rng;
+
+/// Explains the dice notation, for `help roll`.
+pub fn roll_help() -> String {
+    MessageBuilder::new()
+        .push_line("`roll <dice>` rolls dice written as `2d6` for two six-sided dice, `d20` for one \
+                    and `d%` for a hundred sides, added to or subtracted from each other and numbers, \
+                    as `roll 2d6 + d4 - 1`.")
+        .push_line("After dice, `kh3` keeps the three highest and `kl1` the lowest, `dh1` drops the highest \
+                    and `dl1` the lowest, as `roll 4d6kh3`, and `!` rolls again the dice showing their \
+                    highest side, as `roll 3d6!`.")
+        .push_line("`roll d20 adv` rolls with advantage, keeping the best of two rolls, and `roll d20 dis` \
+                    with disadvantage, keeping the worst.")
+        .build()
+}
+
+/// The choices given to a command, separated by commas if there are some,
+/// else by spaces.
+fn choices(input: &str) -> Vec<&str> {
+    if input.contains(',') {
+        input.split(',').map(str::trim).filter(|choice| !choice.is_empty()).collect()
+    } else {
+        input.split_whitespace().collect()
+    }
+}
+
+#[command]
+#[description("Rolls dice, as `roll 4d6kh3+2` or `roll d20 adv`, see `help roll`.")]
+pub async fn roll(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let input = args.rest().trim();
+    if input.is_empty() {
+        msg.channel_id.say(&ctx.http, "Usage: `roll <dice>`, as `roll 2d6+1`, see `help roll`").await?;
+        return Ok(());
+    }
+
+    let ans = match Roll::parse(input) {
+        Ok(roll) => format!("🎲 {}", roll.roll(&mut Rng::from_time())),
+        Err(why) => MessageBuilder::new()
+            .push_line("Could not roll this 🤔:")
+            .push_codeblock_safe(why.pointer(input), None)
+            .build(),
+    };
+    msg.channel_id.say(&ctx.http, ans).await?;
+    Ok(())
+}
+
+#[command]
+#[description("Picks one of the choices given, separated by spaces or commas.")]
+pub async fn pick(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let choices = choices(args.rest());
+    let ans = match Rng::from_time().pick(&choices) {
+        Some(choice) => MessageBuilder::new().push("👉 ").push_bold_safe(*choice).build(),
+        None => String::from("Usage: `pick <choices...>`, separated by spaces or commas"),
+    };
+    msg.channel_id.say(&ctx.http, ans).await?;
+    Ok(())
+}
+
+#[command]
+#[description("Shuffles the items given, separated by spaces or commas.")]
+pub async fn shuffle(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let mut items = choices(args.rest());
+    if items.is_empty() {
+        msg.channel_id.say(&ctx.http, "Usage: `shuffle <items...>`, separated by spaces or commas").await?;
+        return Ok(());
+    }
+
+    Rng::from_time().shuffle(&mut items);
+    let mut ans = MessageBuilder::new();
+    ans.push("🔀 ");
+    for (i, item) in items.iter().enumerate() {
+        if i > 0 {
+            ans.push(", ");
+        }
+        ans.push_safe(*item);
+    }
+    msg.channel_id.say(&ctx.http, ans.build()).await?;
+    Ok(())
+}
+
+#[command]
+#[description("Flips a coin.")]
+pub async fn coin(ctx: &Context, msg: &Message) -> CommandResult {
+    let side = if Rng::from_time().below(2) == 0 { "Heads" } else { "Tails" };
+    msg.channel_id.say(&ctx.http, format!("🪙 {}!", side)).await?;
+    Ok(())
+}
@@ -0,0 +1,178 @@
+use std::str::FromStr;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Write, Error as IOError, ErrorKind};
+use std::sync::Arc;
+
+use serenity::{
+    prelude::{TypeMapKey, RwLock, Context},
+    model::prelude::Message,
+    framework::standard::{
+        Args, CommandResult, macros::command
+    },
+    utils::MessageBuilder,
+};
+
+use super::player::{
+    MUSIC_CHECK, admit_tracks, connect_to_author, queue_tracks, to_track,
+};
+use super::utils::history::{appended, PlayHistory, PlayedTrack};
+
+pub const HISTORY_FILE_PATH: &str = "data/history.txt";
+
+/// Number of tracks `history` and `mostplayed` list by default, and at most.
+const LIST_DEFAULT: usize = 10;
+const LIST_LIMIT: usize = 25;
+
+pub struct HistoryContainer;
+
+impl TypeMapKey for HistoryContainer {
+    type Value = Arc<RwLock<PlayHistory>>;
+}
+
+async fn history_lock(ctx: &Context) -> Arc<RwLock<PlayHistory>> {
+    let data_read = ctx.data.read().await;
+    data_read.get::<HistoryContainer>().expect("Expected History in TypeMap ;(").clone()
+}
+
+/// Reads the optional number of tracks to list.
+fn list_count(args: &mut Args) -> usize {
+    args.single::<usize>().unwrap_or(LIST_DEFAULT).min(LIST_LIMIT).max(1)
+}
+
+/// Lists the last tracks played in the guild, the latest first.
+#[command]
+#[only_in(guilds)]
+async fn history(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+    let count = list_count(&mut args);
+
+    let history_lock = history_lock(ctx).await;
+    let history = history_lock.read().await;
+    let mut recent = history.recent(guild_id.0).take(count).peekable();
+
+    if recent.peek().is_none() {
+        msg.channel_id.say(&ctx.http, "🙊 Nothing has been played here yet.").await?;
+        return Ok(());
+    }
+
+    let mut ans = MessageBuilder::new();
+    ans.push_line("Last played tracks:");
+    for (i, played) in recent.enumerate() {
+        ans.push(format!("{}. ", i + 1)).push_bold_safe(&played.track.title)
+           .push(" - ").push_safe(&played.track.author)
+           .push(format!(" (<t:{}:R>", played.time));
+        if let Some(requester) = played.requester {
+            ans.push(format!(", by <@{}>", requester));
+        }
+        ans.push(")\n");
+    }
+    ans.push("Play one of them again with ").push_mono("replay <n>").push(".");
+
+    msg.channel_id.say(&ctx.http, ans.build()).await?;
+    Ok(())
+}
+
+/// Queues again the `n`-th last track played in the guild.
+#[command]
+#[only_in(guilds)]
+#[checks(Music)]
+#[num_args(1)]
+async fn replay(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+    let position = args.single::<usize>()?;
+
+    let saved = {
+        let history_lock = history_lock(ctx).await;
+        let history = history_lock.read().await;
+        match history.recent(guild_id.0).nth(position.wrapping_sub(1)) {
+            Some(played) => played.track.clone(),
+            None => {
+                msg.channel_id.say(&ctx.http, format!("😮 There is no track {} in the history.", position)).await?;
+                return Ok(());
+            },
+        }
+    };
+
+    if !connect_to_author(ctx, msg, guild_id).await? {
+        return Ok(());
+    }
+    let tracks = admit_tracks(ctx, msg, guild_id, vec![to_track(&saved)]).await?;
+    if tracks.is_empty() || !queue_tracks(ctx, msg, guild_id, &tracks).await? {
+        return Ok(());
+    }
+
+    let ans = MessageBuilder::new()
+        .push("Added to queue again: ").push_bold_safe(&saved.title)
+        .push(" 🔁").build();
+    msg.channel_id.say(&ctx.http, ans).await?;
+    Ok(())
+}
+
+/// Lists the tracks played the most in the guild.
+#[command]
+#[aliases(top)]
+#[only_in(guilds)]
+async fn mostplayed(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+    let count = list_count(&mut args);
+
+    let history_lock = history_lock(ctx).await;
+    let history = history_lock.read().await;
+    let most_played = history.most_played(guild_id.0, count);
+
+    if most_played.is_empty() {
+        msg.channel_id.say(&ctx.http, "🙊 Nothing has been played here yet.").await?;
+        return Ok(());
+    }
+
+    let mut ans = MessageBuilder::new();
+    ans.push_line("Most played tracks:");
+    for (i, (track, plays)) in most_played.into_iter().enumerate() {
+        ans.push(format!("{}. ", i + 1)).push_bold_safe(&track.title)
+           .push(" - ").push_safe(&track.author)
+           .push(format!(" ({} plays)\n", plays));
+    }
+
+    msg.channel_id.say(&ctx.http, ans.build()).await?;
+    Ok(())
+}
+
+fn save_history(filename: &str, history: &PlayHistory) -> Result<(), IOError> {
+    let mut file = File::create(filename)?;
+    file.write_all(history.to_string().as_bytes())?;
+    Ok(())
+}
+
+/// Adds a track played in `guild_id` at the end of the saved history, not to
+/// write it all again on every track.
+pub fn append_history(filename: &str, guild_id: u64, played: &PlayedTrack) -> Result<(), IOError> {
+    let mut file = OpenOptions::new().create(true).append(true).open(filename)?;
+    file.write_all(appended(guild_id, played).as_bytes())?;
+    Ok(())
+}
+
+/// Reads the play history, no file meaning nothing played yet. The file is
+/// written again without the tracks beyond the limit, which appending kept.
+pub fn load_history(filename: &str) -> Result<PlayHistory, IOError> {
+    let data = match fs::read_to_string(filename) {
+        Ok(data) => data,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(PlayHistory::new()),
+        Err(e) => return Err(e),
+    };
+
+    let history = PlayHistory::from_str(&data)
+        .map_err(|s| IOError::new(ErrorKind::Other, s))?;
+    if data.lines().filter(|line| line.starts_with('\t')).count() > history.count() {
+        save_history(filename, &history)?;
+    }
+    Ok(history)
+}
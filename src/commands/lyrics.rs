@@ -0,0 +1,71 @@
+use serenity::{
+    prelude::Context,
+    model::prelude::Message,
+    framework::standard::{
+        Args, CommandResult, macros::command
+    },
+    utils::MessageBuilder,
+};
+
+use super::player::current_track;
+use crate::lyrics::{paginate, LyricsContainer};
+
+/// Length of the lyrics shown by each embed, under the limit of their description.
+const PAGE_LENGTH: usize = 2000;
+/// Number of embeds sent at most, not to flood the channel.
+const MAX_PAGES: usize = 5;
+
+#[command]
+async fn lyrics(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    // without a query, look for the lyrics of what is playing
+    let query = if args.is_empty() {
+        let current = match msg.guild_id {
+            Some(guild_id) => current_track(ctx, guild_id).await,
+            None => None,
+        };
+        match current.and_then(|(track, _)| track.info) {
+            Some(info) => info.title,
+            None => {
+                msg.channel_id.say(&ctx.http, "Nothing is playing, tell me which song you want the lyrics of.").await?;
+                return Ok(());
+            },
+        }
+    } else {
+        args.rest().to_string()
+    };
+
+    let provider = ctx.data.read().await.get::<LyricsContainer>().cloned()
+        .expect("Expected a lyrics provider in TypeMap");
+    let lyrics = match provider.search(&query).await {
+        Ok(Some(lyrics)) => lyrics,
+        Ok(None) => {
+            let ans = MessageBuilder::new()
+                .push("🙊 No lyrics found for ").push_bold_safe(&query)
+                .push(".").build();
+            msg.channel_id.say(&ctx.http, ans).await?;
+            return Ok(());
+        },
+        Err(why) => {
+            eprintln!("Error looking for lyrics: {}", why);
+            msg.channel_id.say(&ctx.http, "Could not look for the lyrics 😕.").await?;
+            return Ok(());
+        },
+    };
+
+    let pages = paginate(&lyrics.text, PAGE_LENGTH);
+    let count = pages.len();
+    for (i, page) in pages.into_iter().take(MAX_PAGES).enumerate() {
+        msg.channel_id.send_message(&ctx.http, |m| m.embed(|e| {
+            if i == 0 {
+                e.title(&lyrics.title);
+            }
+            e.description(page)
+             .footer(|f| f.text(format!("{} - {}/{}", provider.name(), i + 1, count)))
+        })).await?;
+    }
+    if count > MAX_PAGES {
+        msg.channel_id.say(&ctx.http, format!("... and {} more pages.", count - MAX_PAGES)).await?;
+    }
+
+    Ok(())
+}
@@ -1,52 +1,570 @@
+use std::str::FromStr;
+use std::fs::{self, File};
+use std::io::{Write, Error as IOError, ErrorKind};
+use std::sync::Arc;
+
 use serenity::{
     client::Context,
+    prelude::{RwLock, TypeMapKey},
     model::prelude::*,
     framework::standard::{
-        Args, CommandResult, macros::command
-    }
+        Args, CommandError, CommandResult, macros::command
+    },
+    utils::MessageBuilder,
 };
+use tokio::task;
+
+use super::utils::csv;
+use super::utils::exact::{BigInt, Rational};
+use super::utils::expr::{Arithmetic, CONVERT};
+use super::utils::functions::{CONSTANTS, FUNCTIONS};
+use super::utils::integer::Width;
+use super::utils::plot::{Plot, VARIABLE};
+use super::utils::scope::{Number, Outcome, Scopes, Value};
+use super::utils::stats::Summary;
+use super::utils::units::UNITS;
+
+pub const SCOPES_FILE_PATH: &str = "data/scopes.txt";
+
+/// Attached files larger than this are not read for numbers, in bytes.
+const MAX_ATTACHMENT_SIZE: u64 = 1 << 20;
+
+/// Discord refuses longer messages, in characters.
+const MAX_MESSAGE_LENGTH: usize = 2000;
+
+/// The variables and functions of the users of `compute`.
+pub struct CalcScopes;
+
+impl TypeMapKey for CalcScopes {
+    type Value = Arc<RwLock<Scopes>>;
+}
+
+async fn scopes_lock(ctx: &Context) -> Arc<RwLock<Scopes>> {
+    let data_read = ctx.data.read().await;
+    data_read.get::<CalcScopes>().expect("Expected CalcScopes in TypeMap ;(").clone()
+}
+
+/// Writes the scopes to disk, telling the author of `msg` if it failed.
+async fn store(ctx: &Context, msg: &Message, scopes: &Scopes) -> CommandResult {
+    if let Err(why) = save_scopes(SCOPES_FILE_PATH, scopes) {
+        eprintln!("Could not save the calculator scopes: {}", why);
+        msg.channel_id.say(&ctx.http, "Could not write the variables file, they will be lost on restart 😕.").await?;
+    }
+    Ok(())
+}
 
 #[command]
 pub async fn multiply(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let one = args.single::<f64>()?;
-    let two = args.single::<f64>()?;
+    let one = args.single::<Rational>()?;
+    let two = args.single::<Rational>()?;
 
-    let product = one * two;
+    let product = one.mul(&two);
 
-    msg.channel_id.say(&ctx.http, product).await?;
+    say_number(ctx, msg, product).await
+}
+
+#[command]
+pub async fn add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let one = args.single::<Rational>()?;
+    let two = args.single::<Rational>()?;
 
+    let sum = one.add(&two);
+
+    say_number(ctx, msg, sum).await
+}
+
+/// Replies `number`, unless it is too long for a message.
+async fn say_number(ctx: &Context, msg: &Message, number: Rational) -> CommandResult {
+    let shown = number.pretty();
+    if shown.chars().count() > MAX_MESSAGE_LENGTH {
+        msg.channel_id.say(&ctx.http, "The result is too long to be shown 😕").await?;
+    } else {
+        msg.channel_id.say(&ctx.http, shown).await?;
+    }
     Ok(())
 }
 
+/// Reads the numbers given to a command, separated by spaces or commas, and
+/// those of a column of the CSV files attached, chosen with `--column`.
+/// Replies `usage` or why they could not be read if there is none.
+async fn numbers<T: FromStr>(ctx: &Context, msg: &Message, mut args: Args, usage: &str)
+    -> Result<Option<Vec<T>>, CommandError> {
+    let mut numbers = Vec::new();
+    let mut column = None;
+    let mut problem = None;
+    while problem.is_none() && !args.is_empty() {
+        let arg = args.single_quoted::<String>()?;
+        if arg == "--column" {
+            match args.single_quoted::<String>() {
+                Ok(name) => column = Some(name),
+                Err(_) => problem = Some(String::from("expected a column name or number after `--column`")),
+            }
+            continue;
+        }
+        for word in arg.split(',').filter(|word| !word.is_empty()) {
+            match word.parse() {
+                Ok(number) => numbers.push(number),
+                Err(_) => {
+                    problem = Some(format!("`{}` is not a number", word));
+                    break;
+                },
+            }
+        }
+    }
+
+    for attachment in &msg.attachments {
+        if problem.is_some() {
+            break;
+        }
+        if attachment.size > MAX_ATTACHMENT_SIZE {
+            problem = Some(format!("`{}` is too large", attachment.filename));
+            break;
+        }
+        let data = match attachment.download().await.map(String::from_utf8) {
+            Ok(Ok(data)) => data,
+            Ok(Err(_)) => {
+                problem = Some(format!("`{}` is not text", attachment.filename));
+                break;
+            },
+            Err(why) => {
+                eprintln!("Could not download {}: {}", attachment.url, why);
+                problem = Some(format!("`{}` could not be downloaded", attachment.filename));
+                break;
+            },
+        };
+        match csv::column(&data, column.as_deref()) {
+            Ok(column) => numbers.extend(column),
+            Err(why) => problem = Some(format!("in `{}`, {}", attachment.filename, why)),
+        }
+    }
+
+    match problem {
+        Some(why) => {
+            let ans = MessageBuilder::new().push("Could not read the numbers 🤔: ").push_safe(why).build();
+            msg.channel_id.say(&ctx.http, ans).await?;
+            Ok(None)
+        },
+        None if numbers.is_empty() => {
+            msg.channel_id.say(&ctx.http, usage).await?;
+            Ok(None)
+        },
+        None => Ok(Some(numbers)),
+    }
+}
+
 #[command]
-pub async fn add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
-    let one = args.single::<f64>()?;
-    let two = args.single::<f64>()?;
+#[description("Sums numbers, written or in a column of attached CSV files chosen with `--column <name|number>`.")]
+pub async fn sum(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let usage = "Usage: `sum <numbers...> [--column <name|number>]`, with CSV files attached or not";
+    let numbers = match numbers::<Rational>(ctx, msg, args, usage).await? {
+        Some(numbers) => numbers,
+        None => return Ok(()),
+    };
 
-    let sum = one + two;
+    let sum = numbers.iter().fold(Rational::integer(BigInt::zero()), |sum, number| sum.add(number));
+    msg.channel_id.say(&ctx.http, sum.pretty()).await?;
+    Ok(())
+}
 
-    msg.channel_id.say(&ctx.http, sum).await?;
+#[command]
+#[description("Multiplies numbers, written or in a column of attached CSV files chosen with `--column <name|number>`.")]
+pub async fn product(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let usage = "Usage: `product <numbers...> [--column <name|number>]`, with CSV files attached or not";
+    let numbers = match numbers::<Rational>(ctx, msg, args, usage).await? {
+        Some(numbers) => numbers,
+        None => return Ok(()),
+    };
 
+    let mut product = Rational::integer(BigInt::from(1));
+    for number in &numbers {
+        product = product.mul(number);
+        if let Err(why) = product.check() {
+            msg.channel_id.say(&ctx.http, format!("Could not compute this 🤔: {}", why)).await?;
+            return Ok(());
+        }
+    }
+    msg.channel_id.say(&ctx.http, product.pretty()).await?;
     Ok(())
 }
 
 #[command]
+#[description("Statistics of numbers, written or in a column of attached CSV files chosen with `--column <name|number>`.")]
+pub async fn stats(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let usage = "Usage: `stats <numbers...> [--column <name|number>]`, with CSV files attached or not";
+    let numbers = match numbers::<f64>(ctx, msg, args, usage).await? {
+        Some(numbers) => numbers,
+        None => return Ok(()),
+    };
+
+    let ans = match Summary::new(&numbers) {
+        Ok(summary) => MessageBuilder::new().push_codeblock(summary.to_string(), None).build(),
+        Err(why) => format!("Could not compute this 🤔: {}", why),
+    };
+    msg.channel_id.say(&ctx.http, ans).await?;
+    Ok(())
+}
+
+/// Lists what `compute` understands, for `help compute`.
+pub fn compute_help() -> String {
+    let mut ans = MessageBuilder::new();
+    ans.push_line("`compute <expression>` evaluates an expression with `+ - * / % ^`, \
+                   parentheses, `!` factorials and the following.")
+       .push_line("`compute x = <expression>` assigns a variable and `compute f(a, b) = <expression>` \
+                   defines a function, yours only, `ans` being your previous result. \
+                   See them with `vars`, and `forget` them.")
+       .push_line("`compute --exact <expression>` computes with exact fractions and integers of any size, \
+                   which is done whenever possible for expressions written with integers only, \
+                   or for all of them after `exact on`.")
+       .push_line("Numbers can be followed by units, as in `compute 3 GiB / 20 MB/s to min`, see `help convert`.")
+       .push_line("`0xff`, `0o17` and `0b101` are integers in other bases, with the bitwise `& | << >> ~`, \
+                   see `base`. After `programmer u32`, `u64` or `i64`, integers wrap around, \
+                   `^` being a xor and `**` a power.")
+       .push_line("Matrices are written by rows, as `[[1, 2], [3, 4]]`, `[1, 2]` being a column.")
+       .push_line("`plot f(x); g(x) from a to b` draws functions of `x`, from -10 to 10 by default.")
+       .push_bold_line("Functions:");
+    for function in FUNCTIONS {
+        ans.push_mono(function.usage).push(format!(": {}\n", function.help));
+    }
+    ans.push_bold_line("Constants:");
+    for constant in CONSTANTS {
+        ans.push_mono(constant.name).push(format!(": {}\n", constant.help));
+    }
+    ans.build()
+}
+
+/// Lists the units of `compute` and `convert`, for `help convert`.
+pub fn units_help() -> String {
+    let mut ans = MessageBuilder::new();
+    ans.push_line("`convert <quantity> to <units>` converts a quantity, as in `convert 72 F to C` \
+                   or `convert 5 km/h to m/s`, units being combined with `*`, `/` and `^`.")
+       .push_line("SI prefixes go before units written in bold, binary ones as `Ki` and `Mi` \
+                   before bytes and bits.")
+       .push_bold_line("Units:");
+
+    let mut kinds: Vec<&str> = Vec::new();
+    for unit in UNITS {
+        if !kinds.contains(&unit.kind) {
+            kinds.push(unit.kind);
+        }
+    }
+    for kind in kinds {
+        ans.push(format!("{}: ", kind));
+        let units = UNITS.iter().filter(|unit| unit.kind == kind);
+        for (i, unit) in units.enumerate() {
+            if i > 0 {
+                ans.push(", ");
+            }
+            if unit.takes_prefixes() {
+                ans.push_bold(unit.names.join("/"));
+            } else {
+                ans.push(unit.names.join("/"));
+            }
+        }
+        ans.push("\n");
+    }
+    ans.build()
+}
+
+#[command]
+#[description("Evaluates an expression, see `help compute`.")]
 pub async fn compute(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let input = args.rest().trim();
+    let (exact, input) = match input.strip_prefix("--exact") {
+        Some(rest) => (true, rest.trim()),
+        None => (false, input),
+    };
+    run(ctx, msg, input, exact).await
+}
+
+#[command]
+#[description("Converts a quantity to other units, as `convert 72 F to C`, see `help convert`.")]
+pub async fn convert(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let input = args.rest().trim();
+    if !input.split_whitespace().any(|word| word == CONVERT) {
+        msg.channel_id.say(&ctx.http, "Usage: `convert <quantity> to <units>`").await?;
+        return Ok(());
+    }
+    run(ctx, msg, input, false).await
+}
+
+/// An integer result of programmer mode in decimal, hexadecimal, octal and
+/// binary, as `` `255` = `0xff` = `0o377` = `0b11111111` ``.
+fn in_bases(value: &Value, programmer: Option<Width>) -> Option<String> {
+    let width = programmer?;
+    match &value.number {
+        Number::Exact(number) if number.is_integer() && value.units.is_empty() => {
+            let bases = width.bases(number.numerator().to_i128()?);
+            Some(bases.iter().map(|base| format!("`{}`", base)).collect::<Vec<String>>().join(" = "))
+        },
+        _ => None,
+    }
+}
 
-    let all = args.raw().collect::<Vec<&str>>().join(" ");
-    let terms: Vec<&str> = all.split("+").collect();
-    let mut sum : f64 = 0.0;
-    
-    for term in terms {
-        let factors: Vec<&str> = term.split("*").collect();
-        let mut product : f64 = 1.0;
-        for factor in factors {
-            product *= factor.trim().parse::<f64>()?;
+/// `n` in `radix`, after the prefix of its base, as `-0xff`.
+fn prefixed(n: &BigInt, radix: u32, prefix: &str) -> String {
+    let digits = n.to_str_radix(radix);
+    match digits.strip_prefix('-') {
+        Some(digits) => format!("-{}{}", prefix, digits),
+        None => format!("{}{}", prefix, digits),
+    }
+}
+
+#[command]
+#[description("Writes an integer in the bases given, from 2 to 36, as `base 255 16`, \
+               or else in decimal, hexadecimal, octal and binary.")]
+pub async fn base(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let usage = "Usage: `base <integer> [bases...]`, as `base 0xff` or `base 255 2 16`";
+    let n = match args.single::<Rational>() {
+        Ok(n) if n.is_integer() => n.numerator().clone(),
+        _ => {
+            msg.channel_id.say(&ctx.http, usage).await?;
+            return Ok(());
+        },
+    };
+    let mut radixes = Vec::new();
+    while !args.is_empty() {
+        match args.single::<u32>() {
+            Ok(radix) if (2..=36).contains(&radix) => radixes.push(radix),
+            _ => {
+                msg.channel_id.say(&ctx.http, usage).await?;
+                return Ok(());
+            },
         }
-        sum += product;
     }
 
-    msg.channel_id.say(&ctx.http, format!("It makes: `{}`", sum)).await?;
+    let ans = if radixes.is_empty() {
+        let bases = [prefixed(&n, 10, ""), prefixed(&n, 16, "0x"), prefixed(&n, 8, "0o"), prefixed(&n, 2, "0b")];
+        bases.iter().map(|base| format!("`{}`", base)).collect::<Vec<String>>().join(" = ")
+    } else {
+        let mut ans = MessageBuilder::new();
+        for radix in radixes {
+            ans.push(format!("In base {}: ", radix)).push_mono_line(n.to_str_radix(radix));
+        }
+        ans.build()
+    };
+    if ans.chars().count() > MAX_MESSAGE_LENGTH {
+        msg.channel_id.say(&ctx.http, "This integer is too long to be shown in these bases 😕").await?;
+    } else {
+        msg.channel_id.say(&ctx.http, ans).await?;
+    }
+    Ok(())
+}
+
+/// Executes `input` for the author, replying with its outcome.
+async fn run(ctx: &Context, msg: &Message, input: &str, exact: bool) -> CommandResult {
+    let scopes_lock = scopes_lock(ctx).await;
+    let mut scope = scopes_lock.read().await.users.get(&msg.author.id.0).cloned().unwrap_or_default();
+    let programmer = scope.programmer;
+
+    // large exact numbers taking a while, computed on a copy of the scope
+    // away from the other commands, the lock being free meanwhile
+    let statement = input.to_string();
+    let (scope, outcome) = task::spawn_blocking(move || {
+        let outcome = scope.execute(&statement, exact);
+        (scope, outcome)
+    }).await.map_err(|why| CommandError::from(why.to_string()))?;
+
+    let ans = match outcome {
+        // matrices in code blocks, for their columns to be aligned
+        Ok(Outcome::Value(value)) if value.number.is_matrix() => MessageBuilder::new()
+            .push_line("It makes:")
+            .push_codeblock_safe(value.to_string(), None)
+            .build(),
+        Ok(Outcome::Assigned(name, value)) if value.number.is_matrix() => MessageBuilder::new()
+            .push_mono_safe(format!("{} =", name))
+            .push_line(" ✅")
+            .push_codeblock_safe(value.to_string(), None)
+            .build(),
+        Ok(Outcome::Value(value)) => match in_bases(&value, programmer) {
+            Some(bases) => format!("It makes: {}", bases),
+            None => format!("It makes: `{}`", value),
+        },
+        Ok(Outcome::Assigned(name, value)) => match in_bases(&value, programmer) {
+            Some(bases) => format!("`{}` = {} ✅", name, bases),
+            None => format!("`{} = {}` ✅", name, value),
+        },
+        Ok(Outcome::Defined(name)) => format!("Function `{}` defined ✅", name),
+        Err(why) => {
+            let ans = MessageBuilder::new()
+                .push_line("Could not compute this 🤔:")
+                .push_codeblock_safe(why.pointer(input), None)
+                .build();
+            msg.channel_id.say(&ctx.http, ans).await?;
+            return Ok(());
+        },
+    };
+    // `ans` changed too, the modes being left as they may be now
+    let mut scopes = scopes_lock.write().await;
+    let current = scopes.get_mut(msg.author.id.0);
+    current.variables = scope.variables;
+    current.functions = scope.functions;
+    store(ctx, msg, &scopes).await?;
+
+    let ans = if ans.chars().count() > MAX_MESSAGE_LENGTH {
+        String::from("It makes a result too long to be shown, kept as `ans` ✅")
+    } else {
+        ans
+    };
+    msg.channel_id.say(&ctx.http, ans).await?;
+    Ok(())
+}
+
+#[command]
+#[description("Draws functions of `x`, as `plot sin(x)/x; cos(x) from -10 to 10`.")]
+pub async fn plot(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
+    let input = args.rest().trim();
+    if input.is_empty() {
+        msg.channel_id.say(&ctx.http, "Usage: `plot <function of x>[; <function of x>...] [from <a> to <b>]`").await?;
+        return Ok(());
+    }
+
+    // sampled and drawn on a copy of the scope away from the other commands,
+    // as in `run`
+    let scopes_lock = scopes_lock(ctx).await;
+    let scope = scopes_lock.read().await.users.get(&msg.author.id.0).cloned().unwrap_or_default();
+    let functions = input.to_string();
+    let image = task::spawn_blocking(move || {
+        Plot::parse(&functions, &scope).and_then(|plot| plot.render(&scope))
+    }).await.map_err(|why| CommandError::from(why.to_string()))?;
 
+    match image {
+        Ok(image) => {
+            msg.channel_id.send_files(&ctx.http, vec![(image.as_slice(), "plot.png")], |m| m).await?;
+        },
+        Err(why) => {
+            let ans = MessageBuilder::new()
+                .push("Could not plot this as functions of ")
+                .push_mono(VARIABLE)
+                .push_line(" 🤔:")
+                .push_codeblock_safe(why.pointer(input), None)
+                .build();
+            msg.channel_id.say(&ctx.http, ans).await?;
+        },
+    }
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Makes `compute` exact whenever possible for the author, or not, toggling it
+/// without argument.
+#[command]
+pub async fn exact(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let scopes_lock = scopes_lock(ctx).await;
+    let mut scopes = scopes_lock.write().await;
+    let scope = scopes.get_mut(msg.author.id.0);
+    let enable = match args.current() {
+        None => !scope.prefers_exact,
+        Some(_) => match args.single::<String>()?.to_lowercase().as_str() {
+            "on" => true,
+            "off" => false,
+            _ => {
+                msg.channel_id.say(&ctx.http, "Usage: `exact [on|off]`").await?;
+                return Ok(());
+            },
+        },
+    };
+    scope.prefers_exact = enable;
+    store(ctx, msg, &scopes).await?;
+
+    if enable {
+        msg.channel_id.say(&ctx.http, "🎯 Your computations are exact whenever possible.").await?;
+    } else {
+        msg.channel_id.say(&ctx.http, "Your computations are exact only for integers.").await?;
+    }
+    Ok(())
+}
+
+/// Makes `compute` work with wrapping integers of a type for the author, or
+/// with numbers again.
+#[command]
+#[description("Makes `compute` work with integers wrapping around as `u32`, `u64` or `i64` do, \
+               or stops it with `programmer off`.")]
+pub async fn programmer(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let word = args.single::<String>().unwrap_or_default();
+    let programmer = if word.eq_ignore_ascii_case("off") {
+        None
+    } else {
+        match word.parse::<Width>() {
+            Ok(width) => Some(width),
+            Err(_) => {
+                msg.channel_id.say(&ctx.http, "Usage: `programmer <u32|u64|i64|off>`").await?;
+                return Ok(());
+            },
+        }
+    };
+
+    let scopes_lock = scopes_lock(ctx).await;
+    let mut scopes = scopes_lock.write().await;
+    scopes.get_mut(msg.author.id.0).programmer = programmer;
+    store(ctx, msg, &scopes).await?;
+
+    let ans = match programmer {
+        Some(width) => format!("💻 Your computations are done with `{}` integers wrapping around, \
+                                `^` being an exclusive or and `**` a power.", width),
+        None => String::from("Your computations are done with numbers again."),
+    };
+    msg.channel_id.say(&ctx.http, ans).await?;
+    Ok(())
+}
+
+/// Lists the variables and functions of the author.
+#[command]
+pub async fn vars(ctx: &Context, msg: &Message) -> CommandResult {
+    let scopes_lock = scopes_lock(ctx).await;
+    let scopes = scopes_lock.read().await;
+
+    match scopes.users.get(&msg.author.id.0).filter(|scope| !scope.is_empty()) {
+        Some(scope) => {
+            let ans = MessageBuilder::new()
+                .push_line("Your variables and functions:")
+                .push_codeblock_safe(scope.to_string(), None)
+                .build();
+            msg.channel_id.say(&ctx.http, ans).await?;
+        },
+        None => {
+            msg.channel_id.say(&ctx.http, "🙊 No variable nor function yet.").await?;
+        },
+    }
+    Ok(())
+}
+
+/// Forgets a variable or a function of the author.
+#[command]
+#[num_args(1)]
+pub async fn forget(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let name = args.single::<String>()?;
+
+    let scopes_lock = scopes_lock(ctx).await;
+    let mut scopes = scopes_lock.write().await;
+
+    let ans = if scopes.get_mut(msg.author.id.0).forget(&name) {
+        store(ctx, msg, &scopes).await?;
+        MessageBuilder::new().push_mono_safe(&name).push(" has been forgotten ✅.").build()
+    } else {
+        MessageBuilder::new().push("😮 You have no ").push_mono_safe(&name).push(".").build()
+    };
+
+    msg.channel_id.say(&ctx.http, ans).await?;
+    Ok(())
+}
+
+fn save_scopes(filename: &str, scopes: &Scopes) -> Result<(), IOError> {
+    let mut file = File::create(filename)?;
+    file.write_all(scopes.to_string().as_bytes())?;
+    Ok(())
+}
+
+/// Reads the scopes of the users, no file meaning no variable yet. The
+/// invalid lines are skipped, so that one of them does not lose every scope.
+pub fn load_scopes(filename: &str) -> Result<Scopes, IOError> {
+    let data = match fs::read_to_string(filename) {
+        Ok(data) => data,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Scopes::new()),
+        Err(e) => return Err(e),
+    };
+
+    let (scopes, errors) = Scopes::read(&data);
+    for why in errors {
+        eprintln!("Skipped in {}: {}", filename, why);
+    }
+    Ok(scopes)
+}
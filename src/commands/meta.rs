@@ -1,12 +1,17 @@
+use std::collections::HashSet;
+
 use crate::ShardManagerContainer;
+use super::games::roll_help;
+use super::math::{compute_help, units_help};
+use super::player::save_players;
 
 use serenity::{
     prelude::Context,
-    model::prelude::Message,
+    model::prelude::{Message, UserId},
     utils::MessageBuilder,
     framework::standard::{
-        CommandResult, // Args,
-        macros::command,
+        Args, CommandGroup, CommandResult, HelpOptions, help_commands,
+        macros::{command, help},
     }
 };
 
@@ -28,6 +33,32 @@ use serenity::{
 //     Ok(())
 // }
 
+/// The usual help, but with the functions and constants `compute` knows for
+/// `help compute`, the units for `help convert` and the dice notation for
+/// `help roll`.
+#[help]
+async fn help(ctx: &Context, msg: &Message, args: Args, help_options: &'static HelpOptions,
+              groups: &[&'static CommandGroup], owners: HashSet<UserId>) -> CommandResult {
+    match args.current() {
+        Some("compute") => {
+            msg.channel_id.say(&ctx.http, compute_help()).await?;
+            return Ok(());
+        },
+        Some("convert") => {
+            msg.channel_id.say(&ctx.http, units_help()).await?;
+            return Ok(());
+        },
+        Some("roll") => {
+            msg.channel_id.say(&ctx.http, roll_help()).await?;
+            return Ok(());
+        },
+        _ => (),
+    }
+
+    let _ = help_commands::with_embeds(ctx, msg, args, help_options, groups, owners).await;
+    Ok(())
+}
+
 #[command]
 async fn ping(ctx: &Context, msg: &Message) -> CommandResult {
     msg.channel_id.say(&ctx.http, "Pong !").await?;
@@ -50,10 +81,11 @@ async fn hello(ctx: &Context, msg: &Message) -> CommandResult {
 
 #[command]
 async fn quit(ctx: &Context, msg: &Message) -> CommandResult {
-    let data = ctx.data.read().await;
+    let manager = ctx.data.read().await.get::<ShardManagerContainer>().cloned();
 
-    if let Some(manager) = data.get::<ShardManagerContainer>() {
+    if let Some(manager) = manager {
         msg.channel_id.say(&ctx.http, "See ya!").await?;
+        save_players(&ctx.data).await;
         manager.lock().await.shutdown_all().await;
     } else {
         msg.channel_id.say(&ctx.http, "There was a problem getting the shard manager").await?;
@@ -2,4 +2,8 @@ pub mod math;
 pub mod meta;
 pub mod urls;
 pub mod player;
+pub mod playlist;
+pub mod lyrics;
+pub mod history;
+pub mod games;
 pub mod utils;
\ No newline at end of file
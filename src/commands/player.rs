@@ -5,19 +5,25 @@
 
 use std::{
     sync::Arc,
-    collections::HashSet,
-    time::Duration,
+    collections::{HashMap, HashSet, VecDeque},
+    fs::{self, File},
+    io::{Write, Error as IOError, ErrorKind},
+    str::FromStr,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
 };
 
 use serenity::client::bridge::voice::ClientVoiceManager;
+use tokio::{sync::oneshot, task};
 
 use serenity::{
     async_trait,
-    prelude::{TypeMapKey, RwLock, Context, Mutex},
-    model::{channel::Message, id::GuildId},
+    http::Http,
+    prelude::{TypeMap, TypeMapKey, RwLock, Context, Mutex, Mentionable},
+    model::{channel::Message, id::{ChannelId, GuildId, UserId}},
     framework::standard::{
-        Args, CommandResult, macros::command
+        Args, CheckResult, CommandError, CommandOptions, CommandResult, macros::{check, command}
     },
+    utils::MessageBuilder,
 };
 
 use lavalink_rs::{
@@ -26,94 +32,987 @@ use lavalink_rs::{
     gateway::*,
 };
 
+use super::history::{append_history, HistoryContainer, HISTORY_FILE_PATH};
+use super::utils::{
+    history::{PlayHistory, PlayedTrack},
+    player_state::{GuildPlayer, PlayerStates, QueuedTrack},
+    playlists::SavedTrack,
+    queue_rules::{admit, fair_order, QueueRequests},
+};
+use crate::config::{ConfigContainer, NodeConfig};
+use crate::lavalink::{LavalinkNode, LavalinkNodes, NodeStatus, NODE_TIMEOUT};
+
+pub const PLAYER_STATE_FILE_PATH: &str = "data/players.txt";
+
+/// How often `watch_idle_players` looks for idle players.
+const IDLE_CHECK_PERIOD: Duration = Duration::from_secs(30);
+/// How often `watch_lavalink_nodes` checks the health of the nodes.
+const NODE_CHECK_PERIOD: Duration = Duration::from_secs(30);
+/// Number of tracks remembered per guild for autoplay not to repeat them.
+const AUTOPLAY_MEMORY: usize = 50;
+/// Time to wait for the voice server after joining a voice channel.
+const VOICE_SERVER_TIMEOUT: Duration = Duration::from_secs(10);
+/// First delay before trying again to connect to an unreachable lavalink node,
+/// doubled after each failure up to `RECONNECT_MAX_DELAY`.
+const RECONNECT_MIN_DELAY: Duration = Duration::from_secs(5);
+const RECONNECT_MAX_DELAY: Duration = Duration::from_secs(300);
+
 pub struct VoiceManager;
 pub struct Lavalink;
-pub struct VoiceGuildUpdate;
 
 impl TypeMapKey for VoiceManager {
     type Value = Arc<Mutex<ClientVoiceManager>>;
 }
 
 impl TypeMapKey for Lavalink {
-    type Value = Arc<Mutex<LavalinkClient>>;
+    type Value = Arc<RwLock<LavalinkNodes>>;
 }
 
-impl TypeMapKey for VoiceGuildUpdate {
+/// Joins waiting for discord to send the voice server of their guild, which
+/// completes the voice connection.
+pub struct VoiceServerWaiters;
+
+impl TypeMapKey for VoiceServerWaiters {
+    type Value = Arc<Mutex<HashMap<GuildId, oneshot::Sender<()>>>>;
+}
+
+/// Where and by whom a track has been requested, to announce it once it starts.
+#[derive(Clone, Copy)]
+pub struct TrackRequest {
+    pub channel_id: ChannelId,
+    pub requester: UserId,
+    /// Whether autoplay queued the track, after one `requester` asked for.
+    pub autoplay: bool,
+}
+
+/// Requests of the queued tracks of each guild, by lavalink track.
+pub struct TrackRequests;
+
+impl TypeMapKey for TrackRequests {
+    type Value = Arc<RwLock<HashMap<GuildId, QueueRequests<TrackRequest>>>>;
+}
+
+/// The listeners who voted to skip the current track of a guild.
+pub struct SkipVote {
+    pub track: String,
+    pub voters: HashSet<UserId>,
+}
+
+/// Votes to skip the current track of each guild.
+pub struct SkipVotes;
+
+impl TypeMapKey for SkipVotes {
+    type Value = Arc<RwLock<HashMap<GuildId, SkipVote>>>;
+}
+
+/// Guilds where related tracks are queued when the queue runs dry.
+pub struct Autoplay;
+
+impl TypeMapKey for Autoplay {
     type Value = Arc<RwLock<HashSet<GuildId>>>;
 }
 
-pub struct LavalinkHandler;
+/// Last tracks started in each guild, the latest last.
+pub struct PlayedTracks;
+
+impl TypeMapKey for PlayedTracks {
+    type Value = Arc<RwLock<HashMap<GuildId, VecDeque<Track>>>>;
+}
+
+/// Position of the current track of each guild, in milliseconds, as lavalink
+/// last reported it.
+pub struct PlayerPositions;
+
+impl TypeMapKey for PlayerPositions {
+    type Value = Arc<RwLock<HashMap<GuildId, u64>>>;
+}
+
+pub struct LavalinkHandler {
+    pub http: Arc<Http>,
+    pub requests: Arc<RwLock<HashMap<GuildId, QueueRequests<TrackRequest>>>>,
+    pub positions: Arc<RwLock<HashMap<GuildId, u64>>>,
+    pub autoplay: Arc<RwLock<HashSet<GuildId>>>,
+    pub played: Arc<RwLock<HashMap<GuildId, VecDeque<Track>>>>,
+    pub history: Arc<RwLock<PlayHistory>>,
+    /// Status of the node this handler receives the events of.
+    pub status: Arc<RwLock<NodeStatus>>,
+}
+
+impl LavalinkHandler {
+    /// The request of the first `track` of the queue of `guild_id`, the one
+    /// playing.
+    async fn request_of(&self, guild_id: GuildId, track: &str) -> Option<TrackRequest> {
+        self.requests.read().await
+            .get(&guild_id)
+            .and_then(|requests| requests.first(track))
+    }
+
+    async fn forget_request(&self, guild_id: GuildId, track: &str) {
+        if let Some(requests) = self.requests.write().await.get_mut(&guild_id) {
+            requests.pop(track);
+        }
+    }
+
+    /// Tells the requester of a track that could not be played why, and skips it.
+    async fn skip_failed(&self, client: Arc<Mutex<LavalinkClient>>, guild_id: GuildId, track: &str, why: &str) {
+        if let Some(request) = self.request_of(guild_id, track).await {
+            let ans = MessageBuilder::new()
+                .push("⚠️ Could not play the track requested by ").mention(&request.requester)
+                .push(": ")                                       .push_mono_safe(why)
+                .push(". Skipping it.")                           .build();
+            if let Err(why) = request.channel_id.say(&self.http, ans).await {
+                eprintln!("Error announcing a track failure: {}", why);
+            }
+        }
+
+        self.forget_request(guild_id, track).await;
+        client.lock().await.skip(guild_id.0).await;
+    }
+
+    /// Adds a track which just started to the history of `guild_id`, on disk too.
+    async fn record_played(&self, guild_id: GuildId, track: &Track, request: Option<TrackRequest>) {
+        let saved = match to_saved(track) {
+            Some(saved) => saved,
+            None => return,
+        };
+        let time = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs());
+
+        let played = PlayedTrack {
+            time,
+            requester: request.map(|request| request.requester.0),
+            track: saved,
+        };
+        self.history.write().await.record(guild_id.0, played.clone());
+
+        // appended apart from the events, which need not wait for the disk
+        task::spawn_blocking(move || {
+            if let Err(why) = append_history(HISTORY_FILE_PATH, guild_id.0, &played) {
+                eprintln!("Could not save the play history: {}", why);
+            }
+        });
+    }
+
+    /// Queues a track related to the last ones of `guild_id` if autoplay is on
+    /// there and nothing else is queued, announcing it in `request`'s channel.
+    async fn autoplay_next(&self, client: Arc<Mutex<LavalinkClient>>, guild_id: GuildId, finished: &str, request: TrackRequest) {
+        if !self.autoplay.read().await.contains(&guild_id) {
+            return;
+        }
+        let queue_empty = client.lock().await.nodes.get(&guild_id.0)
+            .map_or(true, |node| node.queue.iter().all(|queued| queued.track.track == finished));
+        if !queue_empty {
+            return;
+        }
+
+        let played: Vec<Track> = self.played.read().await
+            .get(&guild_id)
+            .map(|played| played.iter().cloned().collect())
+            .unwrap_or_default();
+        let seed = match played.last().and_then(|track| track.info.as_ref()) {
+            Some(info) => info,
+            None => return,
+        };
+
+        // the youtube mix of a video lists related ones
+        let query = if thumbnail_url(seed).is_some() {
+            format!("https://www.youtube.com/watch?v={0}&list=RD{0}", seed.identifier)
+        } else {
+            format!("ytsearch:{}", seed.author)
+        };
+        let found = match client.lock().await.auto_search_tracks(&query).await {
+            Ok(found) => found.tracks,
+            Err(why) => {
+                eprintln!("Error looking for a track to autoplay in guild {}: {}", guild_id, why);
+                Vec::new()
+            },
+        };
+
+        // never a track played lately
+        let next = found.into_iter().find(|track| match &track.info {
+            Some(info) => !played.iter().any(|played| played.info.as_ref()
+                                                     .map_or(false, |played| played.identifier == info.identifier)),
+            None => false,
+        });
+        let next = match next {
+            Some(next) => next,
+            None => {
+                if let Err(why) = request.channel_id.say(&self.http, "📻 Autoplay found nothing new to play, add some tracks!").await {
+                    eprintln!("Error announcing the end of autoplay: {}", why);
+                }
+                return;
+            },
+        };
+
+        self.requests.write().await
+            .entry(guild_id).or_default()
+            .push(&next.track, TrackRequest { autoplay: true, ..request });
+        if let Err(why) = LavalinkClient::play(guild_id, next).queue(client).await {
+            eprintln!("Error autoplaying in guild {}: {}", guild_id, why);
+        }
+    }
+}
 
 #[async_trait]
 impl LavalinkEventHandler for LavalinkHandler {
-    async fn track_start(&self, _client: Arc<Mutex<LavalinkClient>>, event: TrackStart) {
-        println!("Track started!\nGuild: {}", event.guild_id);
+    async fn stats(&self, _client: Arc<Mutex<LavalinkClient>>, event: Stats) {
+        self.status.write().await.update(&event);
+    }
+
+    async fn player_update(&self, _client: Arc<Mutex<LavalinkClient>>, event: PlayerUpdate) {
+        self.positions.write().await.insert(GuildId(event.guild_id), event.state.position);
     }
-    async fn track_finish(&self, _client: Arc<Mutex<LavalinkClient>>, event: TrackFinish) {
-        println!("Track finished!\nGuild: {}", event.guild_id);
+
+    async fn track_start(&self, client: Arc<Mutex<LavalinkClient>>, event: TrackStart) {
+        let guild_id = GuildId(event.guild_id);
+        self.positions.write().await.insert(guild_id, 0);
+
+        let track = {
+            let lava_client = client.lock().await;
+            match lava_client.nodes.get(&event.guild_id)
+                                   .and_then(|node| node.now_playing.as_ref()) {
+                Some(playing) => playing.track.clone(),
+                None => return,
+            }
+        };
+        {
+            let mut played = self.played.write().await;
+            let played = played.entry(guild_id).or_default();
+            played.push_back(track.clone());
+            if played.len() > AUTOPLAY_MEMORY {
+                played.pop_front();
+            }
+        }
+
+        let request = self.request_of(guild_id, &event.track).await;
+        self.record_played(guild_id, &track, request).await;
+
+        let request = match request {
+            Some(request) => request,
+            None => return,
+        };
+        let info = match track.info {
+            Some(info) => info,
+            None => return,
+        };
+        let requested_by = if request.autoplay {
+            format!("📻 Autoplay, after {}", request.requester.mention())
+        } else {
+            request.requester.mention().to_string()
+        };
+
+        let sent = request.channel_id.send_message(&self.http, |m| m.embed(|e| {
+            e.title("Now playing")
+             .description(format!("[{}]({})", info.title, info.uri))
+             .field("Author", &info.author, true)
+             .field("Duration", format_duration(info.length), true)
+             .field("Requested by", requested_by, true);
+            if let Some(thumbnail) = thumbnail_url(&info) {
+                e.thumbnail(thumbnail);
+            }
+            e
+        })).await;
+
+        if let Err(why) = sent {
+            eprintln!("Error announcing the track started in guild {}: {}", guild_id, why);
+        }
+    }
+
+    async fn track_finish(&self, client: Arc<Mutex<LavalinkClient>>, event: TrackFinish) {
+        let guild_id = GuildId(event.guild_id);
+        let request = self.request_of(guild_id, &event.track).await;
+        self.forget_request(guild_id, &event.track).await;
+
+        // not when the music has been stopped or the track replaced
+        if let (Some(request), "FINISHED") = (request, event.reason.as_str()) {
+            self.autoplay_next(client, guild_id, &event.track, request).await;
+        }
+    }
+
+    async fn track_exception(&self, client: Arc<Mutex<LavalinkClient>>, event: TrackException) {
+        self.skip_failed(client, GuildId(event.guild_id), &event.track, &event.error).await;
+    }
+
+    async fn track_stuck(&self, client: Arc<Mutex<LavalinkClient>>, event: TrackStuck) {
+        let why = format!("stuck for {} ms", event.threshold_ms);
+        self.skip_failed(client, GuildId(event.guild_id), &event.track, &why).await;
     }
 }
 
-#[command]
-async fn join(ctx: &Context, msg: &Message) -> CommandResult {
-    // Get guild id
-    let guild = msg.guild(&ctx.cache).await.expect("whoops, could not get guild");
-    let guild_id = guild.id;
+/// Keeps what is needed to queue `track` again without searching it.
+pub fn to_saved(track: &Track) -> Option<SavedTrack> {
+    let info = track.info.as_ref()?;
+    Some(SavedTrack {
+        track: track.track.clone(),
+        identifier: info.identifier.clone(),
+        title: info.title.clone(),
+        author: info.author.clone(),
+        uri: info.uri.clone(),
+        length: info.length,
+        is_stream: info.is_stream,
+        is_seekable: info.is_seekable,
+    })
+}
 
-    let channel_id = guild
-        .voice_states.get(&msg.author.id)
-        .and_then(|voice_state| voice_state.channel_id);
+pub fn to_track(saved: &SavedTrack) -> Track {
+    Track {
+        track: saved.track.clone(),
+        info: Some(Info {
+            identifier: saved.identifier.clone(),
+            is_seekable: saved.is_seekable,
+            author: saved.author.clone(),
+            length: saved.length,
+            is_stream: saved.is_stream,
+            position: 0,
+            title: saved.title.clone(),
+            uri: saved.uri.clone(),
+        }),
+    }
+}
 
-    // Get channel to connect id
-    let connect_to = match channel_id {
+/// Returns the current track of `node` followed by the rest of its queue.
+pub fn queued_tracks(node: &Node) -> Vec<&Track> {
+    let mut tracks: Vec<&Track> = node.queue.iter().map(|queued| &queued.track).collect();
+    if let Some(playing) = &node.now_playing {
+        if tracks.first().map(|track| &track.track) != Some(&playing.track.track) {
+            tracks.insert(0, &playing.track);
+        }
+    }
+    tracks
+}
+
+/// Formats a duration in milliseconds as `m:ss`, or `h:mm:ss` for long ones.
+fn format_duration(ms: u64) -> String {
+    let secs = ms / 1000;
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+/// Returns the url of the thumbnail of a youtube track.
+fn thumbnail_url(info: &Info) -> Option<String> {
+    if info.uri.contains("youtube.com") || info.uri.contains("youtu.be") {
+        Some(format!("https://img.youtube.com/vi/{}/hqdefault.jpg", info.identifier))
+    } else {
+        None
+    }
+}
+
+/// Returns the lavalink client playing for `guild_id`, assigning the guild to
+/// the least loaded healthy node if none plays for it yet.
+pub async fn lavalink_for(ctx: &Context, guild_id: GuildId) -> Result<Arc<Mutex<LavalinkClient>>, CommandError> {
+    let region = guild_id.to_guild_cached(&ctx.cache).await.map(|guild| guild.region);
+    let nodes_lock = ctx.data.read().await.get::<Lavalink>().cloned()
+        .expect("Expected lavalink nodes in TypeMap");
+    let mut nodes = nodes_lock.write().await;
+
+    nodes.assign(guild_id, region.as_deref()).await
+        .ok_or_else(|| CommandError::from("No lavalink node is available"))
+}
+
+/// Returns the lavalink client playing for `guild_id`, if any.
+pub async fn current_lavalink(data: &RwLock<TypeMap>, guild_id: GuildId) -> Option<Arc<Mutex<LavalinkClient>>> {
+    let nodes_lock = data.read().await.get::<Lavalink>().cloned()
+        .expect("Expected lavalink nodes in TypeMap");
+    let nodes = nodes_lock.read().await;
+
+    nodes.get(guild_id)
+}
+
+/// Returns the least loaded healthy lavalink client, to search tracks with.
+pub async fn any_lavalink(ctx: &Context) -> Result<Arc<Mutex<LavalinkClient>>, CommandError> {
+    let nodes_lock = ctx.data.read().await.get::<Lavalink>().cloned()
+        .expect("Expected lavalink nodes in TypeMap");
+    let nodes = nodes_lock.read().await;
+
+    match nodes.least_loaded(None).await {
+        Some(index) => Ok(Arc::clone(&nodes.nodes[index].client)),
+        None => Err(CommandError::from("No lavalink node is available")),
+    }
+}
+
+/// Whether a lavalink node is available to play music.
+pub async fn lavalink_available(data: &RwLock<TypeMap>) -> bool {
+    let nodes_lock = data.read().await.get::<Lavalink>().cloned()
+        .expect("Expected lavalink nodes in TypeMap");
+    let nodes = nodes_lock.read().await;
+
+    nodes.is_available().await
+}
+
+/// Tells the author the music is unavailable, rather than running a command
+/// needing lavalink while no node is reachable.
+#[check]
+#[name = "Music"]
+pub async fn music_check(ctx: &Context, msg: &Message, _: &mut Args, _: &CommandOptions) -> CheckResult {
+    if lavalink_available(&ctx.data).await {
+        return true.into();
+    }
+
+    let ans = "🔇 The music is unavailable for now, I cannot reach the audio server. \
+               I keep trying to reconnect, try again in a few minutes!";
+    if let Err(why) = msg.channel_id.say(&ctx.http, ans).await {
+        eprintln!("Error telling the music is unavailable: {}", why);
+    }
+    false.into()
+}
+
+/// Returns the voice channel `user_id` is connected to in `guild_id`, if any.
+async fn user_voice_channel(ctx: &Context, guild_id: GuildId, user_id: UserId) -> Option<ChannelId> {
+    let guild = guild_id.to_guild_cached(&ctx.cache).await?;
+
+    guild.voice_states.get(&user_id)
+        .and_then(|voice_state| voice_state.channel_id)
+}
+
+/// Returns the voice channel the bot is connected to in `guild_id`, if any.
+async fn bot_voice_channel(ctx: &Context, guild_id: GuildId) -> Option<ChannelId> {
+    let manager_lock = ctx.data.read().await.get::<VoiceManager>().cloned()
+        .expect("Expected VoiceManager in TypeMap.");
+    let manager = manager_lock.lock().await;
+
+    manager.get(guild_id).and_then(|handler| handler.channel_id)
+}
+
+/// Tells whether the author of `msg` may control the playback, that is if they
+/// listen in the voice channel the bot is connected to. Answers them otherwise.
+async fn in_bot_channel(ctx: &Context, msg: &Message, guild_id: GuildId) -> Result<bool, CommandError> {
+    let bot_channel = match bot_voice_channel(ctx, guild_id).await {
         Some(channel) => channel,
-        None => {
-            msg.channel_id.say(&ctx.http, "Not in a voice channel to play in!").await?;
+        None => return Ok(true),
+    };
 
-            return Ok(());
-        }
+    if user_voice_channel(ctx, guild_id, msg.author.id).await == Some(bot_channel) {
+        Ok(true)
+    } else {
+        msg.channel_id.say(&ctx.http, "You must be in my voice channel to control the music.").await?;
+        Ok(false)
+    }
+}
+
+/// Connects the bot to `channel_id` and creates the lavalink session of the
+/// guild. Returns `false` if the voice manager could not join the channel, if
+/// discord did not send the voice server in time, or if the bot was
+/// disconnected before the session was created.
+async fn join_channel(ctx: &Context, guild_id: GuildId, channel_id: ChannelId) -> Result<bool, CommandError> {
+    let (manager_lock, waiters_lock) = {
+        let data = ctx.data.read().await;
+        (data.get::<VoiceManager>().expect("Expected VoiceManager in TypeMap.").clone(),
+         data.get::<VoiceServerWaiters>().expect("Expected VoiceServerWaiters in TypeMap.").clone())
+    };
+
+    // wait for the voice server before joining, not to miss it
+    let (sender, receiver) = oneshot::channel();
+    waiters_lock.lock().await.insert(guild_id, sender);
+
+    let (was_connected, has_joined) = {
+        let mut manager = manager_lock.lock().await;
+        let was_connected = manager.get(guild_id).is_some();
+        (was_connected, manager.join(guild_id, channel_id).is_some())
+    };
+    if !has_joined {
+        waiters_lock.lock().await.remove(&guild_id);
+        return Ok(false);
+    }
+
+    // the sender being dropped meaning the voice server will not come either
+    match tokio::time::timeout(VOICE_SERVER_TIMEOUT, receiver).await {
+        Ok(Ok(())) => (),
+        _ => {
+            eprintln!("No voice server received for guild {} after joining its channel {}.", guild_id, channel_id);
+            waiters_lock.lock().await.remove(&guild_id);
+            // a connection this call did not make is left to its owner
+            if !was_connected {
+                manager_lock.lock().await.remove(guild_id);
+            }
+            return Ok(false);
+        },
+    }
+
+    // Get handler, gone if the bot was disconnected meanwhile
+    let manager = manager_lock.lock().await;
+    let handler = match manager.get(guild_id) {
+        Some(handler) => handler,
+        None => return Ok(false),
     };
 
-    // Get VoiceManager in the TypeMap in the context
+    // create lavalink session
+    let lava_client_lock = lavalink_for(ctx, guild_id).await?;
+    lava_client_lock.lock().await.create_session(guild_id, &handler).await?;
+
+    Ok(true)
+}
+
+/// Disconnects the bot from the voice channel of `guild_id` and destroys the
+/// lavalink session. Returns `false` if the bot was not connected.
+pub async fn leave_guild(ctx: &Context, guild_id: GuildId) -> Result<bool, CommandError> {
+    // get manager
     let manager_lock = ctx.data.read().await.get::<VoiceManager>().cloned()
         .expect("Expected VoiceManager in TypeMap.");
     let mut manager = manager_lock.lock().await;
-    let has_joined = manager.join(guild_id, connect_to).is_some();
-
-    if has_joined {
-        drop(manager);
-
-        // remove guid_id from the voice guild update (vgu)
-        loop {
-            let data = ctx.data.read().await;
-            let vgu_lock = data.get::<VoiceGuildUpdate>().unwrap();
-            let mut vgu = vgu_lock.write().await;
-            if !vgu.contains(&guild_id) {
-                tokio::time::delay_for(Duration::from_millis(500)).await;
-            } else {
-                vgu.remove(&guild_id);
-                break;
+
+    if manager.get(guild_id).is_none() {
+        return Ok(false);
+    }
+
+    // quitting and remove lavalink session
+    manager.remove(guild_id);
+
+    let nodes_lock = ctx.data.read().await.get::<Lavalink>().cloned()
+        .expect("Expected lavalink nodes in TypeMap");
+    let mut nodes = nodes_lock.write().await;
+    if let Some(lava_client_lock) = nodes.get(guild_id) {
+        lava_client_lock.lock().await.destroy(guild_id).await?;
+    }
+    nodes.release(guild_id);
+
+    // the queue is gone, its requests would be taken for those of the next one
+    let requests_lock = ctx.data.read().await.get::<TrackRequests>().cloned()
+        .expect("Expected TrackRequests in TypeMap");
+    requests_lock.write().await.remove(&guild_id);
+
+    Ok(true)
+}
+
+/// Returns the users, bots apart, listening in `channel_id`.
+async fn listeners(ctx: &Context, guild_id: GuildId, channel_id: ChannelId) -> Vec<UserId> {
+    let guild = match guild_id.to_guild_cached(&ctx.cache).await {
+        Some(guild) => guild,
+        None => return Vec::new(),
+    };
+
+    guild.voice_states.values()
+        .filter(|voice_state| voice_state.channel_id == Some(channel_id))
+        .filter(|voice_state| !guild.members.get(&voice_state.user_id)
+                                    .map_or(false, |member| member.user.bot))
+        .map(|voice_state| voice_state.user_id)
+        .collect()
+}
+
+/// Tells whether a track is playing in `guild_id`.
+async fn is_playing(ctx: &Context, guild_id: GuildId) -> bool {
+    let lava_client_lock = match current_lavalink(&ctx.data, guild_id).await {
+        Some(client) => client,
+        None => return false,
+    };
+    let lava_client = lava_client_lock.lock().await;
+
+    lava_client.nodes.get(&guild_id.0)
+        .map_or(false, |node| node.now_playing.is_some())
+}
+
+/// Leaves the voice channel of `guild_id` if nobody listens in it anymore
+/// once the configured alone timeout elapsed.
+pub async fn leave_if_alone(ctx: Context, guild_id: GuildId) {
+    let alone_timeout = match ctx.data.read().await.get::<ConfigContainer>()
+                                   .expect("Expected a Config in TypeMap").alone_timeout {
+        Some(timeout) => timeout,
+        None => return,
+    };
+
+    let channel_id = match bot_voice_channel(&ctx, guild_id).await {
+        Some(channel) => channel,
+        None => return,
+    };
+    if !listeners(&ctx, guild_id, channel_id).await.is_empty() {
+        return;
+    }
+
+    tokio::time::delay_for(alone_timeout).await;
+
+    // somebody may have come back, or the bot moved, in the meantime
+    if bot_voice_channel(&ctx, guild_id).await == Some(channel_id)
+        && listeners(&ctx, guild_id, channel_id).await.is_empty() {
+        if let Err(why) = leave_guild(&ctx, guild_id).await {
+            eprintln!("Error leaving the voice channel of guild {}: {}", guild_id, why);
+        }
+    }
+}
+
+/// Periodically leaves the voice channels where nothing has been playing for
+/// the configured idle timeout. Never returns.
+pub async fn watch_idle_players(ctx: Context) {
+    let idle_timeout = match ctx.data.read().await.get::<ConfigContainer>()
+                                  .expect("Expected a Config in TypeMap").idle_timeout {
+        Some(timeout) => timeout,
+        None => return,
+    };
+    let mut idle_since: HashMap<GuildId, Instant> = HashMap::new();
+
+    loop {
+        tokio::time::delay_for(IDLE_CHECK_PERIOD).await;
+
+        for guild_id in ctx.cache.guilds().await {
+            if bot_voice_channel(&ctx, guild_id).await.is_none() || is_playing(&ctx, guild_id).await {
+                idle_since.remove(&guild_id);
+                continue;
+            }
+
+            let since = *idle_since.entry(guild_id).or_insert_with(Instant::now);
+            if since.elapsed() >= idle_timeout {
+                idle_since.remove(&guild_id);
+                if let Err(why) = leave_guild(&ctx, guild_id).await {
+                    eprintln!("Error leaving the voice channel of guild {}: {}", guild_id, why);
+                }
             }
         }
+    }
+}
 
-        // Get manager
-        let manager_lock = ctx.data.read().await.get::<VoiceManager>().cloned()
-            .expect("Expected VoiceManager in TypeMap.");
+/// Snapshots the queue, position, volume and loop mode of the player of
+/// `guild_id`, as known by the lavalink client playing for it.
+fn snapshot_player(lava_client: &LavalinkClient, guild_id: GuildId, voice_channel: ChannelId,
+                   requests: &HashMap<GuildId, QueueRequests<TrackRequest>>,
+                   positions: &HashMap<GuildId, u64>) -> Option<GuildPlayer> {
+    let node = lava_client.nodes.get(&guild_id.0)?;
+    let requests = requests.get(&guild_id)?;
 
-        // Get handler
-        let manager = manager_lock.lock().await;
-        let handler = manager.get(guild_id).unwrap();
+    let tracks = queued_tracks(node);
+    let queue: Vec<QueuedTrack> = requests.of_queue(tracks.iter().map(|track| track.track.as_str()))
+        .into_iter()
+        .zip(tracks)
+        .filter_map(|(request, track)| {
+            let request = request?;
+            Some(QueuedTrack {
+                channel_id: request.channel_id.0,
+                requester: request.requester.0,
+                track: to_saved(track)?,
+            })
+        })
+        .collect();
+    if queue.is_empty() {
+        return None;
+    }
+
+    Some(GuildPlayer {
+        guild_id: guild_id.0,
+        voice_channel: voice_channel.0,
+        position: positions.get(&guild_id).copied().unwrap_or(0),
+        volume: node.volume,
+        looping: node.is_on_loops,
+        queue,
+    })
+}
+
+/// Snapshots the player of every guild the bot is connected in.
+pub async fn snapshot_players(data: &RwLock<TypeMap>) -> PlayerStates {
+    let (manager_lock, nodes_lock, requests_lock, positions_lock) = {
+        let data = data.read().await;
+        (data.get::<VoiceManager>().expect("Expected VoiceManager in TypeMap.").clone(),
+         data.get::<Lavalink>().expect("Expected lavalink nodes in TypeMap").clone(),
+         data.get::<TrackRequests>().expect("Expected TrackRequests in TypeMap").clone(),
+         data.get::<PlayerPositions>().expect("Expected PlayerPositions in TypeMap").clone())
+    };
+    let manager = manager_lock.lock().await;
+    let nodes = nodes_lock.read().await;
+    let requests = requests_lock.read().await;
+    let positions = positions_lock.read().await;
+
+    let mut states = PlayerStates::new();
+    for (&guild_id, &index) in &nodes.guilds {
+        let voice_channel = match manager.get(guild_id).and_then(|handler| handler.channel_id) {
+            Some(channel) => channel,
+            None => continue,
+        };
+
+        let lava_client = nodes.nodes[index].client.lock().await;
+        if let Some(player) = snapshot_player(&lava_client, guild_id, voice_channel, &requests, &positions) {
+            states.players.push(player);
+        }
+    }
 
-        let mut wdata = ctx.data.write().await;
-        let lava_client_lock = wdata.get_mut::<Lavalink>()
-            .expect("Expected a lavalink client in TypeMap");
+    states
+}
+
+/// Writes the state of every player to disk, to be restored on next start.
+pub async fn save_players(data: &RwLock<TypeMap>) {
+    // without any node, the players of the previous run may not have been
+    // restored yet, and their saved state must not be lost
+    if !lavalink_available(data).await {
+        return;
+    }
+
+    let states = snapshot_players(data).await;
+
+    if let Err(why) = save_player_states(PLAYER_STATE_FILE_PATH, &states) {
+        eprintln!("Could not save the players state: {}", why);
+    }
+}
+
+/// Saves the state of every player every configured period. Never returns.
+pub async fn persist_players(data: Arc<RwLock<TypeMap>>) {
+    let period = match data.read().await.get::<ConfigContainer>()
+                           .expect("Expected a Config in TypeMap").state_save_period {
+        Some(period) => period,
+        None => return,
+    };
+
+    loop {
+        tokio::time::delay_for(period).await;
+        save_players(&data).await;
+    }
+}
+
+/// Rejoins the voice channels and resumes the players saved by `save_players`,
+/// once a lavalink node is available.
+pub async fn restore_players(ctx: &Context) {
+    while !lavalink_available(&ctx.data).await {
+        tokio::time::delay_for(NODE_CHECK_PERIOD).await;
+    }
+
+    let states = match load_player_states(PLAYER_STATE_FILE_PATH) {
+        Ok(states) => states,
+        Err(why) => {
+            eprintln!("Could not read the players state: {}", why);
+            return;
+        },
+    };
+
+    for player in states.players {
+        let guild_id = GuildId(player.guild_id);
+        if let Err(why) = restore_player(ctx, player).await {
+            eprintln!("Could not restore the player of guild {}: {}", guild_id, why);
+        }
+    }
+}
+
+async fn restore_player(ctx: &Context, player: GuildPlayer) -> Result<(), CommandError> {
+    if !join_channel(ctx, GuildId(player.guild_id), ChannelId(player.voice_channel)).await? {
+        return Ok(());
+    }
+
+    resume_player(ctx, &player).await
+}
+
+/// Queues again the tracks of a snapshotted player, on the lavalink node now
+/// playing for its guild.
+async fn resume_player(ctx: &Context, player: &GuildPlayer) -> Result<(), CommandError> {
+    let guild_id = GuildId(player.guild_id);
+    let lava_client_lock = lavalink_for(ctx, guild_id).await?;
+    let requests_lock = ctx.data.read().await.get::<TrackRequests>().cloned()
+        .expect("Expected TrackRequests in TypeMap");
+
+    for (i, queued) in player.queue.iter().enumerate() {
+        let track = to_track(&queued.track);
+        let request = TrackRequest {
+            channel_id: ChannelId(queued.channel_id),
+            requester: UserId(queued.requester),
+            autoplay: false,
+        };
+        requests_lock.write().await
+            .entry(guild_id).or_default()
+            .push(&track.track, request);
 
-        // create lavalink session
+        // the current track resumes where it stopped
+        let mut parameters = LavalinkClient::play(guild_id, track);
+        if i == 0 && player.position > 0 {
+            parameters = parameters.start_time(Duration::from_millis(player.position));
+        }
+        parameters.queue(Arc::clone(&lava_client_lock)).await?;
+    }
+
+    let mut lava_client = lava_client_lock.lock().await;
+    lava_client.volume(guild_id, player.volume).await?;
+    if let Some(node) = lava_client.nodes.get_mut(&guild_id.0) {
+        node.is_on_loops = player.looping;
+    }
+
+    Ok(())
+}
+
+/// Periodically marks the lavalink nodes that stopped sending their stats as
+/// down, and moves their players to healthy nodes. Never returns.
+pub async fn watch_lavalink_nodes(ctx: Context) {
+    let nodes_lock = ctx.data.read().await.get::<Lavalink>().cloned()
+        .expect("Expected lavalink nodes in TypeMap");
+
+    loop {
+        tokio::time::delay_for(NODE_CHECK_PERIOD).await;
+
+        let mut went_down = Vec::new();
+        for (index, node) in nodes_lock.read().await.nodes.iter().enumerate() {
+            let mut status = node.status.write().await;
+            if status.healthy && status.last_seen.elapsed() > NODE_TIMEOUT {
+                eprintln!("Lavalink node {} is down, moving its players.", node.config);
+                status.healthy = false;
+                went_down.push(index);
+            }
+        }
+
+        for index in went_down {
+            migrate_players(&ctx, index).await;
+        }
+    }
+}
+
+/// Moves the players of the node `index` to the least loaded healthy nodes.
+async fn migrate_players(ctx: &Context, index: usize) {
+    let (manager_lock, nodes_lock, requests_lock, positions_lock) = {
+        let data = ctx.data.read().await;
+        (data.get::<VoiceManager>().expect("Expected VoiceManager in TypeMap.").clone(),
+         data.get::<Lavalink>().expect("Expected lavalink nodes in TypeMap").clone(),
+         data.get::<TrackRequests>().expect("Expected TrackRequests in TypeMap").clone(),
+         data.get::<PlayerPositions>().expect("Expected PlayerPositions in TypeMap").clone())
+    };
+    let (old_client, guilds) = {
+        let nodes = nodes_lock.read().await;
+        (Arc::clone(&nodes.nodes[index].client), nodes.guilds_on(index))
+    };
+
+    for guild_id in guilds {
+        let voice_channel = manager_lock.lock().await.get(guild_id)
+            .and_then(|handler| handler.channel_id);
+
+        // what the old client knows locally survives its node
+        let player = match voice_channel {
+            Some(channel) => {
+                let requests = requests_lock.read().await;
+                let positions = positions_lock.read().await;
+                let old = old_client.lock().await;
+                snapshot_player(&old, guild_id, channel, &requests, &positions)
+            },
+            None => None,
+        };
+        old_client.lock().await.nodes.remove(&guild_id.0);
+        nodes_lock.write().await.release(guild_id);
+
+        let player = match player {
+            Some(player) => player,
+            None => continue,
+        };
+        if let Err(why) = move_player(ctx, &manager_lock, player).await {
+            eprintln!("Could not move the player of guild {}: {}", guild_id, why);
+        }
+    }
+}
+
+/// Creates the lavalink session of a player on the node now assigned to its
+/// guild, and resumes it there.
+async fn move_player(ctx: &Context, manager_lock: &Mutex<ClientVoiceManager>, player: GuildPlayer) -> Result<(), CommandError> {
+    let guild_id = GuildId(player.guild_id);
+    let lava_client_lock = lavalink_for(ctx, guild_id).await?;
+
+    {
+        let manager = manager_lock.lock().await;
+        let handler = manager.get(guild_id)
+            .ok_or_else(|| CommandError::from("Not connected anymore"))?;
         lava_client_lock.lock().await.create_session(guild_id, &handler).await?;
+    }
+
+    resume_player(ctx, &player).await
+}
 
+/// Connects to a lavalink node, its events going to a new `LavalinkHandler`.
+pub async fn connect_node(data: &RwLock<TypeMap>, http: Arc<Http>, bot_id: UserId, config: &NodeConfig) -> Result<LavalinkNode, CommandError> {
+    let (requests, positions, autoplay, played, history) = {
+        let data = data.read().await;
+        (data.get::<TrackRequests>().expect("Expected TrackRequests in TypeMap").clone(),
+         data.get::<PlayerPositions>().expect("Expected PlayerPositions in TypeMap").clone(),
+         data.get::<Autoplay>().expect("Expected Autoplay in TypeMap").clone(),
+         data.get::<PlayedTracks>().expect("Expected PlayedTracks in TypeMap").clone(),
+         data.get::<HistoryContainer>().expect("Expected History in TypeMap").clone())
+    };
+
+    let mut lava_client = LavalinkClient::new(bot_id);
+    lava_client.set_host(&config.host);
+    lava_client.set_port(config.port);
+    lava_client.set_password(&config.password);
+    lava_client.set_is_ssl(config.tls);
+
+    let status = Arc::new(RwLock::new(NodeStatus::new()));
+    let handler = LavalinkHandler {
+        http,
+        requests,
+        positions,
+        autoplay,
+        played,
+        history,
+        status: Arc::clone(&status),
+    };
+
+    match lava_client.initialize(handler).await {
+        Ok(client) => Ok(LavalinkNode { config: config.clone(), client, status }),
+        Err(why) => Err(CommandError::from(format!("{:?}", why))),
+    }
+}
+
+/// Keeps trying to connect to the lavalink nodes that could not be reached,
+/// waiting longer after each failure. Returns once they are all connected.
+pub async fn reconnect_nodes(data: Arc<RwLock<TypeMap>>, http: Arc<Http>, bot_id: UserId, mut pending: Vec<NodeConfig>) {
+    let nodes_lock = data.read().await.get::<Lavalink>().cloned()
+        .expect("Expected lavalink nodes in TypeMap");
+    let mut delay = RECONNECT_MIN_DELAY;
+
+    while !pending.is_empty() {
+        tokio::time::delay_for(delay).await;
+        delay = (delay * 2).min(RECONNECT_MAX_DELAY);
+
+        let mut unreachable = Vec::new();
+        for config in pending {
+            match connect_node(&data, Arc::clone(&http), bot_id, &config).await {
+                Ok(node) => {
+                    eprintln!("Connected to lavalink node {}.", config);
+                    nodes_lock.write().await.add(node);
+                },
+                Err(why) => {
+                    eprintln!("Lavalink node {} is still unreachable ({}), retrying in {}s.",
+                              config, why, delay.as_secs());
+                    unreachable.push(config);
+                },
+            }
+        }
+        pending = unreachable;
+    }
+}
+
+fn save_player_states(filename: &str, states: &PlayerStates) -> Result<(), IOError> {
+    let mut file = File::create(filename)?;
+    file.write_all(states.to_string().as_bytes())?;
+    Ok(())
+}
+
+/// Reads the saved players state, no file meaning nothing to restore.
+fn load_player_states(filename: &str) -> Result<PlayerStates, IOError> {
+    let data = match fs::read_to_string(filename) {
+        Ok(data) => data,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(PlayerStates::new()),
+        Err(e) => return Err(e),
+    };
+
+    PlayerStates::from_str(&data)
+        .map_err(|s| IOError::new(ErrorKind::Other, s))
+}
+
+#[command]
+async fn join(ctx: &Context, msg: &Message) -> CommandResult {
+    // Get guild id
+    let guild = msg.guild(&ctx.cache).await.expect("whoops, could not get guild");
+    let guild_id = guild.id;
+
+    // Get channel to connect id
+    let connect_to = match user_voice_channel(ctx, guild_id, msg.author.id).await {
+        Some(channel) => channel,
+        None => {
+            msg.channel_id.say(&ctx.http, "Not in a voice channel to play in!").await?;
+
+            return Ok(());
+        }
+    };
+
+    // moving the music away from its listeners takes what stopping it does
+    if is_playing(ctx, guild_id).await
+        && (!in_bot_channel(ctx, msg, guild_id).await? || !dj_only(ctx, msg, guild_id).await?) {
+        return Ok(());
+    }
+
+    if join_channel(ctx, guild_id, connect_to).await? {
         msg.channel_id.say(&ctx.http, &format!("Joined !")).await?;
     } else {
-        msg.channel_id.say(&ctx.http, "Error joining the channel").await?;
+        msg.channel_id.say(&ctx.http, "Error joining the channel, try again in a moment.").await?;
     }
 
     Ok(())
@@ -126,21 +1025,11 @@ async fn leave(ctx: &Context, msg: &Message) -> CommandResult {
     let guild = msg.guild(&ctx.cache).await.expect("whoops, could not get guild");
     let guild_id = guild.id;
 
-    // get manager
-    let manager_lock = ctx.data.read().await.get::<VoiceManager>().cloned()
-        .expect("Expected VoiceManager in TypeMap.");
-    let mut manager = manager_lock.lock().await;
-    let has_handler = manager.get(guild_id).is_some();
-
-    // quitting and remove lavalink session
-    if has_handler {
-        manager.remove(guild_id);
-
-        let mut data = ctx.data.write().await;
-        let lava_client_lock = data.get_mut::<Lavalink>()
-            .expect("Expected a lavalink client in TypeMap");
-        lava_client_lock.lock().await.destroy(guild_id).await?;
+    if !in_bot_channel(ctx, msg, guild_id).await? || !dj_only(ctx, msg, guild_id).await? {
+        return Ok(());
+    }
 
+    if leave_guild(ctx, guild_id).await? {
         msg.channel_id.say(&ctx.http, "Left voice channel").await?;
     } else {
         msg.channel_id.say(&ctx.http, "Not in a voice channel !").await?;
@@ -149,6 +1038,243 @@ async fn leave(ctx: &Context, msg: &Message) -> CommandResult {
     Ok(())
 }
 
+/// Whether the author of `msg` may control everyone's music: anyone if no DJ
+/// role is configured, otherwise the owner, the administrators and the members
+/// with the DJ role.
+pub async fn is_dj(ctx: &Context, msg: &Message, guild_id: GuildId) -> bool {
+    let dj_role = match ctx.data.read().await.get::<ConfigContainer>()
+                           .expect("Expected a Config in TypeMap").dj_role.clone() {
+        Some(dj_role) => dj_role,
+        None => return true,
+    };
+    let guild = match guild_id.to_guild_cached(&ctx.cache).await {
+        Some(guild) => guild,
+        None => return false,
+    };
+    if guild.owner_id == msg.author.id {
+        return true;
+    }
+
+    guild.members.get(&msg.author.id).map_or(false, |member| {
+        member.roles.iter()
+            .filter_map(|role_id| guild.roles.get(role_id))
+            .any(|role| role.permissions.administrator() || role.name.eq_ignore_ascii_case(&dj_role))
+    })
+}
+
+/// Checks that the author of `msg` is a DJ, telling them otherwise.
+async fn dj_only(ctx: &Context, msg: &Message, guild_id: GuildId) -> Result<bool, CommandError> {
+    if is_dj(ctx, msg, guild_id).await {
+        return Ok(true);
+    }
+
+    let ans = MessageBuilder::new()
+        .push("🎧 Only the DJs can do that, but you can skip a track with ")
+        .push_mono("voteskip").push(".").build();
+    msg.channel_id.say(&ctx.http, ans).await?;
+    Ok(false)
+}
+
+/// Returns the current track of `guild_id`, with who requested it if known.
+pub async fn current_track(ctx: &Context, guild_id: GuildId) -> Option<(Track, Option<UserId>)> {
+    let lava_client_lock = current_lavalink(&ctx.data, guild_id).await?;
+    let track = lava_client_lock.lock().await
+        .nodes.get(&guild_id.0)?
+        .now_playing.as_ref()?
+        .track.clone();
+
+    let requests_lock = ctx.data.read().await.get::<TrackRequests>().cloned()
+        .expect("Expected TrackRequests in TypeMap");
+    let requester = requests_lock.read().await
+        .get(&guild_id)
+        .and_then(|requests| requests.first(&track.track))
+        .map(|request| request.requester);
+
+    Some((track, requester))
+}
+
+/// Skips the current track of `guild_id`, telling which one in the channel of `msg`.
+async fn skip_current(ctx: &Context, msg: &Message, guild_id: GuildId) -> CommandResult {
+    let lava_client_lock = match current_lavalink(&ctx.data, guild_id).await {
+        Some(client) => client,
+        None => {
+            msg.channel_id.say(&ctx.http, "Nothing to skip.").await?;
+            return Ok(());
+        },
+    };
+
+    if let Some(track) = lava_client_lock.lock().await.skip(*guild_id.as_u64()).await {
+        msg.channel_id.say(&ctx.http, format!("Skipped: {}", track.track.info.as_ref().unwrap().title)).await?;
+    } else {
+        msg.channel_id.say(&ctx.http, "Nothing to skip.").await?;
+    }
+
+    Ok(())
+}
+
+/// Makes sure the bot is in the voice channel of the author of `msg`, joining
+/// it if the bot is not connected yet. Returns `false`, once the author has been
+/// told why, if the music cannot be played for them.
+pub async fn connect_to_author(ctx: &Context, msg: &Message, guild_id: GuildId) -> Result<bool, CommandError> {
+    if bot_voice_channel(ctx, guild_id).await.is_some() {
+        return in_bot_channel(ctx, msg, guild_id).await;
+    }
+
+    // join the author's voice channel as the bot is not connected yet
+    let connect_to = match user_voice_channel(ctx, guild_id, msg.author.id).await {
+        Some(channel) => channel,
+        None => {
+            msg.channel_id.say(&ctx.http, "Not in a voice channel to play in!").await?;
+            return Ok(false);
+        }
+    };
+
+    if !join_channel(ctx, guild_id, connect_to).await? {
+        msg.channel_id.say(&ctx.http, "Error joining the channel, try again in a moment.").await?;
+        return Ok(false);
+    }
+
+    Ok(true)
+}
+
+/// Returns the requesters of the current track of `guild_id` and of its queue,
+/// `None` for the tracks nobody requested.
+async fn queue_requesters(ctx: &Context, guild_id: GuildId) -> Vec<Option<UserId>> {
+    let lava_client_lock = match current_lavalink(&ctx.data, guild_id).await {
+        Some(client) => client,
+        None => return Vec::new(),
+    };
+    let requests_lock = ctx.data.read().await.get::<TrackRequests>().cloned()
+        .expect("Expected TrackRequests in TypeMap");
+    let requests = requests_lock.read().await;
+    let lava_client = lava_client_lock.lock().await;
+
+    match (lava_client.nodes.get(&guild_id.0), requests.get(&guild_id)) {
+        (Some(node), Some(requests)) => requests.of_queue(queued_tracks(node).into_iter().map(|track| track.track.as_str()))
+            .into_iter()
+            .map(|request| request.map(|request| request.requester))
+            .collect(),
+        (Some(node), None) => vec![None; queued_tracks(node).len()],
+        (None, _) => Vec::new(),
+    }
+}
+
+/// Keeps the tracks the author of `msg` may queue in `guild_id`, given the
+/// maximum track length and the tracks they already queued, telling them about
+/// the others.
+pub async fn admit_tracks(ctx: &Context, msg: &Message, guild_id: GuildId, tracks: Vec<Track>) -> Result<Vec<Track>, CommandError> {
+    let config = ctx.data.read().await.get::<ConfigContainer>().cloned()
+        .expect("Expected a Config in TypeMap");
+    let queued = queue_requesters(ctx, guild_id).await.into_iter()
+        .filter(|&requester| requester == Some(msg.author.id))
+        .count();
+    let max_length = config.max_track_length.map(|length| length.as_millis() as u64);
+
+    // streams never end
+    let lengths: Vec<u64> = tracks.iter()
+        .map(|track| track.info.as_ref().map_or(0, |info| if info.is_stream { u64::MAX } else { info.length }))
+        .collect();
+    let admission = admit(&lengths, queued, config.user_queue_limit, max_length);
+
+    if let Some(max_length) = max_length.filter(|_| admission.too_long > 0) {
+        let ans = if tracks.len() == 1 {
+            format!("⏳ This track is longer than the limit of {}.", format_duration(max_length))
+        } else {
+            format!("⏳ Left out {} tracks longer than the limit of {}.", admission.too_long, format_duration(max_length))
+        };
+        msg.channel_id.say(&ctx.http, ans).await?;
+    }
+    if admission.over_limit > 0 {
+        let ans = MessageBuilder::new()
+            .push(format!("✋ You may have at most {} tracks in the queue", config.user_queue_limit.unwrap_or(0)))
+            .push(if admission.accepted.is_empty() { ", let the others play too." } else { ", the rest of yours has been left out." })
+            .build();
+        msg.channel_id.say(&ctx.http, ans).await?;
+    }
+
+    Ok(tracks.into_iter().enumerate()
+        .filter(|(i, _)| admission.accepted.contains(i))
+        .map(|(_, track)| track)
+        .collect())
+}
+
+/// Reorders the queue of `guild_id` for its requesters to take turns, the
+/// current track staying first.
+async fn interleave_queue(ctx: &Context, guild_id: GuildId, lava_client_lock: &Mutex<LavalinkClient>) {
+    let requests_lock = ctx.data.read().await.get::<TrackRequests>().cloned()
+        .expect("Expected TrackRequests in TypeMap");
+    let mut requests = requests_lock.write().await;
+    let requests = requests.entry(guild_id).or_default();
+    let mut lava_client = lava_client_lock.lock().await;
+    let node = match lava_client.nodes.get_mut(&guild_id.0) {
+        Some(node) => node,
+        None => return,
+    };
+
+    // the queue may start with the current track
+    let playing = node.now_playing.as_ref().map(|playing| playing.track.track.clone());
+    let start = match (node.queue.first(), &playing) {
+        (Some(first), Some(playing)) if first.track.track == *playing => 1,
+        _ => 0,
+    };
+    if node.queue.len() <= start + 1 {
+        return;
+    }
+
+    // the requests of the tracks queued twice are reordered with them, the
+    // current track keeping the first one of its track
+    let current = if start == 0 { playing } else { None };
+    let upcoming: Vec<TrackQueue> = node.queue.drain(start..).collect();
+    let tracks: Vec<&str> = current.iter().map(String::as_str)
+        .chain(node.queue.iter().map(|queued| queued.track.track.as_str()))
+        .chain(upcoming.iter().map(|queued| queued.track.track.as_str()))
+        .collect();
+    let mut upcoming_requests = requests.of_queue(tracks.iter().copied());
+    let first_requests: Vec<Option<TrackRequest>> = upcoming_requests.drain(..tracks.len() - upcoming.len()).collect();
+    let requesters: Vec<Option<UserId>> = upcoming_requests.iter()
+        .map(|request| request.map(|request| request.requester))
+        .collect();
+
+    let order = fair_order(&requesters);
+    requests.reorder(tracks[..first_requests.len()].iter().copied().zip(first_requests)
+        .chain(order.iter().map(|&i| (upcoming[i].track.track.as_str(), upcoming_requests[i]))));
+    let mut upcoming: Vec<Option<TrackQueue>> = upcoming.into_iter().map(Some).collect();
+    for i in order {
+        if let Some(queued) = upcoming[i].take() {
+            node.queue.push(queued);
+        }
+    }
+}
+
+/// Queues `tracks` in `guild_id` on behalf of the author of `msg`, in whose
+/// channel they will be announced. Returns `false` if lavalink refused one.
+pub async fn queue_tracks(ctx: &Context, msg: &Message, guild_id: GuildId, tracks: &[Track]) -> Result<bool, CommandError> {
+    let lava_client_lock = lavalink_for(ctx, guild_id).await?;
+    let requests_lock = ctx.data.read().await.get::<TrackRequests>().cloned()
+        .expect("Expected TrackRequests in TypeMap");
+    let request = TrackRequest { channel_id: msg.channel_id, requester: msg.author.id, autoplay: false };
+
+    for track in tracks {
+        requests_lock.write().await
+            .entry(guild_id).or_default()
+            .push(&track.track, request);
+
+        if let Err(why) = LavalinkClient::play(guild_id, track.clone())
+                                         .queue(Arc::clone(&lava_client_lock)).await {
+            eprintln!("Error playing on the lavalink client: {}", why);
+            return Ok(false);
+        };
+    }
+
+    let fair_queue = ctx.data.read().await.get::<ConfigContainer>()
+        .expect("Expected a Config in TypeMap").fair_queue;
+    if fair_queue {
+        interleave_queue(ctx, guild_id, &lava_client_lock).await;
+    }
+
+    Ok(true)
+}
+
 #[command]
 #[min_args(1)]
 async fn play(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
@@ -162,35 +1288,52 @@ async fn play(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
         },
     };
 
-    // Get manager
-    let manager_lock = ctx.data.read().await.get::<VoiceManager>().cloned()
-        .expect("Expected VoiceManager in TypeMap.");
-    let mut manager = manager_lock.lock().await;
+    if !connect_to_author(ctx, msg, guild_id).await? {
+        return Ok(());
+    }
 
-    if let Some(_handler) = manager.get_mut(guild_id) {
-        // get lava client
-        let mut data = ctx.data.write().await;
-        let lava_client_lock = data.get_mut::<Lavalink>().expect("Expected a lavalink client in TypeMap");
-        let lava_client = lava_client_lock.lock().await;
+    // get lava client
+    let playlist_limit = ctx.data.read().await.get::<ConfigContainer>()
+        .expect("Expected a Config in TypeMap").playlist_limit;
+    let lava_client_lock = lavalink_for(ctx, guild_id).await?;
+    let lava_client = lava_client_lock.lock().await;
 
-        let query_information = lava_client.auto_search_tracks(&query).await?;
+    let query_information = lava_client.auto_search_tracks(&query).await?;
 
-        if query_information.tracks.is_empty() {
-            msg.channel_id.say(&ctx, "Could not find any video of the search query.").await?;
-            return Ok(());
-        }
+    if query_information.tracks.is_empty() {
+        msg.channel_id.say(&ctx, "Could not find any video of the search query.").await?;
+        return Ok(());
+    }
 
-        drop(lava_client);
+    drop(lava_client);
 
-        if let Err(why) = LavalinkClient::play(guild_id, query_information.tracks[0].clone())
-                                         .queue(Arc::clone(lava_client_lock)).await {
-            eprintln!("Error playing on the lavalink client: {}", why);
-            return Ok(());
-        };
-        msg.channel_id.say(&ctx.http, format!("Added to queue: {}", query_information.tracks[0].info.as_ref().unwrap().title)).await?;
+    // a playlist url loads every track of the playlist, a search only the first match
+    let playlist_name = query_information.playlist_info.as_ref()
+        .and_then(|info| info.name.clone());
+    let tracks: Vec<Track> = match playlist_name {
+        Some(_) => query_information.tracks.iter().take(playlist_limit).cloned().collect(),
+        None => vec![query_information.tracks[0].clone()],
+    };
+    let found = tracks.len();
 
-    } else {
-        msg.channel_id.say(&ctx.http, "Use `join` first, to connect the bot to your current voice channel.").await?;
+    let tracks = admit_tracks(ctx, msg, guild_id, tracks).await?;
+    if tracks.is_empty() || !queue_tracks(ctx, msg, guild_id, &tracks).await? {
+        return Ok(());
+    }
+
+    match playlist_name {
+        Some(name) => {
+            let mut ans = MessageBuilder::new();
+            ans.push(format!("Queued {} tracks from ", tracks.len()))
+               .push_bold_safe(name);
+            if query_information.tracks.len() > found {
+                ans.push(format!(" (limited to the first {})", playlist_limit));
+            }
+            msg.channel_id.say(&ctx.http, ans.push(".").build()).await?;
+        },
+        None => {
+            msg.channel_id.say(&ctx.http, format!("Added to queue: {}", tracks[0].info.as_ref().unwrap().title)).await?;
+        },
     }
 
     Ok(())
@@ -200,8 +1343,13 @@ async fn play(ctx: &Context, msg: &Message, args: Args) -> CommandResult {
 #[aliases(np)]
 async fn now_playing(ctx: &Context, msg: &Message) -> CommandResult {
     // Get lava client
-    let mut data = ctx.data.write().await;
-    let lava_client_lock = data.get_mut::<Lavalink>().expect("Expected a lavalink client in TypeMap");
+    let lava_client_lock = match current_lavalink(&ctx.data, msg.guild_id.unwrap()).await {
+        Some(client) => client,
+        None => {
+            msg.channel_id.say(&ctx.http, "Nothing is playing at the moment.").await?;
+            return Ok(());
+        },
+    };
     let lava_client = lava_client_lock.lock().await;
 
     if let Some(node) = lava_client.nodes.get(&msg.guild_id.unwrap().0) {
@@ -223,16 +1371,127 @@ async fn skip(ctx: &Context, msg: &Message) -> CommandResult {
     let guild = msg.guild(&ctx.cache).await.expect("whoops, could not get guild");
     let guild_id = guild.id;
 
-    let mut data = ctx.data.write().await;
-    let lava_client_lock = data.get_mut::<Lavalink>()
-        .expect("Expected a lavalink client in TypeMap");
+    if !in_bot_channel(ctx, msg, guild_id).await? {
+        return Ok(());
+    }
 
-    if let Some(track) = lava_client_lock.lock().await.skip(*guild_id.as_u64()).await {
-        msg.channel_id.say(&ctx.http, format!("Skipped: {}", track.track.info.as_ref().unwrap().title)).await?;
-    } else {
-        msg.channel_id.say(&ctx.http, "Nothing to skip.").await?;
+    // anyone may skip the tracks they requested
+    let requester = current_track(ctx, guild_id).await.and_then(|(_, requester)| requester);
+    if requester != Some(msg.author.id) && !dj_only(ctx, msg, guild_id).await? {
+        return Ok(());
     }
 
+    skip_current(ctx, msg, guild_id).await
+}
+
+/// Votes to skip the current track, skipped once enough of the listeners voted.
+#[command]
+#[aliases(vs)]
+#[only_in(guilds)]
+async fn voteskip(ctx: &Context, msg: &Message) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+
+    let bot_channel = match bot_voice_channel(ctx, guild_id).await {
+        Some(channel) => channel,
+        None => {
+            msg.channel_id.say(&ctx.http, "Nothing to skip.").await?;
+            return Ok(());
+        },
+    };
+    if !in_bot_channel(ctx, msg, guild_id).await? {
+        return Ok(());
+    }
+
+    let (track, requester) = match current_track(ctx, guild_id).await {
+        Some(current) => current,
+        None => {
+            msg.channel_id.say(&ctx.http, "Nothing to skip.").await?;
+            return Ok(());
+        },
+    };
+    if requester == Some(msg.author.id) || is_dj(ctx, msg, guild_id).await {
+        return skip_current(ctx, msg, guild_id).await;
+    }
+
+    let (votes_lock, ratio) = {
+        let data = ctx.data.read().await;
+        (data.get::<SkipVotes>().expect("Expected SkipVotes in TypeMap").clone(),
+         data.get::<ConfigContainer>().expect("Expected a Config in TypeMap").vote_skip_ratio)
+    };
+    let listeners = listeners(ctx, guild_id, bot_channel).await;
+    let needed = ((listeners.len() as f64 * ratio).ceil() as usize).max(1);
+
+    let votes = {
+        let mut skip_votes = votes_lock.write().await;
+        let vote = skip_votes.entry(guild_id).or_insert_with(|| SkipVote {
+            track: track.track.clone(),
+            voters: HashSet::new(),
+        });
+        // the votes for a previous track do not count
+        if vote.track != track.track {
+            vote.track = track.track.clone();
+            vote.voters.clear();
+        }
+        vote.voters.insert(msg.author.id);
+        // nor the ones of who left the channel since
+        vote.voters.retain(|voter| listeners.contains(voter));
+
+        let votes = vote.voters.len();
+        if votes >= needed {
+            skip_votes.remove(&guild_id);
+        }
+        votes
+    };
+
+    if votes >= needed {
+        return skip_current(ctx, msg, guild_id).await;
+    }
+
+    let title = track.info.as_ref().map_or("this track", |info| info.title.as_str());
+    let ans = MessageBuilder::new()
+        .push(format!("🗳️ {}/{} votes to skip ", votes, needed)).push_bold_safe(title)
+        .push(".").build();
+    msg.channel_id.say(&ctx.http, ans).await?;
+    Ok(())
+}
+
+/// Turns autoplay on or off, or toggles it without argument.
+#[command]
+#[only_in(guilds)]
+async fn autoplay(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+    if !dj_only(ctx, msg, guild_id).await? {
+        return Ok(());
+    }
+
+    let autoplay_lock = ctx.data.read().await.get::<Autoplay>().cloned()
+        .expect("Expected Autoplay in TypeMap");
+    let mut autoplay = autoplay_lock.write().await;
+    let enable = match args.current() {
+        None => !autoplay.contains(&guild_id),
+        Some(_) => match args.single::<String>()?.to_lowercase().as_str() {
+            "on" => true,
+            "off" => false,
+            _ => {
+                msg.channel_id.say(&ctx.http, "Usage: `autoplay [on|off]`").await?;
+                return Ok(());
+            },
+        },
+    };
+
+    if enable {
+        autoplay.insert(guild_id);
+        msg.channel_id.say(&ctx.http, "📻 Autoplay is on, related tracks will follow when the queue runs dry.").await?;
+    } else {
+        autoplay.remove(&guild_id);
+        msg.channel_id.say(&ctx.http, "Autoplay is off.").await?;
+    }
     Ok(())
 }
 
@@ -254,9 +1513,17 @@ pub async fn stop(ctx: &Context, msg: &Message) -> CommandResult {
     let guild = msg.guild(&ctx.cache).await.expect("whoops, could not get guild");
     let guild_id = guild.id;
 
-    let mut data = ctx.data.write().await;
-    let lava_client_lock = data.get_mut::<Lavalink>()
-        .expect("Expected a lavalink client in TypeMap");
+    if !in_bot_channel(ctx, msg, guild_id).await? || !dj_only(ctx, msg, guild_id).await? {
+        return Ok(());
+    }
+
+    let lava_client_lock = match current_lavalink(&ctx.data, guild_id).await {
+        Some(client) => client,
+        None => {
+            msg.channel_id.say(&ctx.http, "Nothing to skip.").await?;
+            return Ok(());
+        },
+    };
 
     if let Ok(_) = lava_client_lock.lock().await.stop(*guild_id.as_u64()).await {
         msg.channel_id.say(&ctx.http, "Stopped !").await?;
@@ -0,0 +1,365 @@
+use std::str::FromStr;
+use std::fs::{self, File};
+use std::io::{Write, Error as IOError, ErrorKind};
+use std::sync::Arc;
+
+use serenity::{
+    prelude::{TypeMapKey, RwLock, Context},
+    model::prelude::{GuildId, Message},
+    framework::standard::{
+        Args, CommandError, CommandResult, macros::command
+    },
+    utils::MessageBuilder,
+};
+
+use lavalink_rs::model::Track;
+
+use super::player::{
+    MUSIC_CHECK, admit_tracks, any_lavalink, connect_to_author, current_lavalink, is_dj, queue_tracks,
+    queued_tracks, to_saved, to_track,
+};
+use super::utils::playlists::{PlaylistOwner, PlaylistSet, SavedTrack};
+use crate::config::ConfigContainer;
+
+pub const PLAYLISTS_FILE_PATH: &str = "data/playlists.txt";
+
+/// Number of tracks `playlist show` lists at most.
+const SHOW_LIMIT: usize = 25;
+
+pub struct Playlists;
+
+impl TypeMapKey for Playlists {
+    type Value = Arc<RwLock<PlaylistSet>>;
+}
+
+/// Reads the optional `--guild` flag, selecting the playlists of the guild
+/// rather than the ones of the author. `None` if used outside of a guild,
+/// the author being told so.
+async fn owner(ctx: &Context, msg: &Message, args: &mut Args) -> Result<Option<PlaylistOwner>, CommandError> {
+    if args.current() != Some("--guild") {
+        return Ok(Some(PlaylistOwner::User(msg.author.id.0)));
+    }
+    args.advance();
+
+    match msg.guild_id {
+        Some(guild_id) => Ok(Some(PlaylistOwner::Guild(guild_id.0))),
+        None => {
+            let ans = MessageBuilder::new()
+                .push_mono("--guild").push(" only works in a server.").build();
+            msg.channel_id.say(&ctx.http, ans).await?;
+            Ok(None)
+        },
+    }
+}
+
+/// Checks that the author of `msg` may change the playlists of `owner`, only
+/// the DJs changing the ones of the guild, telling them otherwise.
+async fn may_change(ctx: &Context, msg: &Message, owner: PlaylistOwner) -> Result<bool, CommandError> {
+    let guild_id = match owner {
+        PlaylistOwner::Guild(guild_id) => GuildId(guild_id),
+        PlaylistOwner::User(_) => return Ok(true),
+    };
+    if is_dj(ctx, msg, guild_id).await {
+        return Ok(true);
+    }
+
+    msg.channel_id.say(&ctx.http, "🎧 Only the DJs can change the playlists of the server.").await?;
+    Ok(false)
+}
+
+async fn playlists_lock(ctx: &Context) -> Arc<RwLock<PlaylistSet>> {
+    let data_read = ctx.data.read().await;
+    data_read.get::<Playlists>().expect("Expected Playlists in TypeMap ;(").clone()
+}
+
+/// Writes the playlists to disk, telling the author of `msg` if it failed.
+async fn store(ctx: &Context, msg: &Message, playlists: &PlaylistSet) -> CommandResult {
+    if let Err(why) = save_playlists(PLAYLISTS_FILE_PATH, playlists) {
+        eprintln!("Could not save the playlists: {}", why);
+        msg.channel_id.say(&ctx.http, "Could not write the playlists file, they will be lost on restart 😕.").await?;
+    }
+    Ok(())
+}
+
+#[command]
+#[sub_commands(playlist_save, playlist_load, playlist_add, playlist_remove, playlist_list, playlist_show)]
+pub async fn playlist(ctx: &Context, msg: &Message, _args: Args) -> CommandResult {
+    let ans = MessageBuilder::new()
+        .push_line("Usage: `playlist <save|load|add|remove|list|show> [--guild] <name> ...`")
+        .push("Playlists are yours, or the server's ones with ").push_mono("--guild")
+        .push(", which only the DJs can change.").build();
+    msg.channel_id.say(&ctx.http, ans).await?;
+    Ok(())
+}
+
+#[command("save")]
+#[only_in(guilds)]
+#[min_args(1)]
+async fn playlist_save(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+    let owner = match owner(ctx, msg, &mut args).await? {
+        Some(owner) => owner,
+        None => return Ok(()),
+    };
+    let name = args.single::<String>()?;
+    if !may_change(ctx, msg, owner).await? {
+        return Ok(());
+    }
+
+    // snapshot the current track and the queue following it
+    let tracks: Vec<SavedTrack> = match current_lavalink(&ctx.data, guild_id).await {
+        Some(lava_client_lock) => {
+            let lava_client = lava_client_lock.lock().await;
+            match lava_client.nodes.get(&guild_id.0) {
+                Some(node) => queued_tracks(node).into_iter().filter_map(to_saved).collect(),
+                None => Vec::new(),
+            }
+        },
+        None => Vec::new(),
+    };
+
+    if tracks.is_empty() {
+        msg.channel_id.say(&ctx.http, "🙊 Nothing is queued, there is nothing to save.").await?;
+        return Ok(());
+    }
+
+    let pl_lock = playlists_lock(ctx).await;
+    let mut playlists = pl_lock.write().await;
+    let count = tracks.len();
+    playlists.set(owner, &name, tracks);
+    store(ctx, msg, &playlists).await?;
+
+    let ans = MessageBuilder::new()
+        .push(format!("Saved {} tracks in playlist ", count)).push_mono_safe(name)
+        .push(" 💾!").build();
+    msg.channel_id.say(&ctx.http, ans).await?;
+    Ok(())
+}
+
+#[command("load")]
+#[only_in(guilds)]
+#[checks(Music)]
+#[min_args(1)]
+async fn playlist_load(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let guild_id = match msg.guild_id {
+        Some(guild_id) => guild_id,
+        None => return Ok(()),
+    };
+    let owner = match owner(ctx, msg, &mut args).await? {
+        Some(owner) => owner,
+        None => return Ok(()),
+    };
+    let name = args.single::<String>()?;
+
+    let tracks: Vec<Track> = {
+        let pl_lock = playlists_lock(ctx).await;
+        let playlists = pl_lock.read().await;
+        match playlists.get(owner, &name) {
+            Some(saved) => saved.iter().map(to_track).collect(),
+            None => {
+                let ans = MessageBuilder::new()
+                    .push("😮 There is no ").push_mono_safe(name)
+                    .push(" playlist.").build();
+                msg.channel_id.say(&ctx.http, ans).await?;
+                return Ok(());
+            },
+        }
+    };
+
+    if tracks.is_empty() {
+        msg.channel_id.say(&ctx.http, "🙊 This playlist is empty.").await?;
+        return Ok(());
+    }
+
+    if !connect_to_author(ctx, msg, guild_id).await? {
+        return Ok(());
+    }
+    let tracks = admit_tracks(ctx, msg, guild_id, tracks).await?;
+    if tracks.is_empty() || !queue_tracks(ctx, msg, guild_id, &tracks).await? {
+        return Ok(());
+    }
+
+    let ans = MessageBuilder::new()
+        .push(format!("Queued {} tracks from playlist ", tracks.len())).push_mono_safe(name)
+        .push(" 🎶!").build();
+    msg.channel_id.say(&ctx.http, ans).await?;
+    Ok(())
+}
+
+#[command("add")]
+#[checks(Music)]
+#[min_args(2)]
+async fn playlist_add(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let owner = match owner(ctx, msg, &mut args).await? {
+        Some(owner) => owner,
+        None => return Ok(()),
+    };
+    let name = args.single::<String>()?;
+    let query = args.rest().to_string();
+    if !may_change(ctx, msg, owner).await? {
+        return Ok(());
+    }
+
+    let playlist_limit = ctx.data.read().await.get::<ConfigContainer>()
+        .expect("Expected a Config in TypeMap").playlist_limit;
+    let lava_client_lock = any_lavalink(ctx).await?;
+    let query_information = lava_client_lock.lock().await.auto_search_tracks(&query).await?;
+
+    // like `play`, a playlist url adds every track, a search only the first match
+    let found = if query_information.playlist_info.as_ref().and_then(|info| info.name.as_ref()).is_some() {
+        query_information.tracks.iter().take(playlist_limit).collect()
+    } else {
+        query_information.tracks.iter().take(1).collect::<Vec<&Track>>()
+    };
+    let tracks: Vec<SavedTrack> = found.into_iter().filter_map(to_saved).collect();
+
+    if tracks.is_empty() {
+        msg.channel_id.say(&ctx.http, "Could not find any video of the search query.").await?;
+        return Ok(());
+    }
+
+    let pl_lock = playlists_lock(ctx).await;
+    let mut playlists = pl_lock.write().await;
+    let ans = if tracks.len() == 1 {
+        MessageBuilder::new()
+            .push("Added ").push_bold_safe(&tracks[0].title)
+            .push(" to playlist ").push_mono_safe(&name)
+            .push(" 👌!").build()
+    } else {
+        MessageBuilder::new()
+            .push(format!("Added {} tracks to playlist ", tracks.len())).push_mono_safe(&name)
+            .push(" 👌!").build()
+    };
+    playlists.extend(owner, &name, tracks);
+    store(ctx, msg, &playlists).await?;
+
+    msg.channel_id.say(&ctx.http, ans).await?;
+    Ok(())
+}
+
+#[command("remove")]
+#[min_args(1)]
+async fn playlist_remove(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let owner = match owner(ctx, msg, &mut args).await? {
+        Some(owner) => owner,
+        None => return Ok(()),
+    };
+    let name = args.single::<String>()?;
+    if !may_change(ctx, msg, owner).await? {
+        return Ok(());
+    }
+
+    let pl_lock = playlists_lock(ctx).await;
+    let mut playlists = pl_lock.write().await;
+
+    // with a position, remove only that track, otherwise the whole playlist
+    let ans = if args.is_empty() {
+        match playlists.remove(owner, &name) {
+            Some(_) => MessageBuilder::new()
+                .push("Playlist ").push_mono_safe(&name)
+                .push(" has been removed ✅.").build(),
+            None => MessageBuilder::new()
+                .push("😮 There is no ").push_mono_safe(&name)
+                .push(" playlist.").build(),
+        }
+    } else {
+        let position = args.single::<usize>()?;
+        match playlists.remove_track(owner, &name, position.wrapping_sub(1)) {
+            Some(track) => MessageBuilder::new()
+                .push_bold_safe(track.title).push(" has been removed from ")
+                .push_mono_safe(&name).push(" ✅.").build(),
+            None => MessageBuilder::new()
+                .push(format!("😮 There is no track {} in ", position))
+                .push_mono_safe(&name).push(".").build(),
+        }
+    };
+    store(ctx, msg, &playlists).await?;
+
+    msg.channel_id.say(&ctx.http, ans).await?;
+    Ok(())
+}
+
+#[command("list")]
+async fn playlist_list(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let owner = match owner(ctx, msg, &mut args).await? {
+        Some(owner) => owner,
+        None => return Ok(()),
+    };
+
+    let pl_lock = playlists_lock(ctx).await;
+    let playlists = pl_lock.read().await;
+    let list = playlists.list(owner);
+
+    if list.is_empty() {
+        msg.channel_id.say(&ctx.http, "🙊 No saved playlist yet.").await?;
+        return Ok(());
+    }
+
+    let mut ans = MessageBuilder::new();
+    ans.push("Saved playlists: \n");
+    for (name, count) in list {
+        ans.push_bold("-").push_mono_safe(name)
+           .push(format!(" ({} tracks),\n", count));
+    }
+
+    msg.channel_id.say(&ctx.http, ans.build()).await?;
+    Ok(())
+}
+
+#[command("show")]
+#[min_args(1)]
+async fn playlist_show(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    let owner = match owner(ctx, msg, &mut args).await? {
+        Some(owner) => owner,
+        None => return Ok(()),
+    };
+    let name = args.single::<String>()?;
+
+    let pl_lock = playlists_lock(ctx).await;
+    let playlists = pl_lock.read().await;
+
+    let tracks = match playlists.get(owner, &name) {
+        Some(tracks) => tracks,
+        None => {
+            let ans = MessageBuilder::new()
+                .push("😮 There is no ").push_mono_safe(name)
+                .push(" playlist.").build();
+            msg.channel_id.say(&ctx.http, ans).await?;
+            return Ok(());
+        },
+    };
+
+    let mut ans = MessageBuilder::new();
+    ans.push("Tracks of playlist ").push_mono_safe(&name).push(": \n");
+    for (i, track) in tracks.iter().take(SHOW_LIMIT).enumerate() {
+        ans.push(format!("{}. ", i + 1)).push_bold_safe(&track.title)
+           .push(" - ").push_safe(&track.author).push("\n");
+    }
+    if tracks.len() > SHOW_LIMIT {
+        ans.push(format!("... and {} more.", tracks.len() - SHOW_LIMIT));
+    }
+
+    msg.channel_id.say(&ctx.http, ans.build()).await?;
+    Ok(())
+}
+
+fn save_playlists(filename: &str, playlists: &PlaylistSet) -> Result<(), IOError> {
+    let mut file = File::create(filename)?;
+    file.write_all(playlists.to_string().as_bytes())?;
+    Ok(())
+}
+
+/// Reads the saved playlists, no file meaning no playlist yet.
+pub fn load_playlists(filename: &str) -> Result<PlaylistSet, IOError> {
+    let data = match fs::read_to_string(filename) {
+        Ok(data) => data,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(PlaylistSet::new()),
+        Err(e) => return Err(e),
+    };
+
+    PlaylistSet::from_str(&data)
+        .map_err(|s| IOError::new(ErrorKind::Other, s))
+}
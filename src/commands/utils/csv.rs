@@ -0,0 +1,118 @@
+use std::mem;
+use std::str::FromStr;
+
+/// The separators recognized, the most frequent one in the first line being
+/// the one of the file.
+const SEPARATORS: [char; 3] = [',', ';', '\t'];
+
+/// Splits a line into its cells, quotes keeping separators in them.
+fn cells(line: &str, separator: char) -> Vec<String> {
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            // a quote escaped by another one
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                cell.push('"');
+                chars.next();
+            },
+            '"' => quoted = !quoted,
+            c if c == separator && !quoted => cells.push(mem::take(&mut cell).trim().to_string()),
+            c => cell.push(c),
+        }
+    }
+    cells.push(cell.trim().to_string());
+    cells
+}
+
+/// Reads `cell` as a number, with a decimal comma when commas do not
+/// separate cells.
+fn number<T: FromStr>(cell: &str, separator: char) -> Option<T> {
+    if separator == ',' {
+        cell.parse().ok()
+    } else {
+        cell.replace(',', ".").parse().ok()
+    }
+}
+
+/// Reads the numbers of a column of CSV `data`, given by its name in the
+/// header or its number from 1, or else the first one with numbers only.
+pub fn column<T: FromStr>(data: &str, column: Option<&str>) -> Result<Vec<T>, String> {
+    let first_line = data.lines().find(|line| !line.trim().is_empty()).unwrap_or("");
+    let separator = SEPARATORS.iter()
+        .copied()
+        .max_by_key(|separator| first_line.matches(*separator).count())
+        .filter(|separator| first_line.contains(*separator))
+        .unwrap_or(',');
+
+    // with the numbers of their lines
+    let mut rows: Vec<(usize, Vec<String>)> = data.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| (i + 1, cells(line, separator)))
+        .collect();
+    if rows.is_empty() {
+        return Err(String::from("the file is empty"));
+    }
+    let has_header = rows[0].1.iter().any(|cell| !cell.is_empty() && number::<T>(cell, separator).is_none());
+    let header = if has_header { Some(rows.remove(0).1) } else { None };
+
+    let index = match column {
+        Some(name) => header.as_ref()
+            .and_then(|header| header.iter().position(|cell| cell.eq_ignore_ascii_case(name)))
+            .or_else(|| name.parse::<usize>().ok().filter(|n| *n > 0).map(|n| n - 1))
+            .ok_or_else(|| format!("no column `{}`", name))?,
+        None => {
+            let width = rows.iter().map(|(_, cells)| cells.len()).max().unwrap_or(0);
+            (0..width).find(|i| {
+                let mut cells = rows.iter()
+                    .filter_map(|(_, cells)| cells.get(*i))
+                    .filter(|cell| !cell.is_empty())
+                    .peekable();
+                cells.peek().is_some() && cells.all(|cell| number::<T>(cell, separator).is_some())
+            }).ok_or_else(|| String::from("no column of numbers"))?
+        },
+    };
+
+    let mut numbers = Vec::new();
+    for (line, cells) in &rows {
+        match cells.get(index).map(String::as_str) {
+            None | Some("") => {},
+            Some(cell) => match number(cell, separator) {
+                Some(value) => numbers.push(value),
+                None => return Err(format!("`{}` on line {} is not a number", cell, line)),
+            },
+        }
+    }
+    Ok(numbers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cells() {
+        assert_eq!(cells("a, b ,c", ','), ["a", "b", "c"]);
+        assert_eq!(cells("\"a, b\",\"say \"\"hi\"\"\",", ','), ["a, b", "say \"hi\"", ""]);
+        assert_eq!(cells("1,5;2", ';'), ["1,5", "2"]);
+    }
+
+    #[test]
+    fn test_column() {
+        let data = "name,price,count\nbread,2.5,3\n\nmilk,1.2,\ncheese,7,1\n";
+        assert_eq!(column::<f64>(data, None), Ok(vec![2.5, 1.2, 7.0]));
+        assert_eq!(column::<f64>(data, Some("Count")), Ok(vec![3.0, 1.0]));
+        assert_eq!(column::<f64>(data, Some("3")), Ok(vec![3.0, 1.0]));
+        assert_eq!(column::<f64>(data, Some("1")), Err(String::from("`bread` on line 2 is not a number")));
+        assert_eq!(column::<f64>(data, Some("weight")), Err(String::from("no column `weight`")));
+
+        // with decimal commas, or without header
+        assert_eq!(column::<f64>("x;y\n1,5;a\n2;b", None), Ok(vec![1.5, 2.0]));
+        assert_eq!(column::<f64>("1\t2\n3\t4", Some("2")), Ok(vec![2.0, 4.0]));
+        assert_eq!(column::<f64>("a,b\nc,d", None), Err(String::from("no column of numbers")));
+        assert_eq!(column::<f64>("\n \n", None), Err(String::from("the file is empty")));
+    }
+}
@@ -0,0 +1,432 @@
+use std::fmt;
+
+use super::expr::ExprError;
+use super::random::Rng;
+
+/// How many dice may be rolled at once, explosions included, for the
+/// details to fit in a message.
+const MAX_DICE: usize = 100;
+const MAX_SIDES: u64 = 1000;
+const MAX_CONSTANT: u64 = 1_000_000;
+
+/// The words after a roll rolling its single die twice, keeping the best or
+/// the worst of both.
+const ADVANTAGE: [&str; 2] = ["adv", "advantage"];
+const DISADVANTAGE: [&str; 2] = ["dis", "disadvantage"];
+
+/// Which dice of a group count, by how many.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Select {
+    KeepHighest(usize),
+    KeepLowest(usize),
+    DropHighest(usize),
+    DropLowest(usize),
+}
+
+/// Dice thrown together, as `4d6kh3`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dice {
+    pub count: usize,
+    pub sides: u64,
+    pub select: Option<Select>,
+    /// Whether dice showing their highest side are thrown again, as `d6!`.
+    pub explode: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Term {
+    Dice(Dice),
+    Constant(u64),
+}
+
+/// Dice and numbers added or subtracted, as `4d6kh3 + 2`.
+#[derive(Debug, PartialEq)]
+pub struct Roll {
+    /// With whether they are subtracted, and how they were written.
+    terms: Vec<(bool, Term, String)>,
+}
+
+/// A thrown die.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Die {
+    pub value: u64,
+    pub kept: bool,
+    /// Whether it showed its highest side and was thrown again.
+    pub exploded: bool,
+}
+
+/// A term of a roll once thrown, its dice empty for numbers.
+#[derive(Debug, PartialEq)]
+pub struct Group {
+    pub source: String,
+    pub negative: bool,
+    pub dice: Vec<Die>,
+    pub total: i64,
+}
+
+/// The result of a roll, term by term.
+#[derive(Debug, PartialEq)]
+pub struct Outcome {
+    pub groups: Vec<Group>,
+    pub total: i64,
+}
+
+/// Reads the characters of a roll.
+struct Scanner {
+    chars: Vec<char>,
+    current: usize,
+}
+
+impl Scanner {
+    fn skip_spaces(&mut self) {
+        while self.chars.get(self.current).map_or(false, |c| c.is_whitespace()) {
+            self.current += 1;
+        }
+    }
+
+    fn at_end(&self) -> bool {
+        self.current == self.chars.len()
+    }
+
+    /// Goes past `s` if it comes next, whatever the case.
+    fn eat(&mut self, s: &str) -> bool {
+        let next = self.chars.iter().skip(self.current);
+        let matches = s.chars().count() <= self.chars.len() - self.current
+            && s.chars().zip(next).all(|(expected, c)| c.to_ascii_lowercase() == expected);
+        if matches {
+            self.current += s.chars().count();
+        }
+        matches
+    }
+
+    /// The number coming next, saturating when too large.
+    fn number(&mut self) -> Option<u64> {
+        let start = self.current;
+        let mut n: u64 = 0;
+        while let Some(digit) = self.chars.get(self.current).and_then(|c| c.to_digit(10)) {
+            n = n.saturating_mul(10).saturating_add(u64::from(digit));
+            self.current += 1;
+        }
+        if self.current > start {
+            Some(n)
+        } else {
+            None
+        }
+    }
+
+    fn error(&self, message: impl Into<String>) -> ExprError {
+        ExprError { position: self.current, message: message.into() }
+    }
+}
+
+/// Parses dice after their count.
+fn dice(scanner: &mut Scanner, count: usize, start: usize) -> Result<Dice, ExprError> {
+    let sides = if scanner.eat("%") {
+        100
+    } else {
+        scanner.number().ok_or_else(|| scanner.error("expected the number of sides"))?
+    };
+    if count == 0 || count > MAX_DICE {
+        return Err(ExprError { position: start, message: format!("expected from 1 to {} dice", MAX_DICE) });
+    }
+    if sides == 0 || sides > MAX_SIDES {
+        return Err(ExprError { position: start, message: format!("expected from 1 to {} sides", MAX_SIDES) });
+    }
+
+    let mut dice = Dice { count, sides, select: None, explode: false };
+    loop {
+        let position = scanner.current;
+        if scanner.eat("!") {
+            if sides == 1 {
+                return Err(ExprError { position, message: String::from("dice of one side cannot explode") });
+            }
+            dice.explode = true;
+            continue;
+        }
+        // the longest first, `k` and `d` being short for `kh` and `dl`
+        let select: fn(usize) -> Select = if scanner.eat("kl") {
+            Select::KeepLowest
+        } else if scanner.eat("kh") || scanner.eat("k") {
+            Select::KeepHighest
+        } else if scanner.eat("dh") {
+            Select::DropHighest
+        } else if scanner.eat("dl") || scanner.eat("d") {
+            Select::DropLowest
+        } else {
+            return Ok(dice);
+        };
+        let n = scanner.number().ok_or_else(|| scanner.error("expected how many dice"))?;
+        if dice.select.is_some() {
+            return Err(ExprError { position, message: String::from("expected a single keep or drop") });
+        }
+        if n > count as u64 {
+            return Err(ExprError { position, message: format!("there are only {} dice", count) });
+        }
+        dice.select = Some(select(n as usize));
+    }
+}
+
+impl Roll {
+    /// Parses terms as `2d6`, `d20`, `d%` or `3`, added or subtracted, maybe
+    /// followed by `adv` or `dis`.
+    pub fn parse(input: &str) -> Result<Self, ExprError> {
+        let trimmed = input.trim_end();
+        let last_word = trimmed.rsplit(char::is_whitespace).next().unwrap_or("");
+        let lowercase = last_word.to_lowercase();
+        let advantage = if ADVANTAGE.contains(&lowercase.as_str()) {
+            Some(Select::KeepHighest(1))
+        } else if DISADVANTAGE.contains(&lowercase.as_str()) {
+            Some(Select::KeepLowest(1))
+        } else {
+            None
+        };
+        let terms_input = if advantage.is_some() { &trimmed[..trimmed.len() - last_word.len()] } else { input };
+
+        let mut scanner = Scanner { chars: terms_input.chars().collect(), current: 0 };
+        let mut terms = Vec::new();
+        loop {
+            scanner.skip_spaces();
+            let negative = if scanner.eat("-") {
+                true
+            } else if scanner.eat("+") || terms.is_empty() {
+                false
+            } else if scanner.at_end() {
+                break;
+            } else {
+                return Err(scanner.error("expected `+` or `-`"));
+            };
+            scanner.skip_spaces();
+
+            let start = scanner.current;
+            let count = scanner.number();
+            let term = if scanner.eat("d") {
+                Term::Dice(dice(&mut scanner, count.map_or(1, |count| count.min(usize::MAX as u64) as usize), start)?)
+            } else if let Some(n) = count {
+                if n > MAX_CONSTANT {
+                    return Err(ExprError { position: start, message: format!("expected numbers up to {}", MAX_CONSTANT) });
+                }
+                Term::Constant(n)
+            } else {
+                return Err(scanner.error("expected dice, as `2d6`, or a number"));
+            };
+            let source: String = scanner.chars[start..scanner.current].iter().collect();
+            terms.push((negative, term, source));
+        }
+
+        let count: usize = terms.iter().map(|(_, term, _)| match term {
+            Term::Dice(dice) => dice.count,
+            Term::Constant(_) => 0,
+        }).sum();
+        if count > MAX_DICE {
+            return Err(ExprError { position: 0, message: format!("at most {} dice at once", MAX_DICE) });
+        }
+
+        if let Some(select) = advantage {
+            let position = terms_input.chars().count();
+            let single = terms.iter_mut().find_map(|(_, term, source)| match term {
+                Term::Dice(dice) if dice.count == 1 && dice.select.is_none() => Some((dice, source)),
+                _ => None,
+            });
+            match single {
+                Some((dice, source)) => {
+                    dice.count = 2;
+                    dice.select = Some(select);
+                    source.push(' ');
+                    source.push_str(&lowercase);
+                },
+                None => return Err(ExprError {
+                    position,
+                    message: String::from("expected a single die to roll with advantage, as `d20`"),
+                }),
+            }
+        }
+
+        Ok(Self { terms })
+    }
+
+    /// Throws the dice.
+    pub fn roll(&self, rng: &mut Rng) -> Outcome {
+        // what is left for explosions
+        let mut spare = MAX_DICE - self.terms.iter().map(|(_, term, _)| match term {
+            Term::Dice(dice) => dice.count,
+            Term::Constant(_) => 0,
+        }).sum::<usize>();
+
+        let groups: Vec<Group> = self.terms.iter().map(|(negative, term, source)| {
+            let (dice, total) = match term {
+                Term::Constant(n) => (Vec::new(), *n as i64),
+                Term::Dice(dice) => {
+                    let thrown = dice.throw(rng, &mut spare);
+                    let total = thrown.iter().filter(|die| die.kept).map(|die| die.value as i64).sum();
+                    (thrown, total)
+                },
+            };
+            Group {
+                source: source.clone(),
+                negative: *negative,
+                dice,
+                total: if *negative { -total } else { total },
+            }
+        }).collect();
+
+        let total = groups.iter().map(|group| group.total).sum();
+        Outcome { groups, total }
+    }
+}
+
+impl Dice {
+    /// Throws the dice, exploding as long as `spare` dice are left.
+    fn throw(&self, rng: &mut Rng, spare: &mut usize) -> Vec<Die> {
+        let mut thrown = Vec::with_capacity(self.count);
+        for _ in 0..self.count {
+            loop {
+                let value = rng.between(1, self.sides);
+                let exploded = self.explode && value == self.sides && *spare > 0;
+                thrown.push(Die { value, kept: true, exploded });
+                if !exploded {
+                    break;
+                }
+                *spare -= 1;
+            }
+        }
+
+        // from the lowest to the highest, the first ones on ties
+        let mut order: Vec<usize> = (0..thrown.len()).collect();
+        order.sort_by_key(|i| thrown[*i].value);
+        let dropped = match self.select {
+            None => 0..0,
+            Some(Select::KeepHighest(n)) => 0..thrown.len() - n,
+            Some(Select::KeepLowest(n)) => n..thrown.len(),
+            Some(Select::DropHighest(n)) => thrown.len() - n..thrown.len(),
+            Some(Select::DropLowest(n)) => 0..n,
+        };
+        for i in &order[dropped] {
+            thrown[*i].kept = false;
+        }
+        thrown
+    }
+}
+
+/// A line per term, the dropped dice struck through, then the total.
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, group) in self.groups.iter().enumerate() {
+            let sign = match (group.negative, i) {
+                (true, _) => "-",
+                (false, 0) => "",
+                (false, _) => "+",
+            };
+            if group.dice.is_empty() {
+                writeln!(f, "{}{}", sign, group.source)?;
+                continue;
+            }
+            let dice: Vec<String> = group.dice.iter().map(|die| {
+                let exploded = if die.exploded { "!" } else { "" };
+                if die.kept {
+                    format!("{}{}", die.value, exploded)
+                } else {
+                    format!("~~{}{}~~", die.value, exploded)
+                }
+            }).collect();
+            writeln!(f, "{}{}: [{}] = {}", sign, group.source, dice.join(", "), group.total.abs())?;
+        }
+        write!(f, "Total: **{}**", self.total)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dice(count: usize, sides: u64, select: Option<Select>, explode: bool) -> Term {
+        Term::Dice(Dice { count, sides, select, explode })
+    }
+
+    fn terms(input: &str) -> Vec<(bool, Term)> {
+        Roll::parse(input).unwrap().terms.into_iter().map(|(negative, term, _)| (negative, term)).collect()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(terms("4d6kh3+2"), [(false, dice(4, 6, Some(Select::KeepHighest(3)), false)),
+                                       (false, Term::Constant(2))]);
+        assert_eq!(terms(" d20 - 1 "), [(false, dice(1, 20, None, false)), (true, Term::Constant(1))]);
+        assert_eq!(terms("-2D%"), [(true, dice(2, 100, None, false))]);
+        assert_eq!(terms("3d6!d1"), [(false, dice(3, 6, Some(Select::DropLowest(1)), true))]);
+        assert_eq!(terms("2d8kl1 + 5d4dh2"), [(false, dice(2, 8, Some(Select::KeepLowest(1)), false)),
+                                              (false, dice(5, 4, Some(Select::DropHighest(2)), false))]);
+
+        let roll = Roll::parse("1d20 + 5 adv").unwrap();
+        assert_eq!(roll.terms[0], (false, dice(2, 20, Some(Select::KeepHighest(1)), false), String::from("1d20 adv")));
+        assert_eq!(terms("d20 Dis")[0].1, dice(2, 20, Some(Select::KeepLowest(1)), false));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |input: &str| Roll::parse(input).unwrap_err();
+        assert_eq!(error("2d"), ExprError { position: 2, message: String::from("expected the number of sides") });
+        assert_eq!(error("2d6 3"), ExprError { position: 4, message: String::from("expected `+` or `-`") });
+        assert_eq!(error("2d6 +"), ExprError { position: 5, message: String::from("expected dice, as `2d6`, or a number") });
+        assert_eq!(error("1 + 0d6").position, 4);
+        assert_eq!(error("1d1001").message, "expected from 1 to 1000 sides");
+        assert_eq!(error("2d6kh3").message, "there are only 2 dice");
+        assert_eq!(error("4d6kh3dl1").position, 6);
+        assert_eq!(error("1d1!").message, "dice of one side cannot explode");
+        assert_eq!(error("60d6 + 60d6").message, "at most 100 dice at once");
+        assert_eq!(error("2d20 adv"), ExprError {
+            position: 5,
+            message: String::from("expected a single die to roll with advantage, as `d20`"),
+        });
+        assert!(Roll::parse("").is_err());
+    }
+
+    #[test]
+    fn test_roll() {
+        let mut rng = Rng::new(3);
+        for _ in 0..100 {
+            let outcome = Roll::parse("4d6kh3 - 2").unwrap().roll(&mut rng);
+            let dice = &outcome.groups[0].dice;
+            assert_eq!(dice.len(), 4);
+            assert!(dice.iter().all(|die| (1..=6).contains(&die.value)));
+            assert_eq!(dice.iter().filter(|die| die.kept).count(), 3);
+            let lowest = dice.iter().map(|die| die.value).min().unwrap();
+            let dropped = dice.iter().find(|die| !die.kept).unwrap();
+            assert_eq!(dropped.value, lowest);
+            assert_eq!(outcome.total, outcome.groups[0].total - 2);
+        }
+        // the same rolls from the same seed
+        let roll = Roll::parse("10d20").unwrap();
+        assert_eq!(roll.roll(&mut Rng::new(9)), roll.roll(&mut Rng::new(9)));
+    }
+
+    #[test]
+    fn test_explode() {
+        let mut rng = Rng::new(5);
+        let outcome = Roll::parse("50d2!").unwrap().roll(&mut rng);
+        let dice = &outcome.groups[0].dice;
+        assert!(dice.len() > 50 && dice.len() <= MAX_DICE);
+        assert!(dice.iter().all(|die| !die.exploded || die.value == 2));
+        assert_eq!(outcome.total, dice.iter().map(|die| die.value as i64).sum::<i64>());
+    }
+
+    #[test]
+    fn test_display() {
+        let outcome = Outcome {
+            groups: vec![
+                Group {
+                    source: String::from("3d6!kh2"),
+                    negative: false,
+                    dice: vec![
+                        Die { value: 6, kept: true, exploded: true },
+                        Die { value: 2, kept: true, exploded: false },
+                        Die { value: 1, kept: false, exploded: false },
+                    ],
+                    total: 8,
+                },
+                Group { source: String::from("1"), negative: true, dice: Vec::new(), total: -1 },
+            ],
+            total: 7,
+        };
+        assert_eq!(outcome.to_string(), "3d6!kh2: [6!, 2, ~~1~~] = 8\n-1\nTotal: **7**");
+    }
+}
@@ -0,0 +1,906 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use super::expr::{Arithmetic, Op};
+use super::functions::{Constant, Function};
+use super::matrix::Scalar;
+use super::scope::Number;
+
+/// Limbs are in base 10^9, to print them easily.
+const BASE: u64 = 1_000_000_000;
+const BASE_DIGITS: usize = 9;
+
+/// Most digits of the numerator and of the denominator of a result, not to
+/// compute numbers too large to be shown in a message.
+pub const MAX_DIGITS: usize = 3000;
+
+/// Decimals shown of a rational which is not a decimal number.
+const DECIMALS: usize = 30;
+
+/// An integer of any size.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BigInt {
+    negative: bool,
+    /// Least significant limb first, without leading zeros, zero having none.
+    limbs: Vec<u32>,
+}
+
+fn trim(limbs: &mut Vec<u32>) {
+    while limbs.last() == Some(&0) {
+        limbs.pop();
+    }
+}
+
+fn cmp_magnitudes(a: &[u32], b: &[u32]) -> Ordering {
+    a.len().cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+fn add_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut sum = Vec::with_capacity(a.len().max(b.len()) + 1);
+    let mut carry = 0;
+    for i in 0..a.len().max(b.len()) {
+        let limb = *a.get(i).unwrap_or(&0) as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        sum.push((limb % BASE) as u32);
+        carry = limb / BASE;
+    }
+    if carry > 0 {
+        sum.push(carry as u32);
+    }
+    sum
+}
+
+/// Subtracts `b` from `a`, which must be at least `b`.
+fn sub_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut difference = Vec::with_capacity(a.len());
+    let mut borrow = 0;
+    for (i, &limb) in a.iter().enumerate() {
+        let subtrahend = *b.get(i).unwrap_or(&0) as i64 + borrow;
+        let mut limb = limb as i64 - subtrahend;
+        borrow = 0;
+        if limb < 0 {
+            limb += BASE as i64;
+            borrow = 1;
+        }
+        difference.push(limb as u32);
+    }
+    trim(&mut difference);
+    difference
+}
+
+fn mul_magnitudes(a: &[u32], b: &[u32]) -> Vec<u32> {
+    if a.is_empty() || b.is_empty() {
+        return Vec::new();
+    }
+    let mut product = vec![0u32; a.len() + b.len()];
+    for (i, &x) in a.iter().enumerate() {
+        let mut carry = 0;
+        for (j, &y) in b.iter().enumerate() {
+            let limb = product[i + j] as u64 + x as u64 * y as u64 + carry;
+            product[i + j] = (limb % BASE) as u32;
+            carry = limb / BASE;
+        }
+        product[i + b.len()] = carry as u32;
+    }
+    trim(&mut product);
+    product
+}
+
+/// Divides `a` by a single limb, which must not be zero, giving the quotient
+/// and the remainder.
+fn div_rem_limb(a: &[u32], b: u32) -> (Vec<u32>, u32) {
+    let mut quotient = vec![0u32; a.len()];
+    let mut remainder = 0;
+    for (i, &limb) in a.iter().enumerate().rev() {
+        let current = remainder * BASE + limb as u64;
+        quotient[i] = (current / b as u64) as u32;
+        remainder = current % b as u64;
+    }
+    trim(&mut quotient);
+    (quotient, remainder as u32)
+}
+
+/// Divides `a` by `b`, which must not be zero, giving the quotient and the
+/// remainder, by long division guessing each limb of the quotient from the
+/// leading limbs (Knuth's algorithm D).
+fn div_rem_magnitudes(a: &[u32], b: &[u32]) -> (Vec<u32>, Vec<u32>) {
+    if cmp_magnitudes(a, b) == Ordering::Less {
+        return (Vec::new(), a.to_vec());
+    }
+    if b.len() == 1 {
+        let (quotient, remainder) = div_rem_limb(a, b[0]);
+        let mut remainder = vec![remainder];
+        trim(&mut remainder);
+        return (quotient, remainder);
+    }
+
+    // both scaled for the leading limb of `b` to be at least half the base,
+    // the guesses then being at most two too large
+    let scale = (BASE / (*b.last().unwrap() as u64 + 1)) as u32;
+    let b = mul_magnitudes(b, &[scale]);
+    let mut remainder = mul_magnitudes(a, &[scale]);
+    remainder.resize(a.len() + 1, 0);
+    let n = b.len();
+    let (top, second) = (b[n - 1] as u64, b[n - 2] as u64);
+    let mut quotient = vec![0u32; a.len() - n + 1];
+
+    for j in (0..quotient.len()).rev() {
+        let leading = remainder[j + n] as u64 * BASE + remainder[j + n - 1] as u64;
+        let (mut guess, mut rest) = (leading / top, leading % top);
+        while guess >= BASE || guess * second > rest * BASE + remainder[j + n - 2] as u64 {
+            guess -= 1;
+            rest += top;
+            if rest >= BASE {
+                break;
+            }
+        }
+
+        // subtracts `guess` times `b` from the leading limbs
+        let (mut carry, mut borrow) = (0, 0);
+        for i in 0..n {
+            let product = guess * b[i] as u64 + carry;
+            carry = product / BASE;
+            let mut limb = remainder[i + j] as i64 - (product % BASE) as i64 - borrow;
+            borrow = 0;
+            if limb < 0 {
+                limb += BASE as i64;
+                borrow = 1;
+            }
+            remainder[i + j] = limb as u32;
+        }
+        let mut leading = remainder[j + n] as i64 - carry as i64 - borrow;
+        // rarely, the guess is still one too large: `b` is added back
+        if leading < 0 {
+            guess -= 1;
+            let mut carry = 0;
+            for i in 0..n {
+                let limb = remainder[i + j] as u64 + b[i] as u64 + carry;
+                remainder[i + j] = (limb % BASE) as u32;
+                carry = limb / BASE;
+            }
+            leading += carry as i64;
+        }
+        remainder[j + n] = leading as u32;
+        quotient[j] = guess as u32;
+    }
+
+    trim(&mut quotient);
+    trim(&mut remainder);
+    (quotient, div_rem_limb(&remainder, scale).0)
+}
+
+impl BigInt {
+    fn new(negative: bool, mut limbs: Vec<u32>) -> Self {
+        trim(&mut limbs);
+        Self {
+            negative: negative && !limbs.is_empty(),
+            limbs,
+        }
+    }
+
+    pub fn zero() -> Self {
+        Self::default()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    fn is_one(&self) -> bool {
+        !self.negative && self.limbs == [1]
+    }
+
+    fn is_even(&self) -> bool {
+        self.limbs.first().copied().unwrap_or(0) % 2 == 0
+    }
+
+    pub fn neg(&self) -> Self {
+        Self::new(!self.negative, self.limbs.clone())
+    }
+
+    pub fn abs(&self) -> Self {
+        Self::new(false, self.limbs.clone())
+    }
+
+    pub fn add(&self, other: &Self) -> Self {
+        if self.negative == other.negative {
+            return Self::new(self.negative, add_magnitudes(&self.limbs, &other.limbs));
+        }
+        match cmp_magnitudes(&self.limbs, &other.limbs) {
+            Ordering::Less => Self::new(other.negative, sub_magnitudes(&other.limbs, &self.limbs)),
+            _ => Self::new(self.negative, sub_magnitudes(&self.limbs, &other.limbs)),
+        }
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        self.add(&other.neg())
+    }
+
+    pub fn mul(&self, other: &Self) -> Self {
+        Self::new(self.negative != other.negative, mul_magnitudes(&self.limbs, &other.limbs))
+    }
+
+    /// Divides by `other`, which must not be zero, rounding toward zero, the
+    /// remainder having the sign of `self`.
+    pub fn div_rem(&self, other: &Self) -> (Self, Self) {
+        let (quotient, remainder) = div_rem_magnitudes(&self.limbs, &other.limbs);
+        (Self::new(self.negative != other.negative, quotient), Self::new(self.negative, remainder))
+    }
+
+    pub fn gcd(&self, other: &Self) -> Self {
+        let (mut a, mut b) = (self.abs(), other.abs());
+        while !b.is_zero() {
+            let remainder = a.div_rem(&b).1;
+            a = b;
+            b = remainder;
+        }
+        a
+    }
+
+    pub fn pow10(exponent: usize) -> Self {
+        let mut limbs = vec![0; exponent / BASE_DIGITS];
+        limbs.push(10u32.pow((exponent % BASE_DIGITS) as u32));
+        Self::new(false, limbs)
+    }
+
+    /// Number of decimal digits, zero having none.
+    pub fn digits(&self) -> usize {
+        match self.limbs.last() {
+            Some(top) => (self.limbs.len() - 1) * BASE_DIGITS + top.to_string().len(),
+            None => 0,
+        }
+    }
+
+    pub fn to_u64(&self) -> Option<u64> {
+        if self.negative || self.limbs.len() > 2 {
+            return None;
+        }
+        Some(self.limbs.iter().rev().fold(0, |n, &limb| n * BASE + limb as u64))
+    }
+
+    pub fn from_i128(n: i128) -> Self {
+        let mut magnitude = if n < 0 { (n as u128).wrapping_neg() } else { n as u128 };
+        let mut limbs = Vec::new();
+        while magnitude > 0 {
+            limbs.push((magnitude % BASE as u128) as u32);
+            magnitude /= BASE as u128;
+        }
+        Self::new(n < 0, limbs)
+    }
+
+    pub fn to_i128(&self) -> Option<i128> {
+        let magnitude = self.limbs.iter().rev()
+            .try_fold(0i128, |n, &limb| n.checked_mul(BASE as i128)?.checked_add(limb as i128))?;
+        Some(if self.negative { -magnitude } else { magnitude })
+    }
+
+    /// Reads decimal digits, without a sign, none if one is not a digit.
+    fn from_digits(digits: &str) -> Option<Self> {
+        if digits.is_empty() || !digits.bytes().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+
+        let digits = digits.as_bytes();
+        let limbs = (0..digits.len()).rev().step_by(BASE_DIGITS)
+            .map(|end| {
+                let start = (end + 1).saturating_sub(BASE_DIGITS);
+                digits[start..=end].iter().fold(0, |limb, c| limb * 10 + (c - b'0') as u32)
+            })
+            .collect();
+        Some(Self::new(false, limbs))
+    }
+
+    /// Reads digits in `radix`, from 2 to 36, none if one is not a digit
+    /// there.
+    pub fn from_str_radix(digits: &str, radix: u32) -> Option<Self> {
+        if digits.is_empty() {
+            return None;
+        }
+        let radix_big = BigInt::from(radix as u64);
+        digits.chars().try_fold(BigInt::zero(), |n, c| {
+            let digit = c.to_digit(radix)?;
+            Some(n.mul(&radix_big).add(&BigInt::from(digit as u64)))
+        })
+    }
+
+    /// Writes the integer in `radix`, from 2 to 36, with lowercase letters.
+    pub fn to_str_radix(&self, radix: u32) -> String {
+        let radix_big = BigInt::from(radix as u64);
+        let mut digits = Vec::new();
+        let mut n = self.abs();
+        while !n.is_zero() {
+            let (quotient, remainder) = n.div_rem(&radix_big);
+            let digit = remainder.to_u64().unwrap_or(0) as u32;
+            digits.push(std::char::from_digit(digit, radix).unwrap_or('?'));
+            n = quotient;
+        }
+        if digits.is_empty() {
+            digits.push('0');
+        }
+        if self.negative {
+            digits.push('-');
+        }
+        digits.iter().rev().collect()
+    }
+}
+
+impl From<u64> for BigInt {
+    fn from(mut n: u64) -> Self {
+        let mut limbs = Vec::new();
+        while n > 0 {
+            limbs.push((n % BASE) as u32);
+            n /= BASE;
+        }
+        Self::new(false, limbs)
+    }
+}
+
+impl Ord for BigInt {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self.negative, other.negative) {
+            (false, true) => Ordering::Greater,
+            (true, false) => Ordering::Less,
+            (false, false) => cmp_magnitudes(&self.limbs, &other.limbs),
+            (true, true) => cmp_magnitudes(&other.limbs, &self.limbs),
+        }
+    }
+}
+
+impl PartialOrd for BigInt {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.negative {
+            write!(f, "-")?;
+        }
+        match self.limbs.split_last() {
+            Some((top, rest)) => {
+                write!(f, "{}", top)?;
+                for limb in rest.iter().rev() {
+                    write!(f, "{:09}", limb)?;
+                }
+                Ok(())
+            },
+            None => write!(f, "0"),
+        }
+    }
+}
+
+/// The radix and the digits of an integer written `0x1f`, `0o17` or `0b101`.
+pub fn split_radix(text: &str) -> Option<(u32, &str)> {
+    let radix = match text.get(..2)? {
+        "0x" | "0X" => 16,
+        "0o" | "0O" => 8,
+        "0b" | "0B" => 2,
+        _ => return None,
+    };
+    Some((radix, &text[2..]))
+}
+
+/// An optionally negative run of decimal digits.
+impl FromStr for BigInt {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (negative, digits) = split_sign(s);
+        let integer = Self::from_digits(digits).ok_or_else(|| format!("Invalid integer `{}`", s))?;
+        Ok(if negative { integer.neg() } else { integer })
+    }
+}
+
+/// Splits one optional `+` or `-` from `s`, telling whether it was a `-`.
+fn split_sign(s: &str) -> (bool, &str) {
+    if let Some(unsigned) = s.strip_prefix('-') {
+        (true, unsigned)
+    } else {
+        (false, s.strip_prefix('+').unwrap_or(s))
+    }
+}
+
+/// An exact fraction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Rational {
+    numerator: BigInt,
+    /// Always positive, and coprime with the numerator.
+    denominator: BigInt,
+}
+
+impl Rational {
+    /// The fraction `numerator / denominator`, the latter not being zero.
+    pub fn new(numerator: BigInt, denominator: BigInt) -> Self {
+        if denominator.is_one() {
+            return Self::integer(numerator);
+        }
+        let gcd = numerator.gcd(&denominator);
+        let (mut numerator, mut denominator) = (numerator.div_rem(&gcd).0, denominator.div_rem(&gcd).0);
+        if denominator.is_negative() {
+            numerator = numerator.neg();
+            denominator = denominator.neg();
+        }
+        Self { numerator, denominator }
+    }
+
+    pub fn integer(n: BigInt) -> Self {
+        Self { numerator: n, denominator: BigInt::from(1) }
+    }
+
+    pub fn numerator(&self) -> &BigInt {
+        &self.numerator
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.denominator.is_one()
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.numerator.is_zero()
+    }
+
+    pub fn neg(&self) -> Self {
+        Self { numerator: self.numerator.neg(), denominator: self.denominator.clone() }
+    }
+
+    pub fn abs(&self) -> Self {
+        Self { numerator: self.numerator.abs(), denominator: self.denominator.clone() }
+    }
+
+    /// Adds `other`, reducing by the common factors of the denominators
+    /// only, which are smaller than the sum.
+    pub fn add(&self, other: &Self) -> Self {
+        if self.is_integer() && other.is_integer() {
+            return Self::integer(self.numerator.add(&other.numerator));
+        }
+        let gcd = self.denominator.gcd(&other.denominator);
+        let (left, right) = (self.denominator.div_rem(&gcd).0, other.denominator.div_rem(&gcd).0);
+        let numerator = self.numerator.mul(&right).add(&other.numerator.mul(&left));
+        if numerator.is_zero() {
+            return Self::integer(numerator);
+        }
+        let common = numerator.gcd(&gcd);
+        Self {
+            numerator: numerator.div_rem(&common).0,
+            denominator: left.mul(&other.denominator.div_rem(&common).0),
+        }
+    }
+
+    pub fn sub(&self, other: &Self) -> Self {
+        self.add(&other.neg())
+    }
+
+    /// Multiplies by `other`, reducing each numerator by the denominator of
+    /// the other fraction beforehand.
+    pub fn mul(&self, other: &Self) -> Self {
+        if self.is_zero() || other.is_zero() {
+            return Self::integer(BigInt::zero());
+        }
+        let (first, second) = (self.numerator.gcd(&other.denominator), other.numerator.gcd(&self.denominator));
+        Self {
+            numerator: self.numerator.div_rem(&first).0.mul(&other.numerator.div_rem(&second).0),
+            denominator: self.denominator.div_rem(&second).0.mul(&other.denominator.div_rem(&first).0),
+        }
+    }
+
+    /// Divides by `other`, `None` if it is zero.
+    pub fn div(&self, other: &Self) -> Option<Self> {
+        if other.is_zero() {
+            return None;
+        }
+        Some(self.mul(&other.inverse()))
+    }
+
+    /// The inverse of the fraction, which must not be zero.
+    fn inverse(&self) -> Self {
+        if self.numerator.is_negative() {
+            Self { numerator: self.denominator.neg(), denominator: self.numerator.neg() }
+        } else {
+            Self { numerator: self.denominator.clone(), denominator: self.numerator.clone() }
+        }
+    }
+
+    /// Remainder of the division by `other` rounded toward zero, like `%` on
+    /// floats, `None` if `other` is zero.
+    pub fn rem(&self, other: &Self) -> Option<Self> {
+        let quotient = self.div(other)?.trunc();
+        Some(self.sub(&other.mul(&quotient)))
+    }
+
+    pub fn trunc(&self) -> Self {
+        Self::integer(self.numerator.div_rem(&self.denominator).0)
+    }
+
+    pub fn floor(&self) -> Self {
+        let (quotient, remainder) = self.numerator.div_rem(&self.denominator);
+        if remainder.is_negative() {
+            Self::integer(quotient.sub(&BigInt::from(1)))
+        } else {
+            Self::integer(quotient)
+        }
+    }
+
+    pub fn ceil(&self) -> Self {
+        self.neg().floor().neg()
+    }
+
+    /// Rounds half-way cases away from zero, like `f64::round`.
+    pub fn round(&self) -> Self {
+        let half = Self::new(BigInt::from(1), BigInt::from(2));
+        let rounded = self.abs().add(&half).trunc();
+        if self.numerator.is_negative() { rounded.neg() } else { rounded }
+    }
+
+    /// Raises to the power `exponent`, which must be an integer.
+    pub fn pow(&self, exponent: &Self) -> Result<Self, String> {
+        if !exponent.is_integer() {
+            return Err(String::from("no exact power with a fractional exponent"));
+        }
+        let exponent = &exponent.numerator;
+        if self.is_zero() {
+            return match exponent.cmp(&BigInt::zero()) {
+                Ordering::Less => Err(String::from("division by zero")),
+                Ordering::Equal => Ok(Self::integer(BigInt::from(1))),
+                Ordering::Greater => Ok(self.clone()),
+            };
+        }
+        if self.abs() == Self::integer(BigInt::from(1)) {
+            return Ok(if exponent.is_even() { self.abs() } else { self.clone() });
+        }
+
+        // any other base at least doubles its numerator or denominator
+        let mut n = match exponent.abs().to_u64() {
+            Some(n) if n <= 4 * MAX_DIGITS as u64 => n,
+            _ => return Err(String::from("result too large")),
+        };
+        // the powers of coprime integers being coprime, no need to reduce
+        let mut power = Self::integer(BigInt::from(1));
+        let mut base = self.clone();
+        loop {
+            if n & 1 == 1 {
+                power = Self {
+                    numerator: power.numerator.mul(&base.numerator),
+                    denominator: power.denominator.mul(&base.denominator),
+                };
+                power.check_size()?;
+            }
+            n >>= 1;
+            if n == 0 {
+                break;
+            }
+            base = Self {
+                numerator: base.numerator.mul(&base.numerator),
+                denominator: base.denominator.mul(&base.denominator),
+            };
+            base.check_size()?;
+        }
+
+        if exponent.is_negative() {
+            Ok(power.inverse())
+        } else {
+            Ok(power)
+        }
+    }
+
+    /// The factorial, of a natural number.
+    pub fn factorial(&self) -> Result<Self, String> {
+        let n = match self.numerator.to_u64() {
+            Some(n) if self.is_integer() => n,
+            _ => return Err(String::from("factorial of a non-natural number")),
+        };
+        let mut product = BigInt::from(1);
+        for i in 2..=n {
+            product = product.mul(&BigInt::from(i));
+            if product.digits() > MAX_DIGITS {
+                return Err(String::from("result too large"));
+            }
+        }
+        Ok(Self::integer(product))
+    }
+
+    fn check_size(&self) -> Result<(), String> {
+        if self.numerator.digits() > MAX_DIGITS || self.denominator.digits() > MAX_DIGITS {
+            Err(String::from("result too large"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The integer the number is, for bitwise operations.
+    fn bitwise_integer(&self) -> Result<i128, String> {
+        if !self.is_integer() {
+            return Err(String::from("bitwise operations take integers"));
+        }
+        self.numerator.to_i128().ok_or_else(|| String::from("too large for bitwise operations"))
+    }
+
+    /// The nearest float, infinite if it is too large for one.
+    pub fn to_f64(&self) -> f64 {
+        // an integer quotient of about 20 digits, then shifted back
+        let shift = 20 + self.denominator.digits() as isize - self.numerator.digits() as isize;
+        let quotient = if shift >= 0 {
+            self.numerator.mul(&BigInt::pow10(shift as usize)).div_rem(&self.denominator).0
+        } else {
+            self.numerator.div_rem(&self.denominator.mul(&BigInt::pow10(-shift as usize))).0
+        };
+        format!("{}e{}", quotient, -shift).parse().unwrap_or(f64::NAN)
+    }
+
+    /// Writes the number in decimal with at most `decimals` decimals, telling
+    /// whether it is exact.
+    fn decimal(&self, decimals: usize) -> (String, bool) {
+        let (integer, remainder) = self.numerator.abs().div_rem(&self.denominator);
+        let (fraction, rest) = remainder.mul(&BigInt::pow10(decimals)).div_rem(&self.denominator);
+
+        let mut decimal = String::new();
+        if self.numerator.is_negative() {
+            decimal.push('-');
+        }
+        decimal.push_str(&integer.to_string());
+        let fraction = format!("{:0>width$}", fraction.to_string(), width = decimals);
+        let fraction = fraction.trim_end_matches('0');
+        if !fraction.is_empty() {
+            decimal.push('.');
+            decimal.push_str(fraction);
+        }
+        (decimal, rest.is_zero())
+    }
+
+    /// Shows the number to people: in decimal if it is exact, or else as a
+    /// fraction followed by its first decimals.
+    pub fn pretty(&self) -> String {
+        // a denominator dividing a power of ten divides the one with four
+        // times its digits
+        let (decimal, exact) = self.decimal(4 * self.denominator.digits());
+        if exact && decimal.len() <= self.to_string().len() + DECIMALS {
+            return decimal;
+        }
+        format!("{} ≈ {}", self, self.decimal(DECIMALS).0)
+    }
+}
+
+impl Ord for Rational {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.numerator.mul(&other.denominator).cmp(&other.numerator.mul(&self.denominator))
+    }
+}
+
+impl PartialOrd for Rational {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// `numerator/denominator`, or only the numerator of integers, which is how
+/// expressions read them back.
+impl fmt::Display for Rational {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_integer() {
+            write!(f, "{}", self.numerator)
+        } else {
+            write!(f, "{}/{}", self.numerator, self.denominator)
+        }
+    }
+}
+
+/// A decimal number, as `-12`, `1.5`, `.5` or `1.5e-3`, or an integer in
+/// another base, as `0xff`, `0o17` or `0b101`.
+impl FromStr for Rational {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid number `{}`", s);
+        let (negative, unsigned) = split_sign(s);
+        if let Some((radix, digits)) = split_radix(unsigned) {
+            let integer = BigInt::from_str_radix(digits, radix).ok_or_else(invalid)?;
+            return Ok(Self::integer(if negative { integer.neg() } else { integer }));
+        }
+        let (mantissa, exponent) = match unsigned.find(&['e', 'E'][..]) {
+            Some(e) => (&unsigned[..e], unsigned[e + 1..].parse::<i64>().map_err(|_| invalid())?),
+            None => (unsigned, 0),
+        };
+        let (integer, fraction) = match mantissa.find('.') {
+            Some(dot) => (&mantissa[..dot], &mantissa[dot + 1..]),
+            None => (mantissa, ""),
+        };
+        if integer.is_empty() && fraction.is_empty() {
+            return Err(invalid());
+        }
+
+        let digits = format!("{}{}", integer, fraction);
+        let numerator = BigInt::from_digits(&digits).ok_or_else(invalid)?;
+        let numerator = if negative { numerator.neg() } else { numerator };
+        let exponent = exponent - fraction.len() as i64;
+        if exponent.abs() > 2 * MAX_DIGITS as i64 {
+            return Err(String::from("result too large"));
+        }
+
+        Ok(if exponent >= 0 {
+            Self::integer(numerator.mul(&BigInt::pow10(exponent as usize)))
+        } else {
+            Self::new(numerator, BigInt::pow10(-exponent as usize))
+        })
+    }
+}
+
+/// Exact evaluation, the functions and constants without an exact value
+/// being refused.
+impl Arithmetic for Rational {
+    fn literal(_value: f64, text: &str) -> Result<Self, String> {
+        text.parse()
+    }
+
+    /// Refuses the variables computed with floats, which are not exact.
+    fn variable(value: &Number) -> Result<Self, String> {
+        match value {
+            Number::Exact(value) => Ok(value.clone()),
+            Number::Float(_) => Err(String::from("has no exact value")),
+            Number::Matrix(_) => Err(String::from("is a matrix")),
+        }
+    }
+
+    fn constant(constant: &Constant) -> Result<Self, String> {
+        Err(format!("`{}` has no exact value", constant.name))
+    }
+
+    fn negate(self) -> Self {
+        self.neg()
+    }
+
+    fn binary(op: Op, left: Self, right: Self) -> Result<Self, String> {
+        match op {
+            Op::Add => Ok(left.add(&right)),
+            Op::Sub => Ok(left.sub(&right)),
+            Op::Mul => Ok(left.mul(&right)),
+            Op::Div => left.div(&right).ok_or_else(|| String::from("division by zero")),
+            Op::Rem => left.rem(&right).ok_or_else(|| String::from("modulo by zero")),
+            Op::Pow => left.pow(&right),
+            op => op.bitwise(left.bitwise_integer()?, right.bitwise_integer()?).map(|n| Self::integer(BigInt::from_i128(n))),
+        }
+    }
+
+    fn to_integer(&self) -> Option<i64> {
+        let magnitude = self.numerator.abs().to_u64().filter(|&n| self.is_integer() && n <= i32::MAX as u64)? as i64;
+        Some(if self.numerator.is_negative() { -magnitude } else { magnitude })
+    }
+
+    fn call(function: &Function, args: &[Self]) -> Result<Self, String> {
+        match function.name {
+            "abs" => Ok(args[0].abs()),
+            "floor" => Ok(args[0].floor()),
+            "ceil" => Ok(args[0].ceil()),
+            "round" => Ok(args[0].round()),
+            "min" => Ok(args.iter().min().cloned().unwrap()),
+            "max" => Ok(args.iter().max().cloned().unwrap()),
+            "fact" => args[0].factorial(),
+            name => Err(format!("`{}` has no exact value", name)),
+        }
+    }
+
+    fn check(&self) -> Result<(), String> {
+        self.check_size()
+    }
+}
+
+/// Exact numbers being compared to zero only, elimination just avoids them.
+impl Scalar for Rational {
+    fn magnitude(&self) -> f64 {
+        self.to_f64().abs()
+    }
+
+    fn is_negligible(&self, _scale: f64) -> bool {
+        self.is_zero()
+    }
+
+    fn size(&self) -> usize {
+        self.numerator.digits() + self.denominator.digits()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rational(s: &str) -> Rational {
+        s.parse().unwrap()
+    }
+
+    fn int(s: &str) -> BigInt {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_big_int() {
+        let a = int("123456789012345678901234567890");
+        let b = int("-987654321098765432109876543210");
+        assert_eq!(a.add(&b).to_string(), "-864197532086419753208641975320");
+        assert_eq!(a.sub(&b).to_string(), "1111111110111111111011111111100");
+        assert_eq!(a.mul(&b).to_string(), "-121932631137021795226185032733622923332237463801111263526900");
+        let (quotient, remainder) = b.div_rem(&a);
+        assert_eq!((quotient.to_string(), remainder.to_string()), (String::from("-8"), String::from("-9000000000900000000090")));
+        assert_eq!(int("1000000000").div_rem(&int("7")).0.to_string(), "142857142");
+        assert_eq!(int("-0").to_string(), "0");
+        assert_eq!(int("462").gcd(&int("-1071")).to_string(), "21");
+        assert_eq!(BigInt::pow10(20).to_string(), "100000000000000000000");
+        assert_eq!(BigInt::pow10(20).digits(), 21);
+        assert!(int("-5") < int("3") && int("-5") < int("-4"));
+        assert!("1a".parse::<BigInt>().is_err());
+    }
+
+    #[test]
+    fn test_long_division() {
+        let power = |base: u64, exponent: u64| Rational::integer(BigInt::from(base)).pow(&Rational::integer(BigInt::from(exponent))).unwrap().numerator;
+        for (a, b) in [(power(7, 900), power(3, 400).add(&int("12345"))), (power(10, 300), power(10, 150).sub(&int("1"))), (power(2, 500), int("999999999"))].iter() {
+            let (quotient, remainder) = a.div_rem(b);
+            assert_eq!(quotient.mul(b).add(&remainder), *a);
+            assert!(!remainder.is_negative() && remainder < *b);
+        }
+        assert_eq!(int("999999999999999999").div_rem(&int("1000000000")).0.to_string(), "999999999");
+    }
+
+    #[test]
+    fn test_rational() {
+        assert_eq!(rational("0.1").add(&rational("0.2")).pretty(), "0.3");
+        assert_eq!(rational("1").div(&rational("3")).unwrap().pretty(), "1/3 ≈ 0.333333333333333333333333333333");
+        assert_eq!(rational("-1.5e-3").to_string(), "-3/2000");
+        assert_eq!(rational("-1.5e-3").pretty(), "-0.0015");
+        assert_eq!(rational(".5").mul(&rational("4")).to_string(), "2");
+        assert!(rational("1").div(&rational("0")).is_none());
+        assert_eq!(rational("-7").rem(&rational("3")).unwrap().to_string(), "-1");
+        assert_eq!(rational("-2.5").floor().to_string(), "-3");
+        assert_eq!(rational("-2.5").ceil().to_string(), "-2");
+        assert_eq!(rational("-2.5").round().to_string(), "-3");
+        assert_eq!(rational("2.4").round().to_string(), "2");
+        assert!(rational("-0.5") < rational("0.25"));
+        assert!("1.2.3".parse::<Rational>().is_err() && ".".parse::<Rational>().is_err());
+        assert_eq!(rational("+5").to_string(), "5");
+        assert!("--5".parse::<Rational>().is_err() && "-+5".parse::<Rational>().is_err());
+        assert!("1.-5".parse::<Rational>().is_err() && "+-5".parse::<BigInt>().is_err());
+    }
+
+    #[test]
+    fn test_pow_and_factorial() {
+        assert_eq!(rational("2").pow(&rational("100")).unwrap().to_string(), "1267650600228229401496703205376");
+        assert_eq!(rational("2").div(&rational("3")).unwrap().pow(&rational("-2")).unwrap().to_string(), "9/4");
+        assert_eq!(rational("-1").pow(&rational("1000001")).unwrap().to_string(), "-1");
+        assert_eq!(rational("0").pow(&rational("0")).unwrap().to_string(), "1");
+        assert!(rational("0").pow(&rational("-1")).is_err());
+        assert!(rational("2").pow(&rational("0.5")).is_err());
+        assert_eq!(rational("2").pow(&rational("1e9")).unwrap_err(), "result too large");
+        assert_eq!(rational("25").factorial().unwrap().to_string(), "15511210043330985984000000");
+        assert_eq!(rational("1000").factorial().unwrap().numerator().digits(), 2568);
+        assert_eq!(rational("2").pow(&rational("3000")).unwrap().numerator().digits(), 904);
+        assert_eq!(rational("2000").factorial().unwrap_err(), "result too large");
+        assert!(rational("2.5").factorial().is_err());
+    }
+
+    #[test]
+    fn test_conversions() {
+        assert_eq!(rational("0.1").to_f64(), 0.1);
+        assert_eq!(rational("-1").div(&rational("3")).unwrap().to_f64(), -1.0 / 3.0);
+        assert_eq!(rational("1e300").to_f64(), 1e300);
+        assert!(rational("1e400").to_f64().is_infinite());
+    }
+
+    #[test]
+    fn test_radix() {
+        assert_eq!(rational("0xff").to_string(), "255");
+        assert_eq!(rational("-0b1010").to_string(), "-10");
+        assert_eq!(rational("0o777").to_string(), "511");
+        assert_eq!(rational("0x1E3").to_string(), "483");
+        assert!("0b102".parse::<Rational>().is_err() && "0x".parse::<Rational>().is_err());
+        assert_eq!(int("-255").to_str_radix(16), "-ff");
+        assert_eq!(int("0").to_str_radix(2), "0");
+        assert_eq!(BigInt::from_str_radix("zz", 36), Some(int("1295")));
+        let large = int("-170141183460469231731687303715884105727");
+        assert_eq!(large.to_i128(), Some(-i128::MAX));
+        assert_eq!(BigInt::from_i128(-i128::MAX), large);
+        assert_eq!(large.sub(&int("2")).to_i128(), None);
+    }
+}
@@ -0,0 +1,1072 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::exact::split_radix;
+use super::functions::{constant, function, Constant, Function};
+use super::scope::{Number, Scope};
+use super::units::{unit, Quantity};
+
+/// How deep user functions may call each other, not to recurse forever.
+const MAX_DEPTH: usize = 64;
+
+/// How deep expressions may nest, in parentheses, brackets, signs or powers,
+/// for their parsing and evaluation to fit on the stack, their evaluation
+/// going through the bodies of the user functions called too.
+const MAX_NESTING: usize = 200;
+
+/// The word before the units to convert to.
+pub const CONVERT: &str = "to";
+
+/// An error in an expression, at the position (in characters) of its cause.
+#[derive(Debug, PartialEq)]
+pub struct ExprError {
+    pub position: usize,
+    pub message: String,
+}
+
+impl ExprError {
+    fn new(position: usize, message: impl Into<String>) -> Self {
+        Self {
+            position,
+            message: message.into(),
+        }
+    }
+
+    /// Shows `expr` with a caret under the position of the error, followed by
+    /// the message.
+    pub fn pointer(&self, expr: &str) -> String {
+        format!("{}\n{}^ {}", expr, " ".repeat(self.position), self.message)
+    }
+}
+
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} (at {})", self.message, self.position + 1)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Pow,
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
+}
+
+impl Op {
+    /// Precedence of the operator, the higher binding the tighter.
+    fn precedence(self) -> u8 {
+        match self {
+            Op::BitOr => 1,
+            Op::BitXor => 2,
+            Op::BitAnd => 3,
+            Op::Shl | Op::Shr => 4,
+            Op::Add | Op::Sub => 5,
+            Op::Mul | Op::Div | Op::Rem => 6,
+            Op::Pow => 7,
+        }
+    }
+
+    /// Applies a bitwise operator to integers of any sign, in two's
+    /// complement, shifts multiplying or dividing by powers of two.
+    pub fn bitwise(self, left: i128, right: i128) -> Result<i128, String> {
+        match self {
+            Op::BitAnd => Ok(left & right),
+            Op::BitOr => Ok(left | right),
+            Op::BitXor => Ok(left ^ right),
+            Op::Shl | Op::Shr if right < 0 => Err(String::from("negative shift")),
+            Op::Shl if left == 0 => Ok(0),
+            Op::Shl => 2i128.checked_pow(right.min(u32::MAX as i128) as u32)
+                .and_then(|power| left.checked_mul(power))
+                .ok_or_else(|| String::from("result too large")),
+            Op::Shr if right >= 127 => Ok(if left < 0 { -1 } else { 0 }),
+            Op::Shr => Ok(left >> right),
+            _ => unreachable!("`{:?}` is not a bitwise operator", self),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    /// A number, and how it is written.
+    Number(f64, String),
+    Ident(String),
+    Op(Op),
+    /// The factorial `!`.
+    Bang,
+    /// The bitwise not `~`.
+    Tilde,
+    Comma,
+    Assign,
+    LParen,
+    RParen,
+    LBracket,
+    RBracket,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Number(_, text) => write!(f, "{}", text),
+            Token::Ident(name) => write!(f, "{}", name),
+            Token::Bang => write!(f, "!"),
+            Token::Tilde => write!(f, "~"),
+            Token::Comma => write!(f, ","),
+            Token::Assign => write!(f, "="),
+            Token::Op(op) => write!(f, "{}", match op {
+                Op::Add => "+",
+                Op::Sub => "-",
+                Op::Mul => "*",
+                Op::Div => "/",
+                Op::Rem => "%",
+                Op::Pow => "^",
+                Op::BitAnd => "&",
+                Op::BitOr => "|",
+                Op::BitXor => "^",
+                Op::Shl => "<<",
+                Op::Shr => ">>",
+            }),
+            Token::LParen => write!(f, "("),
+            Token::RParen => write!(f, ")"),
+            Token::LBracket => write!(f, "["),
+            Token::RBracket => write!(f, "]"),
+        }
+    }
+}
+
+/// The radix of the integer starting `chars`, if it is written in another
+/// base, as `0xff`.
+fn radix_at(chars: &[char]) -> Option<u32> {
+    let prefix: String = chars.iter().take(2).collect();
+    let (radix, _) = split_radix(&prefix)?;
+    chars.get(2).filter(|c| c.is_digit(radix)).map(|_| radix)
+}
+
+/// Splits `expr` into tokens, with their position in characters. `^` is an
+/// exclusive or in `programmer` mode, powers being written `**` there, or
+/// else a power.
+fn tokenize(expr: &str, programmer: bool) -> Result<Vec<(Token, usize)>, ExprError> {
+    let chars: Vec<char> = expr.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let (c, position) = (chars[i], i);
+        let token = match c {
+            ' ' | '\t' | '\n' => {
+                i += 1;
+                continue;
+            },
+            // an integer in another base, as `0xff`
+            '0' if radix_at(&chars[i..]).is_some() => {
+                let start = i;
+                while i < chars.len() && chars[i].is_ascii_alphanumeric() {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                let (radix, digits) = split_radix(&text).expect("Expected a base prefix");
+                let number = digits.chars()
+                    .try_fold(0.0, |number, c| c.to_digit(radix).map(|digit| number * radix as f64 + digit as f64))
+                    .ok_or_else(|| ExprError::new(start, format!("invalid number `{}`", text)))?;
+                tokens.push((Token::Number(number, text), start));
+                continue;
+            },
+            '0'..='9' | '.' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                // an exponent, as in `1.5e-3`
+                if i < chars.len() && (chars[i] == 'e' || chars[i] == 'E') {
+                    let sign = if i + 1 < chars.len() && (chars[i + 1] == '-' || chars[i + 1] == '+') { 1 } else { 0 };
+                    if i + 1 + sign < chars.len() && chars[i + 1 + sign].is_ascii_digit() {
+                        i += 1 + sign;
+                        while i < chars.len() && chars[i].is_ascii_digit() {
+                            i += 1;
+                        }
+                    }
+                }
+                let text: String = chars[start..i].iter().collect();
+                let number = text.parse::<f64>()
+                    .map_err(|_| ExprError::new(start, format!("invalid number `{}`", text)))?;
+                tokens.push((Token::Number(number, text), start));
+                continue;
+            },
+            // `°` for the temperatures
+            c if c.is_alphabetic() || c == '_' || c == '°' => {
+                let start = i;
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '°') {
+                    i += 1;
+                }
+                tokens.push((Token::Ident(chars[start..i].iter().collect()), start));
+                continue;
+            },
+            '+' => Token::Op(Op::Add),
+            '-' | '−' => Token::Op(Op::Sub),
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                i += 1;
+                Token::Op(Op::Pow)
+            },
+            '*' | '×' => Token::Op(Op::Mul),
+            '/' | '÷' => Token::Op(Op::Div),
+            '%' => Token::Op(Op::Rem),
+            '^' if programmer => Token::Op(Op::BitXor),
+            '^' => Token::Op(Op::Pow),
+            '&' => Token::Op(Op::BitAnd),
+            '|' => Token::Op(Op::BitOr),
+            '<' if chars.get(i + 1) == Some(&'<') => {
+                i += 1;
+                Token::Op(Op::Shl)
+            },
+            '>' if chars.get(i + 1) == Some(&'>') => {
+                i += 1;
+                Token::Op(Op::Shr)
+            },
+            '~' => Token::Tilde,
+            '!' => Token::Bang,
+            ',' => Token::Comma,
+            '=' => Token::Assign,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '[' => Token::LBracket,
+            ']' => Token::RBracket,
+            c => return Err(ExprError::new(i, format!("unexpected character `{}`", c))),
+        };
+        tokens.push((token, position));
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+/// A parsed expression.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    /// A number, how it is written for exact arithmetic, and its position.
+    Number(f64, String, usize),
+    /// A number followed by units, at the position of the units.
+    Quantity(Box<Expr>, Box<Expr>, usize),
+    /// Negation of an expression, at the position of its minus sign.
+    Neg(Box<Expr>, usize),
+    /// Operation between two expressions, at the position of the operator.
+    Binary(Op, Box<Expr>, Box<Expr>, usize),
+    /// A variable, a constant or a unit, at its position.
+    Var(String, usize),
+    /// Call of a function, at the position of its name.
+    Call(String, Vec<Expr>, usize),
+    /// Rows of a matrix, at the position of its opening bracket.
+    Matrix(Vec<Vec<Expr>>, usize),
+}
+
+/// Recursive descent parser, by precedence climbing.
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    current: usize,
+    /// Position of the end of the expression, for errors there.
+    end: usize,
+    /// How many of `binary` and `unary` are being parsed, see `MAX_NESTING`.
+    nesting: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.current).map(|(token, _)| token)
+    }
+
+    fn position(&self) -> usize {
+        self.tokens.get(self.current).map_or(self.end, |&(_, position)| position)
+    }
+
+    fn next(&mut self) -> Option<(Token, usize)> {
+        let token = self.tokens.get(self.current).cloned();
+        self.current += 1;
+        token
+    }
+
+    /// Parses with `parse` one level deeper, unless it is too deep.
+    fn nested(&mut self, parse: impl FnOnce(&mut Self) -> Result<Expr, ExprError>) -> Result<Expr, ExprError> {
+        if self.nesting >= MAX_NESTING {
+            return Err(ExprError::new(self.position(), "expression nested too deeply"));
+        }
+        self.nesting += 1;
+        let parsed = parse(self);
+        self.nesting -= 1;
+        parsed
+    }
+
+    /// Parses left associative operations whose operators bind at least as
+    /// tight as `min_precedence`, the powers being parsed by `unary`.
+    fn binary(&mut self, min_precedence: u8) -> Result<Expr, ExprError> {
+        self.nested(|parser| parser.binary_operations(min_precedence))
+    }
+
+    fn binary_operations(&mut self, min_precedence: u8) -> Result<Expr, ExprError> {
+        let mut left = self.unary()?;
+
+        while let Some(&Token::Op(op)) = self.peek() {
+            if op.precedence() < min_precedence {
+                break;
+            }
+            let position = self.position();
+            self.current += 1;
+
+            let right = self.binary(op.precedence() + 1)?;
+            left = Expr::Binary(op, Box::new(left), Box::new(right), position);
+        }
+
+        Ok(left)
+    }
+
+    /// Continues a right associative chain of powers, `2^3^2` being `2^(3^2)`.
+    fn pow_tail(&mut self, base: Expr) -> Result<Expr, ExprError> {
+        if let Some(&Token::Op(Op::Pow)) = self.peek() {
+            let position = self.position();
+            self.current += 1;
+            let exponent = self.unary()?;
+            return Ok(Expr::Binary(Op::Pow, Box::new(base), Box::new(exponent), position));
+        }
+        Ok(base)
+    }
+
+    /// Parses a possibly negated or inverted power, `-2^2` being `-(2^2)`.
+    fn unary(&mut self) -> Result<Expr, ExprError> {
+        self.nested(Self::signed)
+    }
+
+    fn signed(&mut self) -> Result<Expr, ExprError> {
+        match self.peek() {
+            Some(Token::Op(Op::Sub)) => {
+                let position = self.position();
+                self.current += 1;
+                let operand = self.unary()?;
+                Ok(Expr::Neg(Box::new(operand), position))
+            },
+            Some(Token::Op(Op::Add)) => {
+                self.current += 1;
+                self.unary()
+            },
+            // `~x` being `-1 ^ x` in two's complement
+            Some(Token::Tilde) => {
+                let position = self.position();
+                self.current += 1;
+                let operand = self.unary()?;
+                let ones = Expr::Number(-1.0, String::from("-1"), position);
+                Ok(Expr::Binary(Op::BitXor, Box::new(ones), Box::new(operand), position))
+            },
+            _ => {
+                let base = self.postfix()?;
+                self.pow_tail(base)
+            },
+        }
+    }
+
+    /// Parses a primary expression followed by any number of factorials.
+    fn postfix(&mut self) -> Result<Expr, ExprError> {
+        let mut expr = self.primary()?;
+        while let Some(Token::Bang) = self.peek() {
+            let position = self.position();
+            self.current += 1;
+            expr = Expr::Call(String::from("fact"), vec![expr], position);
+        }
+        Ok(expr)
+    }
+
+    /// Parses the arguments of a call, its opening parenthesis being consumed.
+    fn arguments(&mut self, open: usize) -> Result<Vec<Expr>, ExprError> {
+        let mut args = Vec::new();
+        if let Some(Token::RParen) = self.peek() {
+            self.current += 1;
+            return Ok(args);
+        }
+
+        loop {
+            args.push(self.binary(0)?);
+            match self.next() {
+                Some((Token::Comma, _)) => continue,
+                Some((Token::RParen, _)) => return Ok(args),
+                _ => return Err(ExprError::new(open, "unclosed parenthesis")),
+            }
+        }
+    }
+
+    /// Parses a matrix, its opening bracket being consumed, as rows in
+    /// brackets, `[[1, 2], [3, 4]]`, or as the entries of a column, `[1, 2]`.
+    fn matrix(&mut self, open: usize) -> Result<Expr, ExprError> {
+        if self.peek() != Some(&Token::LBracket) {
+            let column = self.entries(open)?;
+            return Ok(Expr::Matrix(column.into_iter().map(|entry| vec![entry]).collect(), open));
+        }
+
+        let mut rows: Vec<Vec<Expr>> = Vec::new();
+        loop {
+            let row_position = self.position();
+            if self.next().map(|(token, _)| token) != Some(Token::LBracket) {
+                return Err(ExprError::new(row_position, "expected a row in brackets"));
+            }
+            let row = self.entries(row_position)?;
+            if rows.first().map_or(false, |first| first.len() != row.len()) {
+                return Err(ExprError::new(row_position, "expected rows of the same length"));
+            }
+            rows.push(row);
+            match self.next() {
+                Some((Token::Comma, _)) => continue,
+                Some((Token::RBracket, _)) => return Ok(Expr::Matrix(rows, open)),
+                _ => return Err(ExprError::new(open, "unclosed bracket")),
+            }
+        }
+    }
+
+    /// Parses the entries of a row up to its closing bracket, the opening one
+    /// being at `open`.
+    fn entries(&mut self, open: usize) -> Result<Vec<Expr>, ExprError> {
+        let mut entries = Vec::new();
+        loop {
+            entries.push(self.binary(0)?);
+            match self.next() {
+                Some((Token::Comma, _)) => continue,
+                Some((Token::RBracket, _)) => return Ok(entries),
+                _ => return Err(ExprError::new(open, "unclosed bracket")),
+            }
+        }
+    }
+
+    /// Tells whether the token at `index` is a unit, rather than a function.
+    fn is_unit(&self, index: usize) -> bool {
+        match (self.tokens.get(index), self.tokens.get(index + 1)) {
+            (Some((Token::Ident(_), _)), Some((Token::LParen, _))) => false,
+            (Some((Token::Ident(name), _)), _) => unit(name).is_some(),
+            _ => false,
+        }
+    }
+
+    /// Parses a unit, with an integer exponent if any.
+    fn unit(&mut self) -> Result<Expr, ExprError> {
+        let (name, position) = match self.next() {
+            Some((Token::Ident(name), position)) => (name, position),
+            _ => unreachable!("units are checked before being parsed"),
+        };
+        let unit = Expr::Var(name, position);
+        if let Some(Token::Op(Op::Pow)) = self.peek() {
+            let pow_position = self.position();
+            self.current += 1;
+            let negative = self.peek() == Some(&Token::Op(Op::Sub));
+            if negative {
+                self.current += 1;
+            }
+            let exponent = match self.next() {
+                Some((Token::Number(n, text), position)) if n.fract() == 0.0 => Expr::Number(n, text, position),
+                _ => return Err(ExprError::new(pow_position, "expected an integer exponent")),
+            };
+            let exponent = if negative { Expr::Neg(Box::new(exponent), pow_position + 1) } else { exponent };
+            return Ok(Expr::Binary(Op::Pow, Box::new(unit), Box::new(exponent), pow_position));
+        }
+        Ok(unit)
+    }
+
+    /// Parses the units following a number, as `km/h` in `5 km/h`, which bind
+    /// tighter than any operator.
+    fn units(&mut self) -> Result<Expr, ExprError> {
+        let mut units = self.unit()?;
+        loop {
+            let op = match self.peek() {
+                Some(&Token::Op(op)) if (op == Op::Mul || op == Op::Div) && self.is_unit(self.current + 1) => op,
+                _ => return Ok(units),
+            };
+            let position = self.position();
+            self.current += 1;
+            units = Expr::Binary(op, Box::new(units), Box::new(self.unit()?), position);
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expr, ExprError> {
+        let position = self.position();
+        match self.next() {
+            Some((Token::Number(n, text), _)) if self.is_unit(self.current) => {
+                let units_position = self.position();
+                let number = Expr::Number(n, text, position);
+                Ok(Expr::Quantity(Box::new(number), Box::new(self.units()?), units_position))
+            },
+            Some((Token::Number(n, text), _)) => Ok(Expr::Number(n, text, position)),
+            Some((Token::Ident(name), _)) => match self.peek() {
+                Some(Token::LParen) => {
+                    let open = self.position();
+                    self.current += 1;
+                    Ok(Expr::Call(name, self.arguments(open)?, position))
+                },
+                _ => Ok(Expr::Var(name, position)),
+            },
+            Some((Token::LParen, _)) => {
+                let inner = self.binary(0)?;
+                match self.next() {
+                    Some((Token::RParen, _)) => Ok(inner),
+                    _ => Err(ExprError::new(position, "unclosed parenthesis")),
+                }
+            },
+            Some((Token::LBracket, _)) => self.matrix(position),
+            Some((token, position)) => Err(ExprError::new(position, format!("expected a number, found `{}`", token))),
+            None => Err(ExprError::new(position, "expected a number")),
+        }
+    }
+}
+
+/// Parses the tokens of an expression, whose end is at `end`.
+fn parse_tokens(tokens: Vec<(Token, usize)>, end: usize) -> Result<Expr, ExprError> {
+    let mut parser = Parser {
+        tokens,
+        current: 0,
+        end,
+        nesting: 0,
+    };
+
+    let parsed = parser.binary(0)?;
+    match parser.next() {
+        None => Ok(parsed),
+        Some((Token::RParen, position)) => Err(ExprError::new(position, "unmatched parenthesis")),
+        Some((Token::RBracket, position)) => Err(ExprError::new(position, "unmatched bracket")),
+        Some((token, position)) => Err(ExprError::new(position, format!("expected an operator, found `{}`", token))),
+    }
+}
+
+/// Units to convert a result to, as written after `to`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Target {
+    pub units: Expr,
+    pub source: String,
+}
+
+/// What a line given to the calculator asks for.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Statement {
+    /// `expr`, or `expr to units`.
+    Eval(Expr, Option<Target>),
+    /// `name = expr`, at the position of the name.
+    Assign(String, Expr, usize),
+    /// `name(params) = body`, with the source of the body to show and save it.
+    Define {
+        name: String,
+        params: Vec<String>,
+        body: Expr,
+        source: String,
+        position: usize,
+    },
+}
+
+/// Parses an expression alone, `^` being a power.
+pub fn parse(input: &str) -> Result<Expr, ExprError> {
+    parse_tokens(tokenize(input, false)?, input.chars().count())
+}
+
+/// Parses an assignment, a function definition or else an expression,
+/// maybe converted, `^` being an exclusive or in `programmer` mode.
+pub fn parse_statement(input: &str, programmer: bool) -> Result<Statement, ExprError> {
+    let mut tokens = tokenize(input, programmer)?;
+    let end = input.chars().count();
+
+    let assign = match tokens.iter().position(|(token, _)| *token == Token::Assign) {
+        Some(assign) => assign,
+        None => return parse_conversion(input, tokens, end),
+    };
+    let (equal_position, body_tokens) = {
+        let mut body = tokens.split_off(assign);
+        (body.remove(0).1, body)
+    };
+    let body = parse_tokens(body_tokens, end)?;
+
+    let mut head = tokens.into_iter();
+    let (name, position) = match head.next() {
+        Some((Token::Ident(name), position)) => (name, position),
+        Some((_, position)) => return Err(ExprError::new(position, "expected a name to assign")),
+        None => return Err(ExprError::new(equal_position, "expected a name before `=`")),
+    };
+
+    match head.next() {
+        None => Ok(Statement::Assign(name, body, position)),
+        Some((Token::LParen, _)) => {
+            let mut params = Vec::new();
+            loop {
+                match head.next() {
+                    Some((Token::RParen, _)) if params.is_empty() => break,
+                    Some((Token::Ident(param), param_position)) => {
+                        if params.contains(&param) {
+                            return Err(ExprError::new(param_position, format!("parameter `{}` given twice", param)));
+                        }
+                        params.push(param);
+                    },
+                    Some((_, position)) => return Err(ExprError::new(position, "expected a parameter name")),
+                    None => return Err(ExprError::new(equal_position, "expected `)`")),
+                }
+                match head.next() {
+                    Some((Token::Comma, _)) => continue,
+                    Some((Token::RParen, _)) => break,
+                    Some((_, position)) => return Err(ExprError::new(position, "expected `,` or `)`")),
+                    None => return Err(ExprError::new(equal_position, "expected `)`")),
+                }
+            }
+            if let Some((_, position)) = head.next() {
+                return Err(ExprError::new(position, "expected `=`"));
+            }
+
+            // on a single line, to be saved as one
+            let source: String = input.chars().skip(equal_position + 1).collect();
+            let source = source.trim().replace(&['\n', '\r'][..], " ");
+            Ok(Statement::Define { name, params, body, source, position })
+        },
+        Some((_, position)) => Err(ExprError::new(position, "expected `=` or parameters")),
+    }
+}
+
+/// The numbers expressions are evaluated with, the errors being put at the
+/// position of their cause.
+pub trait Arithmetic: Clone {
+    /// The number written `text`, whose value as a float is `value`.
+    fn literal(value: f64, text: &str) -> Result<Self, String>;
+    /// The value of a variable, or why these numbers cannot hold it, as
+    /// `has no exact value` following its name.
+    fn variable(value: &Number) -> Result<Self, String>;
+    fn constant(constant: &Constant) -> Result<Self, String>;
+    /// The matrix of `entries`, row after row.
+    fn matrix(_rows: usize, _columns: usize, _entries: Vec<Self>) -> Result<Self, String> {
+        Err(String::from("expected numbers, not a matrix"))
+    }
+    fn negate(self) -> Self;
+    fn binary(op: Op, left: Self, right: Self) -> Result<Self, String>;
+    /// The value as an integer, `None` if it is not one or is too large.
+    fn to_integer(&self) -> Option<i64>;
+    /// Calls a built-in function, with a number of arguments it accepts.
+    fn call(function: &Function, args: &[Self]) -> Result<Self, String>;
+    /// Refuses the numbers too large to go on with.
+    fn check(&self) -> Result<(), String>;
+}
+
+impl Arithmetic for f64 {
+    fn literal(value: f64, _text: &str) -> Result<Self, String> {
+        Ok(value)
+    }
+
+    fn variable(value: &Number) -> Result<Self, String> {
+        match value {
+            Number::Float(value) => Ok(*value),
+            Number::Exact(value) => Ok(value.to_f64()),
+            Number::Matrix(_) => Err(String::from("is a matrix")),
+        }
+    }
+
+    fn constant(constant: &Constant) -> Result<Self, String> {
+        Ok(constant.value)
+    }
+
+    fn negate(self) -> Self {
+        -self
+    }
+
+    fn binary(op: Op, left: Self, right: Self) -> Result<Self, String> {
+        match op {
+            Op::Add => Ok(left + right),
+            Op::Sub => Ok(left - right),
+            Op::Mul => Ok(left * right),
+            Op::Div if right == 0.0 => Err(String::from("division by zero")),
+            Op::Div => Ok(left / right),
+            Op::Rem if right == 0.0 => Err(String::from("modulo by zero")),
+            Op::Rem => Ok(left % right),
+            Op::Pow if left == 0.0 && right < 0.0 => Err(String::from("division by zero")),
+            Op::Pow => {
+                let power = left.powf(right);
+                if power.is_nan() {
+                    return Err(String::from("no real power"));
+                }
+                Ok(power)
+            },
+            op => op.bitwise(float_integer(left)?, float_integer(right)?).map(|n| n as f64),
+        }
+    }
+
+    fn to_integer(&self) -> Option<i64> {
+        if self.fract() == 0.0 && self.abs() <= i32::MAX as f64 {
+            Some(*self as i64)
+        } else {
+            None
+        }
+    }
+
+    fn call(function: &Function, args: &[Self]) -> Result<Self, String> {
+        (function.call)(args)
+    }
+
+    fn check(&self) -> Result<(), String> {
+        if self.is_infinite() {
+            Err(String::from("result too large"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// The integer `value` is, for bitwise operations.
+fn float_integer(value: f64) -> Result<i128, String> {
+    if value.fract() == 0.0 && value.abs() < 2f64.powi(127) {
+        Ok(value as i128)
+    } else {
+        Err(String::from("bitwise operations take integers"))
+    }
+}
+
+/// Parses an expression followed by `to` and the units to convert it to, if
+/// any.
+fn parse_conversion(input: &str, mut tokens: Vec<(Token, usize)>, end: usize) -> Result<Statement, ExprError> {
+    let to = match tokens.iter().position(|(token, _)| *token == Token::Ident(String::from(CONVERT))) {
+        Some(to) => to,
+        None => return Ok(Statement::Eval(parse_tokens(tokens, end)?, None)),
+    };
+    let mut units_tokens = tokens.split_off(to);
+    let (_, to_position) = units_tokens.remove(0);
+    if units_tokens.is_empty() {
+        return Err(ExprError::new(end, "expected units"));
+    }
+
+    let expr = parse_tokens(tokens, to_position)?;
+    let units = parse_tokens(units_tokens, end)?;
+    let source: String = input.chars().skip(to_position + CONVERT.len()).collect();
+    Ok(Statement::Eval(expr, Some(Target { units, source: source.trim().to_string() })))
+}
+
+impl Target {
+    /// Expresses `quantity` in the units, temperatures with an offset being
+    /// taken from there.
+    pub fn convert<N: Arithmetic>(&self, quantity: Quantity<N>) -> Result<N, ExprError> {
+        let at = |why: String| ExprError::new(self.units.position(), why);
+        let target = self.units.eval_units::<N>()?;
+        if target.dimension != quantity.dimension {
+            return Err(at(format!("cannot convert `{}` to `{}`", quantity.dimension, self.source)));
+        }
+        if let Expr::Var(name, _) = &self.units {
+            match unit(name) {
+                Some(unit) if unit.has_offset() => return unit.relative(quantity).map_err(at),
+                _ => (),
+            }
+        }
+        N::binary(Op::Div, quantity.value, target.value).map_err(at)
+    }
+}
+
+impl Expr {
+    /// Evaluates the expression, its names referring to the variables and
+    /// functions of `scope`, or else to the built-in ones and to units.
+    pub fn eval<N: Arithmetic>(&self, scope: &Scope) -> Result<Quantity<N>, ExprError> {
+        self.eval_in(scope, &HashMap::new(), 0, 0)
+    }
+
+    /// Evaluates the expression as `eval` does, `name` being a number worth
+    /// `value` there.
+    pub fn eval_at<N: Arithmetic>(&self, scope: &Scope, name: &str, value: N) -> Result<Quantity<N>, ExprError> {
+        let mut locals = HashMap::new();
+        locals.insert(name, Quantity::number(value));
+        self.eval_in(scope, &locals, 0, 0)
+    }
+
+    /// Evaluates the expression with `locals`, the arguments of the user
+    /// function being evaluated at `depth`, `nesting` expressions deep.
+    fn eval_in<N: Arithmetic>(&self, scope: &Scope, locals: &HashMap<&str, Quantity<N>>, depth: usize, nesting: usize)
+        -> Result<Quantity<N>, ExprError> {
+        if nesting >= MAX_NESTING {
+            return Err(ExprError::new(self.position(), "expression nested too deeply"));
+        }
+        let eval = |expr: &Expr| expr.eval_in(scope, locals, depth, nesting + 1);
+        let at = |position: usize| move |why: String| ExprError::new(position, why);
+        let value = match self {
+            Expr::Number(n, text, position) => Quantity::number(N::literal(*n, text).map_err(at(*position))?),
+            Expr::Quantity(number, units, position) => {
+                let number = eval(number)?.value;
+                match units.as_ref() {
+                    // a temperature from its zero, as in `20 °C`
+                    Expr::Var(name, _) if unit(name).map_or(false, |unit| unit.has_offset()) => {
+                        unit(name).unwrap().absolute(number).map_err(at(*position))?
+                    },
+                    units => Quantity::binary(Op::Mul, Quantity::number(number), units.eval_units()?)
+                        .map_err(at(*position))?,
+                }
+            },
+            Expr::Neg(operand, _) => eval(operand)?.negate(),
+            Expr::Binary(op, left, right, position) => {
+                let (left, right) = (eval(left)?, eval(right)?);
+                Quantity::binary(*op, left, right).map_err(at(*position))?
+            },
+            Expr::Var(name, position) => {
+                let value = match (locals.get(name.as_str()), scope.variables.get(name)) {
+                    (Some(local), _) => Some(local.clone()),
+                    (None, Some(variable)) => match N::variable(&variable.value) {
+                        Ok(value) => Some(Quantity { value, dimension: variable.dimension }),
+                        Err(why) => return Err(ExprError::new(*position, format!("`{}` {}", name, why))),
+                    },
+                    (None, None) => None,
+                };
+                match (value, constant(name), unit(name)) {
+                    (Some(value), _, _) => value,
+                    (None, Some(constant), _) => Quantity::number(N::constant(constant).map_err(at(*position))?),
+                    (None, None, Some(unit)) => unit.quantity().map_err(at(*position))?,
+                    (None, None, None) => return Err(ExprError::new(*position, format!("unknown variable `{}`", name))),
+                }
+            },
+            Expr::Call(name, args, position) => {
+                let args_count = args.len();
+                let args = args.iter().map(eval).collect::<Result<Vec<Quantity<N>>, ExprError>>()?;
+
+                if let Some(function) = function(name) {
+                    if !function.accepts(args_count) {
+                        return Err(ExprError::new(*position, function.arity()));
+                    }
+                    Quantity::call(function, args).map_err(at(*position))?
+                } else if let Some(function) = scope.functions.get(name) {
+                    if function.params.len() != args_count {
+                        return Err(ExprError::new(*position, format!("`{}` takes {} arguments", name, function.params.len())));
+                    }
+                    if depth >= MAX_DEPTH {
+                        return Err(ExprError::new(*position, "too many nested calls"));
+                    }
+                    let locals = function.params.iter().map(String::as_str).zip(args).collect();
+                    // the position of an error in a body means nothing in the
+                    // expression, the outermost call is pointed at instead
+                    let value = function.body.eval_in(scope, &locals, depth + 1, nesting + 1);
+                    if depth > 0 {
+                        value?
+                    } else {
+                        value.map_err(|why| ExprError::new(*position, format!("in `{}`: {}", name, why.message)))?
+                    }
+                } else {
+                    return Err(ExprError::new(*position, format!("unknown function `{}`", name)));
+                }
+            },
+            Expr::Matrix(rows, position) => {
+                let mut dimension = None;
+                let mut entries = Vec::new();
+                for entry in rows.iter().flatten() {
+                    let quantity = eval(entry)?;
+                    if dimension.map_or(false, |dimension| dimension != quantity.dimension) {
+                        return Err(ExprError::new(entry.position(), "entries of a matrix must have the same units"));
+                    }
+                    dimension = Some(quantity.dimension);
+                    entries.push(quantity.value);
+                }
+                let value = N::matrix(rows.len(), rows[0].len(), entries).map_err(at(*position))?;
+                Quantity { value, dimension: dimension.unwrap_or_default() }
+            },
+        };
+
+        value.check().map_err(at(self.position()))?;
+        Ok(value)
+    }
+
+    /// Evaluates units, as `km/h`, whose names refer to units only.
+    fn eval_units<N: Arithmetic>(&self) -> Result<Quantity<N>, ExprError> {
+        let at = |position: usize| move |why: String| ExprError::new(position, why);
+        match self {
+            Expr::Number(n, text, position) => Ok(Quantity::number(N::literal(*n, text).map_err(at(*position))?)),
+            Expr::Var(name, position) => match unit(name) {
+                Some(unit) => unit.quantity().map_err(at(*position)),
+                None => Err(ExprError::new(*position, format!("unknown unit `{}`", name))),
+            },
+            Expr::Neg(operand, _) => Ok(operand.eval_units()?.negate()),
+            Expr::Binary(op, left, right, position) => {
+                Quantity::binary(*op, left.eval_units()?, right.eval_units()?).map_err(at(*position))
+            },
+            Expr::Quantity(..) | Expr::Call(..) | Expr::Matrix(..) => Err(ExprError::new(self.position(), "expected units")),
+        }
+    }
+
+    /// Tells whether all the numbers of the expression are written as
+    /// integers.
+    pub fn is_integral(&self) -> bool {
+        match self {
+            Expr::Number(_, text, _) => text.chars().all(|c| c.is_ascii_digit()) || split_radix(text).is_some(),
+            Expr::Quantity(number, _, _) | Expr::Neg(number, _) => number.is_integral(),
+            Expr::Binary(_, left, right, _) => left.is_integral() && right.is_integral(),
+            Expr::Var(..) => true,
+            Expr::Call(_, args, _) => args.iter().all(Expr::is_integral),
+            Expr::Matrix(rows, _) => rows.iter().flatten().all(Expr::is_integral),
+        }
+    }
+
+    /// Position of the expression, or of its operator.
+    fn position(&self) -> usize {
+        match self {
+            Expr::Number(_, _, position) | Expr::Quantity(_, _, position) | Expr::Neg(_, position)
+                | Expr::Binary(_, _, _, position) | Expr::Var(_, position) | Expr::Call(_, _, position)
+                | Expr::Matrix(_, position) => *position,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn evaluate(expr: &str) -> Result<f64, ExprError> {
+        parse(expr)?.eval(&Scope::new()).map(|quantity| quantity.value)
+    }
+
+    #[test]
+    fn test_operators() {
+        assert_eq!(evaluate("2-1"), Ok(1.0));
+        assert_eq!(evaluate("3/4"), Ok(0.75));
+        assert_eq!(evaluate("(1+2)*3"), Ok(9.0));
+        assert_eq!(evaluate("2^10"), Ok(1024.0));
+        assert_eq!(evaluate("7 % 4"), Ok(3.0));
+        assert_eq!(evaluate("1.5e3 + .5"), Ok(1500.5));
+    }
+
+    #[test]
+    fn test_precedence_and_associativity() {
+        assert_eq!(evaluate("1 + 2 * 3"), Ok(7.0));
+        assert_eq!(evaluate("10 - 4 - 3"), Ok(3.0));
+        assert_eq!(evaluate("2 * 3 ^ 2"), Ok(18.0));
+        assert_eq!(evaluate("2 ^ 3 ^ 2"), Ok(512.0));
+        assert_eq!(evaluate("64 / 4 / 2"), Ok(8.0));
+    }
+
+    #[test]
+    fn test_unary_minus() {
+        assert_eq!(evaluate("-2^2"), Ok(-4.0));
+        assert_eq!(evaluate("2^-1"), Ok(0.5));
+        assert_eq!(evaluate("3 - -2"), Ok(5.0));
+        assert_eq!(evaluate("-(1 + 2) * 2"), Ok(-6.0));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(evaluate("2 + * 3").unwrap_err().position, 4);
+        assert_eq!(evaluate("(1 + 2").unwrap_err().position, 0);
+        assert_eq!(evaluate("1 + 2)").unwrap_err().position, 5);
+        assert_eq!(evaluate("1 / (2 - 2)").unwrap_err(), ExprError::new(2, "division by zero"));
+        assert_eq!(evaluate("2 $ 3").unwrap_err().position, 2);
+        assert_eq!(evaluate("1 +").unwrap_err().position, 3);
+        assert_eq!(evaluate("").unwrap_err().position, 0);
+        assert_eq!(evaluate("1 2").unwrap_err().position, 2);
+        assert_eq!(evaluate("(-8)^0.5").unwrap_err().position, 4);
+    }
+
+    #[test]
+    fn test_nesting() {
+        let nested = |depth: usize| format!("{}1{}", "(".repeat(depth), ")".repeat(depth));
+        assert_eq!(evaluate(&nested(90)), Ok(1.0));
+        assert_eq!(evaluate(&format!("{}1", "-".repeat(100))), Ok(1.0));
+        let too_deep = ExprError::new(MAX_NESTING / 2, "expression nested too deeply");
+        assert_eq!(evaluate(&nested(5000)).unwrap_err(), too_deep);
+        assert_eq!(evaluate(&format!("[{}]", nested(5000))).unwrap_err().message, too_deep.message);
+        assert_eq!(evaluate(&format!("{}1", "-".repeat(5000))).unwrap_err().message, too_deep.message);
+        assert_eq!(evaluate(&format!("2{}", "^2".repeat(5000))).unwrap_err().message, too_deep.message);
+        assert_eq!(parse_statement(&format!("{}1", "~".repeat(5000)), true).unwrap_err().message, too_deep.message);
+
+        // deep bodies called deep in one another
+        let mut scope = Scope::new();
+        scope.execute(&format!("f(x) = f({}x)", "-".repeat(150)), false).unwrap();
+        for exact in &[false, true] {
+            let message = format!("in `f`: {}", too_deep.message);
+            assert_eq!(scope.execute("f(1)", *exact).unwrap_err().message, message);
+        }
+    }
+
+    #[test]
+    fn test_functions_and_constants() {
+        assert_eq!(evaluate("sqrt(16) + abs(-2)"), Ok(6.0));
+        assert_eq!(evaluate("max(1, 2 * 4, 3)"), Ok(8.0));
+        assert_eq!(evaluate("3! ^ 2"), Ok(36.0));
+        assert_eq!(evaluate("2 ^ 3!"), Ok(64.0));
+        assert_eq!(evaluate("-3!"), Ok(-6.0));
+        assert_eq!(evaluate("round(sind(30) * 10)"), Ok(5.0));
+        assert_eq!(evaluate("cos(pi)"), Ok(-1.0));
+        assert_eq!(evaluate("ln(e)"), Ok(1.0));
+        assert_eq!(evaluate("log(1000)"), Ok(3.0));
+    }
+
+    #[test]
+    fn test_function_errors() {
+        assert_eq!(evaluate("1 + foo(2)").unwrap_err(), ExprError::new(4, "unknown function `foo`"));
+        assert_eq!(evaluate("2 * baz").unwrap_err(), ExprError::new(4, "unknown variable `baz`"));
+        assert_eq!(evaluate("sqrt(1, 2)").unwrap_err(), ExprError::new(0, "`sqrt` takes 1 argument"));
+        assert_eq!(evaluate("sqrt(-1)").unwrap_err().position, 0);
+        assert_eq!(evaluate("max(1, 2").unwrap_err(), ExprError::new(3, "unclosed parenthesis"));
+        assert_eq!(evaluate("(-1)!").unwrap_err().position, 4);
+    }
+
+    #[test]
+    fn test_statements() {
+        assert!(matches!(parse_statement("1 + 2", false), Ok(Statement::Eval(_, None))));
+        assert!(matches!(parse_statement("x = 3.5", false), Ok(Statement::Assign(name, _, 0)) if name == "x"));
+        match parse_statement("f(a, b) = a^2 + b", false) {
+            Ok(Statement::Define { name, params, source, .. }) => {
+                assert_eq!(name, "f");
+                assert_eq!(params, vec!["a", "b"]);
+                assert_eq!(source, "a^2 + b");
+            },
+            other => panic!("unexpected {:?}", other),
+        }
+        match parse_statement("f(x) = x +\n 1", false) {
+            Ok(Statement::Define { source, .. }) => assert_eq!(source, "x +  1"),
+            other => panic!("unexpected {:?}", other),
+        }
+        assert_eq!(parse_statement("f(a, a) = a", false).unwrap_err().position, 5);
+        assert_eq!(parse_statement("2 = 3", false).unwrap_err().position, 0);
+        assert_eq!(parse_statement("x = ", false).unwrap_err().position, 4);
+        assert_eq!(parse_statement("f(1) = 2", false).unwrap_err().position, 2);
+    }
+
+    /// Evaluates `input`, converted if it says so, with floats.
+    fn convert(input: &str) -> Result<f64, ExprError> {
+        match parse_statement(input, false)? {
+            Statement::Eval(expr, Some(target)) => target.convert(expr.eval::<f64>(&Scope::new())?),
+            Statement::Eval(expr, None) => expr.eval(&Scope::new()).map(|quantity| quantity.value),
+            _ => panic!("not an expression"),
+        }
+    }
+
+    #[test]
+    fn test_units() {
+        assert!((convert("5 km/h to m/s").unwrap() - 25.0 / 18.0).abs() < 1e-12);
+        assert!((convert("3 GiB / 20 MB/s to s").unwrap() - 161.061_273_6).abs() < 1e-9);
+        assert!((convert("72 °F to C").unwrap() - 200.0 / 9.0).abs() < 1e-12);
+        assert!((convert("1 mi to km").unwrap() - 1.609_344).abs() < 1e-12);
+        assert_eq!(convert("2 m * 3 m to ha").unwrap(), 0.0006);
+        assert_eq!(convert("9.81 m*s^-2 * 2 kg to N").unwrap(), 19.62);
+        assert_eq!(convert("sqrt(16 m^2) to cm").unwrap(), 400.0);
+        assert_eq!(convert("1 kWh to J").unwrap(), 3_600_000.0);
+        assert_eq!(convert("1 m + 1 s").unwrap_err(), ExprError::new(4, "incompatible units, `m` and `s`"));
+        assert_eq!(convert("5 km to kg").unwrap_err(), ExprError::new(8, "cannot convert `m` to `kg`"));
+        assert_eq!(convert("5 km to").unwrap_err().position, 7);
+        assert_eq!(convert("1 to parsec").unwrap_err(), ExprError::new(5, "unknown unit `parsec`"));
+        assert_eq!(convert("sin(2 m)").unwrap_err().message, "`sin` takes numbers without units");
+        assert_eq!(convert("2 min(3, 4)").unwrap_err().position, 2);
+    }
+
+    #[test]
+    fn test_matrices() {
+        let numbers = |expr: Expr| match expr {
+            Expr::Matrix(rows, _) => rows.iter()
+                .map(|row| row.iter().map(|entry| match entry {
+                    Expr::Number(n, _, _) => *n,
+                    other => panic!("unexpected {:?}", other),
+                }).collect())
+                .collect::<Vec<Vec<f64>>>(),
+            other => panic!("unexpected {:?}", other),
+        };
+        assert_eq!(numbers(parse("[[1, 2], [3, 4]]").unwrap()), vec![vec![1.0, 2.0], vec![3.0, 4.0]]);
+        assert_eq!(numbers(parse("[1, 2]").unwrap()), vec![vec![1.0], vec![2.0]]);
+        assert_eq!(numbers(parse("[[1, 2]]").unwrap()), vec![vec![1.0, 2.0]]);
+        assert_eq!(parse("[[1, 2], [3]]").unwrap_err(), ExprError::new(9, "expected rows of the same length"));
+        assert_eq!(parse("[[1, 2], 3]").unwrap_err(), ExprError::new(9, "expected a row in brackets"));
+        assert_eq!(parse("[1, 2").unwrap_err(), ExprError::new(0, "unclosed bracket"));
+        assert_eq!(parse("[1] ]").unwrap_err(), ExprError::new(4, "unmatched bracket"));
+        assert_eq!(evaluate("[1, 2]").unwrap_err(), ExprError::new(0, "expected numbers, not a matrix"));
+    }
+
+    #[test]
+    fn test_pointer() {
+        let error = evaluate("2 + * 3").unwrap_err();
+        assert_eq!(error.pointer("2 + * 3"), "2 + * 3\n    ^ expected a number, found `*`");
+    }
+}
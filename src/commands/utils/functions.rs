@@ -0,0 +1,174 @@
+use std::f64::consts;
+
+/// A function usable in expressions.
+pub struct Function {
+    pub name: &'static str,
+    /// Minimum and maximum numbers of arguments, `None` for no maximum.
+    pub min_args: usize,
+    pub max_args: Option<usize>,
+    /// How to call it, and what it does, for the help.
+    pub usage: &'static str,
+    pub help: &'static str,
+    pub call: fn(&[f64]) -> Result<f64, String>,
+}
+
+impl Function {
+    /// Tells how many arguments the function takes, when called with a wrong
+    /// number of them.
+    pub fn arity(&self) -> String {
+        let plural = |n: usize| if n == 1 { "" } else { "s" };
+        match self.max_args {
+            Some(max) if max == self.min_args => format!("`{}` takes {} argument{}", self.name, max, plural(max)),
+            Some(max) => format!("`{}` takes {} to {} arguments", self.name, self.min_args, max),
+            None => format!("`{}` takes at least {} argument{}", self.name, self.min_args, plural(self.min_args)),
+        }
+    }
+
+    pub fn accepts(&self, count: usize) -> bool {
+        count >= self.min_args && self.max_args.map_or(true, |max| count <= max)
+    }
+}
+
+/// A named constant usable in expressions.
+pub struct Constant {
+    pub name: &'static str,
+    pub value: f64,
+    pub help: &'static str,
+}
+
+/// The functions of expressions. To add one, add it here.
+pub const FUNCTIONS: &[Function] = &[
+    Function { name: "sqrt", min_args: 1, max_args: Some(1), usage: "sqrt(x)", help: "square root", call: sqrt },
+    Function { name: "abs", min_args: 1, max_args: Some(1), usage: "abs(x)", help: "absolute value", call: |a| Ok(a[0].abs()) },
+    Function { name: "sin", min_args: 1, max_args: Some(1), usage: "sin(x)", help: "sine of radians", call: |a| Ok(a[0].sin()) },
+    Function { name: "cos", min_args: 1, max_args: Some(1), usage: "cos(x)", help: "cosine of radians", call: |a| Ok(a[0].cos()) },
+    Function { name: "tan", min_args: 1, max_args: Some(1), usage: "tan(x)", help: "tangent of radians", call: |a| Ok(a[0].tan()) },
+    Function { name: "sind", min_args: 1, max_args: Some(1), usage: "sind(x)", help: "sine of degrees", call: |a| Ok(a[0].to_radians().sin()) },
+    Function { name: "cosd", min_args: 1, max_args: Some(1), usage: "cosd(x)", help: "cosine of degrees", call: |a| Ok(a[0].to_radians().cos()) },
+    Function { name: "tand", min_args: 1, max_args: Some(1), usage: "tand(x)", help: "tangent of degrees", call: |a| Ok(a[0].to_radians().tan()) },
+    Function { name: "asin", min_args: 1, max_args: Some(1), usage: "asin(x)", help: "arcsine, in radians", call: |a| in_domain(a[0].asin()) },
+    Function { name: "acos", min_args: 1, max_args: Some(1), usage: "acos(x)", help: "arccosine, in radians", call: |a| in_domain(a[0].acos()) },
+    Function { name: "atan", min_args: 1, max_args: Some(1), usage: "atan(x)", help: "arctangent, in radians", call: |a| Ok(a[0].atan()) },
+    Function { name: "ln", min_args: 1, max_args: Some(1), usage: "ln(x)", help: "natural logarithm", call: |a| log(a[0], consts::E) },
+    Function { name: "log", min_args: 1, max_args: Some(2), usage: "log(x[, base])", help: "logarithm, in base 10 by default", call: |a| log(a[0], a.get(1).copied().unwrap_or(10.0)) },
+    Function { name: "exp", min_args: 1, max_args: Some(1), usage: "exp(x)", help: "e to the power x", call: |a| Ok(a[0].exp()) },
+    Function { name: "floor", min_args: 1, max_args: Some(1), usage: "floor(x)", help: "rounds down", call: |a| Ok(a[0].floor()) },
+    Function { name: "ceil", min_args: 1, max_args: Some(1), usage: "ceil(x)", help: "rounds up", call: |a| Ok(a[0].ceil()) },
+    Function { name: "round", min_args: 1, max_args: Some(1), usage: "round(x)", help: "rounds to the nearest integer", call: |a| Ok(a[0].round()) },
+    Function { name: "min", min_args: 1, max_args: None, usage: "min(x, ...)", help: "smallest argument", call: |a| Ok(a.iter().copied().fold(f64::INFINITY, f64::min)) },
+    Function { name: "max", min_args: 1, max_args: None, usage: "max(x, ...)", help: "largest argument", call: |a| Ok(a.iter().copied().fold(f64::NEG_INFINITY, f64::max)) },
+    Function { name: "fact", min_args: 1, max_args: Some(1), usage: "fact(n) or n!", help: "factorial", call: factorial },
+    // on matrices, numbers being matrices of a single entry
+    Function { name: "transpose", min_args: 1, max_args: Some(1), usage: "transpose(A)", help: "rows of a matrix as columns", call: |a| Ok(a[0]) },
+    Function { name: "det", min_args: 1, max_args: Some(1), usage: "det(A)", help: "determinant of a square matrix", call: |a| Ok(a[0]) },
+    Function { name: "inv", min_args: 1, max_args: Some(1), usage: "inv(A)", help: "inverse of a square matrix, as `A^-1`", call: inverse },
+    Function { name: "solve", min_args: 2, max_args: Some(2), usage: "solve(A, b)", help: "solution `x` of `A*x = b`", call: solve },
+];
+
+pub const CONSTANTS: &[Constant] = &[
+    Constant { name: "pi", value: consts::PI, help: "half a turn, in radians" },
+    Constant { name: "tau", value: 2.0 * consts::PI, help: "a whole turn, in radians" },
+    Constant { name: "e", value: consts::E, help: "base of the natural logarithm" },
+];
+
+pub fn function(name: &str) -> Option<&'static Function> {
+    FUNCTIONS.iter().find(|function| function.name == name)
+}
+
+pub fn constant(name: &str) -> Option<&'static Constant> {
+    CONSTANTS.iter().find(|constant| constant.name == name)
+}
+
+fn in_domain(value: f64) -> Result<f64, String> {
+    if value.is_nan() {
+        Err(String::from("argument out of the domain"))
+    } else {
+        Ok(value)
+    }
+}
+
+fn inverse(args: &[f64]) -> Result<f64, String> {
+    if args[0] == 0.0 {
+        Err(String::from("the matrix is not invertible"))
+    } else {
+        Ok(1.0 / args[0])
+    }
+}
+
+fn solve(args: &[f64]) -> Result<f64, String> {
+    if args[0] == 0.0 {
+        Err(String::from("the system has no single solution"))
+    } else {
+        Ok(args[1] / args[0])
+    }
+}
+
+fn sqrt(args: &[f64]) -> Result<f64, String> {
+    if args[0] < 0.0 {
+        return Err(String::from("no real square root of a negative number"));
+    }
+    Ok(args[0].sqrt())
+}
+
+fn log(x: f64, base: f64) -> Result<f64, String> {
+    if x <= 0.0 {
+        return Err(String::from("logarithm of a non-positive number"));
+    }
+    if base <= 0.0 || base == 1.0 {
+        return Err(String::from("logarithm base must be positive and not 1"));
+    }
+    // the dedicated functions are exact on powers of their base
+    Ok(if base == 10.0 {
+        x.log10()
+    } else if base == 2.0 {
+        x.log2()
+    } else {
+        x.ln() / base.ln()
+    })
+}
+
+fn factorial(args: &[f64]) -> Result<f64, String> {
+    let n = args[0];
+    if n < 0.0 || n.fract() != 0.0 {
+        return Err(String::from("factorial of a non-natural number"));
+    }
+    // 171! does not fit in a float
+    if n > 170.0 {
+        return Err(String::from("result too large"));
+    }
+    Ok((2..=n as u64).fold(1.0, |product, i| product * i as f64))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_registry() {
+        assert_eq!((function("sqrt").unwrap().call)(&[16.0]), Ok(4.0));
+        assert_eq!((function("log").unwrap().call)(&[8.0, 2.0]), Ok(3.0));
+        assert_eq!((function("max").unwrap().call)(&[1.0, 7.0, 3.0]), Ok(7.0));
+        assert_eq!((function("fact").unwrap().call)(&[5.0]), Ok(120.0));
+        assert!((function("fact").unwrap().call)(&[2.5]).is_err());
+        assert!((function("ln").unwrap().call)(&[0.0]).is_err());
+        assert!(function("nope").is_none());
+        assert_eq!(constant("tau").unwrap().value, 2.0 * constant("pi").unwrap().value);
+    }
+
+    #[test]
+    fn test_arity() {
+        let log = function("log").unwrap();
+        assert!(log.accepts(1) && log.accepts(2) && !log.accepts(3));
+        assert_eq!(log.arity(), "`log` takes 1 to 2 arguments");
+        assert_eq!(function("sqrt").unwrap().arity(), "`sqrt` takes 1 argument");
+        assert!(function("min").unwrap().accepts(10));
+    }
+
+    #[test]
+    fn test_unique_names() {
+        for (i, function) in FUNCTIONS.iter().enumerate() {
+            assert!(FUNCTIONS[i + 1..].iter().all(|other| other.name != function.name));
+            assert!(constant(function.name).is_none());
+        }
+    }
+}
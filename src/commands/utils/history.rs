@@ -0,0 +1,196 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use super::playlists::SavedTrack;
+
+/// Number of tracks remembered per guild, the oldest being forgotten first.
+pub const HISTORY_LIMIT: usize = 1000;
+
+/// A track played in a guild.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayedTrack {
+    /// When it started, in seconds since the unix epoch.
+    pub time: u64,
+    /// Who asked for it, `None` if nobody did.
+    pub requester: Option<u64>,
+    pub track: SavedTrack,
+}
+
+/// `<time>\t<requester or ->\t<track>`.
+impl fmt::Display for PlayedTrack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let requester = self.requester.map_or(String::from("-"), |id| id.to_string());
+        write!(f, "{}\t{}\t{}", self.time, requester, self.track)
+    }
+}
+
+/// The lines adding `played` to the history of `guild_id` at the end of a
+/// saved history.
+pub fn appended(guild_id: u64, played: &PlayedTrack) -> String {
+    format!("guild={}\n\t{}\n", guild_id, played)
+}
+
+/// The tracks played in each guild, oldest first.
+pub struct PlayHistory {
+    guilds: HashMap<u64, Vec<PlayedTrack>>,
+}
+
+impl PlayHistory {
+    pub fn new() -> Self {
+        Self {
+            guilds: HashMap::new(),
+        }
+    }
+
+    /// Number of tracks remembered, of every guild.
+    pub fn count(&self) -> usize {
+        self.guilds.values().map(Vec::len).sum()
+    }
+
+    pub fn record(&mut self, guild_id: u64, played: PlayedTrack) {
+        let history = self.guilds.entry(guild_id).or_default();
+        history.push(played);
+        if history.len() > HISTORY_LIMIT {
+            let excess = history.len() - HISTORY_LIMIT;
+            history.drain(..excess);
+        }
+    }
+
+    /// Returns the tracks played in `guild_id`, the latest first.
+    pub fn recent(&self, guild_id: u64) -> impl Iterator<Item = &PlayedTrack> {
+        self.guilds.get(&guild_id)
+            .into_iter()
+            .flat_map(|history| history.iter().rev())
+    }
+
+    /// Returns the `count` tracks played the most in `guild_id`, with how many
+    /// times they have been, ties going to the latest played.
+    pub fn most_played(&self, guild_id: u64, count: usize) -> Vec<(&SavedTrack, usize)> {
+        let mut plays: Vec<(&SavedTrack, usize)> = Vec::new();
+        for played in self.recent(guild_id) {
+            match plays.iter_mut().find(|(track, _)| track.identifier == played.track.identifier) {
+                Some((_, plays)) => *plays += 1,
+                None => plays.push((&played.track, 1)),
+            }
+        }
+
+        // the sort is stable, keeping the latest first among equals
+        plays.sort_by(|a, b| b.1.cmp(&a.1));
+        plays.truncate(count);
+        plays
+    }
+}
+
+/// One `guild=..` line per guild, followed by its tracks on lines starting with
+/// a tab: `\t<time>\t<requester or ->\t<track>`. A guild may come again
+/// further, with the tracks `appended` to the saved history.
+impl fmt::Display for PlayHistory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut guilds: Vec<&u64> = self.guilds.keys().collect();
+        guilds.sort();
+
+        for guild_id in guilds {
+            writeln!(f, "guild={}", guild_id)?;
+            for played in &self.guilds[guild_id] {
+                writeln!(f, "\t{}", played)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PlayHistory {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut history = PlayHistory::new();
+        let mut guild = None;
+
+        for line in s.lines().filter(|line| !line.trim().is_empty()) {
+            if let Some(played) = line.strip_prefix('\t') {
+                let guild_id = guild.ok_or_else(|| String::from("Track found before any guild"))?;
+                let mut parts = played.splitn(3, '\t');
+                let invalid = || format!("Invalid played track `{}`", played);
+                let time = parts.next().and_then(|time| time.parse().ok()).ok_or_else(invalid)?;
+                let requester = match parts.next().ok_or_else(invalid)? {
+                    "-" => None,
+                    id => Some(id.parse().map_err(|_| invalid())?),
+                };
+                let track = parts.next().ok_or_else(invalid)?.parse()?;
+                history.record(guild_id, PlayedTrack { time, requester, track });
+            } else {
+                guild = Some(line.trim().strip_prefix("guild=")
+                                 .and_then(|id| id.parse().ok())
+                                 .ok_or_else(|| format!("Invalid guild `{}`", line))?);
+            }
+        }
+
+        Ok(history)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(identifier: &str) -> SavedTrack {
+        SavedTrack {
+            track: format!("QAAA{}", identifier),
+            identifier: identifier.to_string(),
+            title: format!("Title of {}", identifier),
+            author: String::from("Someone"),
+            uri: format!("https://www.youtube.com/watch?v={}", identifier),
+            length: 180_000,
+            is_stream: false,
+            is_seekable: true,
+        }
+    }
+
+    fn played(time: u64, identifier: &str) -> PlayedTrack {
+        PlayedTrack { time, requester: Some(7), track: track(identifier) }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut history = PlayHistory::new();
+        history.record(1, played(100, "a"));
+        history.record(1, PlayedTrack { time: 200, requester: None, track: track("b") });
+        history.record(2, played(300, "c"));
+
+        let parsed = PlayHistory::from_str(&history.to_string()).unwrap();
+        assert_eq!(parsed.recent(1).collect::<Vec<_>>(), history.recent(1).collect::<Vec<_>>());
+        assert_eq!(parsed.recent(2).count(), 1);
+        assert!(PlayHistory::from_str("\t1\t-\tnot a track").is_err());
+
+        let appended = format!("{}{}", history, appended(1, &played(400, "d")));
+        let parsed = PlayHistory::from_str(&appended).unwrap();
+        assert_eq!(parsed.recent(1).next().unwrap().track.identifier, "d");
+        assert_eq!(parsed.count(), 4);
+    }
+
+    #[test]
+    fn test_most_played() {
+        let mut history = PlayHistory::new();
+        for (time, identifier) in ["a", "b", "a", "c", "b", "a"].iter().enumerate() {
+            history.record(1, played(time as u64, identifier));
+        }
+
+        let most: Vec<(&str, usize)> = history.most_played(1, 2).into_iter()
+            .map(|(track, plays)| (track.identifier.as_str(), plays))
+            .collect();
+        assert_eq!(most, vec![("a", 3), ("b", 2)]);
+        assert_eq!(history.recent(1).next().unwrap().track.identifier, "a");
+        assert!(history.most_played(2, 5).is_empty());
+    }
+
+    #[test]
+    fn test_limit() {
+        let mut history = PlayHistory::new();
+        for time in 0..HISTORY_LIMIT as u64 + 10 {
+            history.record(1, played(time, "a"));
+        }
+        assert_eq!(history.recent(1).count(), HISTORY_LIMIT);
+        assert_eq!(history.recent(1).last().unwrap().time, 10);
+    }
+}
@@ -0,0 +1,250 @@
+use std::fmt;
+use std::marker::PhantomData;
+use std::str::FromStr;
+
+use super::exact::{BigInt, Rational};
+use super::expr::{Arithmetic, Op};
+use super::functions::{Constant, Function};
+use super::scope::Number;
+
+/// The integer types of programmer mode.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Width {
+    U32,
+    U64,
+    I64,
+}
+
+pub const WIDTHS: [Width; 3] = [Width::U32, Width::U64, Width::I64];
+
+impl Width {
+    pub fn bits(self) -> u32 {
+        match self {
+            Width::U32 => 32,
+            Width::U64 | Width::I64 => 64,
+        }
+    }
+
+    pub fn is_signed(self) -> bool {
+        self == Width::I64
+    }
+
+    /// The bits of `value` in the type, negative values in two's complement.
+    fn bits_of(self, value: i128) -> u128 {
+        value as u128 & ((1 << self.bits()) - 1)
+    }
+
+    /// Wraps `value` around into the range of the type, as machines do.
+    pub fn wrap(self, value: i128) -> i128 {
+        let bits = self.bits_of(value);
+        if self.is_signed() && bits >> (self.bits() - 1) == 1 {
+            bits as i128 - (1 << self.bits())
+        } else {
+            bits as i128
+        }
+    }
+
+    /// `value` in decimal, hexadecimal, octal and binary, the negative ones
+    /// in two's complement but in decimal.
+    pub fn bases(self, value: i128) -> [String; 4] {
+        let bits = self.bits_of(value);
+        [value.to_string(), format!("0x{:x}", bits), format!("0o{:o}", bits), format!("0b{:b}", bits)]
+    }
+}
+
+impl fmt::Display for Width {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Width::U32 => write!(f, "u32"),
+            Width::U64 => write!(f, "u64"),
+            Width::I64 => write!(f, "i64"),
+        }
+    }
+}
+
+impl FromStr for Width {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        WIDTHS.iter()
+            .find(|width| width.to_string().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| format!("Invalid integer type `{}`", s))
+    }
+}
+
+/// The type of an `Integer`, known to its functions taking no value.
+pub trait Kind: Clone + fmt::Debug + PartialEq {
+    const WIDTH: Width;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct U32;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct U64;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct I64;
+
+impl Kind for U32 {
+    const WIDTH: Width = Width::U32;
+}
+
+impl Kind for U64 {
+    const WIDTH: Width = Width::U64;
+}
+
+impl Kind for I64 {
+    const WIDTH: Width = Width::I64;
+}
+
+/// An integer of programmer mode, wrapping around on overflow.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Integer<K> {
+    pub value: i128,
+    kind: PhantomData<K>,
+}
+
+impl<K: Kind> Integer<K> {
+    pub fn new(value: i128) -> Self {
+        Self {
+            value: K::WIDTH.wrap(value),
+            kind: PhantomData,
+        }
+    }
+
+    /// The integer as an exact number, to keep it in a variable.
+    pub fn into_number(self) -> Number {
+        Number::Exact(Rational::integer(BigInt::from_i128(self.value)))
+    }
+}
+
+/// Integer arithmetic, divisions being truncated and every result wrapping
+/// around the range of the type.
+impl<K: Kind> Arithmetic for Integer<K> {
+    fn literal(_value: f64, text: &str) -> Result<Self, String> {
+        let number: Rational = text.parse()?;
+        if !number.is_integer() {
+            return Err(format!("`{}` is not an integer", text));
+        }
+        number.numerator().to_i128().map(Self::new).ok_or_else(|| String::from("integer too large"))
+    }
+
+    fn variable(value: &Number) -> Result<Self, String> {
+        match value {
+            Number::Exact(value) if value.is_integer() => {
+                value.numerator().to_i128().map(Self::new).ok_or_else(|| String::from("is too large an integer"))
+            },
+            Number::Float(value) if value.fract() == 0.0 && value.abs() < 2f64.powi(127) => Ok(Self::new(*value as i128)),
+            Number::Matrix(_) => Err(String::from("is a matrix")),
+            _ => Err(String::from("is not an integer")),
+        }
+    }
+
+    fn constant(constant: &Constant) -> Result<Self, String> {
+        Err(format!("`{}` is not an integer", constant.name))
+    }
+
+    fn negate(self) -> Self {
+        Self::new(self.value.wrapping_neg())
+    }
+
+    fn binary(op: Op, left: Self, right: Self) -> Result<Self, String> {
+        let (left, right) = (left.value, right.value);
+        let value = match op {
+            Op::Add => left.wrapping_add(right),
+            Op::Sub => left.wrapping_sub(right),
+            Op::Mul => left.wrapping_mul(right),
+            Op::Div if right == 0 => return Err(String::from("division by zero")),
+            Op::Div => left.wrapping_div(right),
+            Op::Rem if right == 0 => return Err(String::from("modulo by zero")),
+            Op::Rem => left.wrapping_rem(right),
+            Op::Pow if right < 0 => return Err(String::from("no negative powers of integers")),
+            Op::Pow => {
+                let (mut base, mut exponent, mut power) = (left, right, 1i128);
+                while exponent > 0 {
+                    if exponent % 2 == 1 {
+                        power = power.wrapping_mul(base);
+                    }
+                    base = base.wrapping_mul(base);
+                    exponent /= 2;
+                }
+                power
+            },
+            // the bits shifted out of the type being lost
+            Op::Shl if right >= 128 => 0,
+            Op::Shl if right >= 0 => left.wrapping_shl(right as u32),
+            op => op.bitwise(left, right)?,
+        };
+        Ok(Self::new(value))
+    }
+
+    fn to_integer(&self) -> Option<i64> {
+        if self.value.abs() <= i32::MAX as i128 {
+            Some(self.value as i64)
+        } else {
+            None
+        }
+    }
+
+    fn call(function: &Function, args: &[Self]) -> Result<Self, String> {
+        match function.name {
+            "abs" => Ok(Self::new(args[0].value.wrapping_abs())),
+            "floor" | "ceil" | "round" => Ok(args[0].clone()),
+            "min" => Ok(args.iter().min_by_key(|arg| arg.value).cloned().unwrap()),
+            "max" => Ok(args.iter().max_by_key(|arg| arg.value).cloned().unwrap()),
+            "fact" if args[0].value < 0 => Err(String::from("no factorial of negative numbers")),
+            // wrapping to 0 from 66!, which 2^64 divides
+            "fact" => Ok(Self::new((2..=args[0].value.min(66)).fold(1, |product: i128, n| K::WIDTH.wrap(product * n)))),
+            name => Err(format!("`{}` has no integer value", name)),
+        }
+    }
+
+    fn check(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::expr::{parse_statement, ExprError, Statement};
+    use super::super::scope::Scope;
+
+    fn compute<K: Kind>(input: &str) -> Result<i128, ExprError> {
+        match parse_statement(input, true)? {
+            Statement::Eval(expr, None) => expr.eval::<Integer<K>>(&Scope::new()).map(|quantity| quantity.value.value),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(Width::U32.wrap(-1), 0xffff_ffff);
+        assert_eq!(Width::U32.wrap(1 << 32), 0);
+        assert_eq!(Width::I64.wrap(i64::MAX as i128 + 1), i64::MIN as i128);
+        assert_eq!(Width::U64.wrap(-1), u64::MAX as i128);
+        assert_eq!(Width::I64.bases(-1), ["-1", "0xffffffffffffffff", "0o1777777777777777777777", &format!("0b{}", "1".repeat(64))]);
+        assert_eq!(Width::U32.bases(255), ["255", "0xff", "0o377", "0b11111111"]);
+        assert_eq!("I64".parse(), Ok(Width::I64));
+        assert!("u8".parse::<Width>().is_err());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        assert_eq!(compute::<U32>("0xffffffff + 1"), Ok(0));
+        assert_eq!(compute::<U32>("0 - 1"), Ok(0xffff_ffff));
+        assert_eq!(compute::<I64>("0x7fffffffffffffff + 1"), Ok(i64::MIN as i128));
+        assert_eq!(compute::<U64>("2 ** 64 + 7 / 2"), Ok(3));
+        assert_eq!(compute::<I64>("-7 / 2"), Ok(-3));
+        assert_eq!(compute::<U32>("0b1100 ^ 0b1010 | 1 << 8"), Ok(0b1_0000_0110));
+        assert_eq!(compute::<U32>("~0 >> 28 & 0o7"), Ok(7));
+        assert_eq!(compute::<I64>("-16 >> 2"), Ok(-4));
+        assert_eq!(compute::<U32>("1 << 40"), Ok(0));
+        assert_eq!(compute::<U64>("fact(70)"), Ok(0));
+        assert_eq!(compute::<U32>("1.5").unwrap_err().message, "`1.5` is not an integer");
+        assert_eq!(compute::<U32>("1 / 0").unwrap_err().message, "division by zero");
+        assert_eq!(compute::<U32>("sqrt(4)").unwrap_err().message, "`sqrt` has no integer value");
+    }
+}
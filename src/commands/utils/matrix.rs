@@ -0,0 +1,445 @@
+use super::expr::{Arithmetic, Op};
+use super::functions::{Constant, Function};
+use super::scope::Number;
+
+/// The most rows or columns of a matrix, for results to fit in a message.
+pub const MAX_SIZE: usize = 10;
+
+/// The most work of an operation on exact matrices, as the sum of the
+/// squared digits of the numbers multiplied, divided, added or subtracted,
+/// their fractions growing quickly.
+const MAX_WORK: u64 = 10_000_000_000;
+
+/// The functions of matrices, numbers being taken as matrices of a single
+/// entry.
+const MATRIX_FUNCTIONS: [&str; 4] = ["transpose", "det", "inv", "solve"];
+
+/// Numbers in rows and columns.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Matrix<N> {
+    pub rows: usize,
+    pub columns: usize,
+    /// Row after row.
+    pub entries: Vec<N>,
+}
+
+/// The numbers of matrices, which elimination needs to compare.
+pub trait Scalar: Arithmetic {
+    /// The absolute value, roughly.
+    fn magnitude(&self) -> f64;
+    /// Whether the number is zero, or too small to be told from it among
+    /// numbers of `scale` magnitude.
+    fn is_negligible(&self, scale: f64) -> bool;
+    /// The digits written, a computation taking about their square in time.
+    fn size(&self) -> usize;
+}
+
+/// The work done so far by an operation on matrices, see `MAX_WORK`.
+#[derive(Default)]
+struct Work {
+    done: u64,
+}
+
+impl Work {
+    /// Computes `left op right`, unless it is too much work.
+    fn binary<N: Scalar>(&mut self, op: Op, left: N, right: N) -> Result<N, String> {
+        let size = (left.size() + right.size()) as u64;
+        self.done += size * size;
+        if self.done > MAX_WORK {
+            return Err(String::from("too long to compute exactly"));
+        }
+        N::binary(op, left, right)
+    }
+}
+
+impl Scalar for f64 {
+    fn magnitude(&self) -> f64 {
+        self.abs()
+    }
+
+    fn is_negligible(&self, scale: f64) -> bool {
+        self.abs() <= 1e-12 * scale
+    }
+
+    fn size(&self) -> usize {
+        1
+    }
+}
+
+impl<N> Matrix<N> {
+    pub fn row(&self, i: usize) -> &[N] {
+        &self.entries[i * self.columns..(i + 1) * self.columns]
+    }
+
+    pub fn map<M>(self, f: impl FnMut(N) -> M) -> Matrix<M> {
+        Matrix {
+            rows: self.rows,
+            columns: self.columns,
+            entries: self.entries.into_iter().map(f).collect(),
+        }
+    }
+
+    /// The size, as `2×3` for two rows and three columns.
+    fn shape(&self) -> String {
+        format!("{}×{}", self.rows, self.columns)
+    }
+
+    fn check_square(&self) -> Result<(), String> {
+        if self.rows == self.columns {
+            Ok(())
+        } else {
+            Err(format!("expected a square matrix, not a {} one", self.shape()))
+        }
+    }
+}
+
+impl<N: Scalar> Matrix<N> {
+    fn identity(size: usize) -> Result<Self, String> {
+        let (zero, one) = (N::literal(0.0, "0")?, N::literal(1.0, "1")?);
+        let entries = (0..size * size).map(|i| if i % (size + 1) == 0 { one.clone() } else { zero.clone() }).collect();
+        Ok(Self { rows: size, columns: size, entries })
+    }
+
+    fn transpose(&self) -> Self {
+        let entries = (0..self.columns)
+            .flat_map(|j| (0..self.rows).map(move |i| (i, j)))
+            .map(|(i, j)| self.entries[i * self.columns + j].clone())
+            .collect();
+        Self { rows: self.columns, columns: self.rows, entries }
+    }
+
+    /// Adds or subtracts the entries of matrices of the same size.
+    fn entrywise(op: Op, left: Self, right: Self) -> Result<Self, String> {
+        if (left.rows, left.columns) != (right.rows, right.columns) {
+            return Err(format!("matrices of different sizes, {} and {}", left.shape(), right.shape()));
+        }
+        let entries = left.entries.into_iter()
+            .zip(right.entries)
+            .map(|(left, right)| N::binary(op, left, right))
+            .collect::<Result<Vec<N>, String>>()?;
+        Ok(Self { rows: left.rows, columns: left.columns, entries })
+    }
+
+    /// Applies `op` between each entry and `scalar`, which is on the left if
+    /// `scalar_first`.
+    fn scale(self, op: Op, scalar: &N, scalar_first: bool) -> Result<Self, String> {
+        let (rows, columns) = (self.rows, self.columns);
+        let entries = self.entries.into_iter()
+            .map(|entry| if scalar_first { N::binary(op, scalar.clone(), entry) } else { N::binary(op, entry, scalar.clone()) })
+            .collect::<Result<Vec<N>, String>>()?;
+        Ok(Self { rows, columns, entries })
+    }
+
+    fn mul(&self, other: &Self, work: &mut Work) -> Result<Self, String> {
+        if self.columns != other.rows {
+            return Err(format!("cannot multiply a {} matrix by a {} one", self.shape(), other.shape()));
+        }
+        let mut entries = Vec::with_capacity(self.rows * other.columns);
+        for i in 0..self.rows {
+            for j in 0..other.columns {
+                let mut sum = work.binary(Op::Mul, self.entries[i * self.columns].clone(), other.entries[j].clone())?;
+                for k in 1..self.columns {
+                    let product = work.binary(Op::Mul, self.entries[i * self.columns + k].clone(),
+                                              other.entries[k * other.columns + j].clone())?;
+                    sum = work.binary(Op::Add, sum, product)?;
+                }
+                entries.push(sum);
+            }
+        }
+        let product = Self { rows: self.rows, columns: other.columns, entries };
+        product.check()?;
+        Ok(product)
+    }
+
+    /// Raises a square matrix to an integer power, by squaring.
+    fn pow(&self, exponent: i64, work: &mut Work) -> Result<Self, String> {
+        self.check_square()?;
+        let mut base = if exponent < 0 { self.inverse(work)? } else { self.clone() };
+        let mut exponent = exponent.abs();
+        let mut power = Self::identity(self.rows)?;
+        while exponent > 0 {
+            if exponent % 2 == 1 {
+                power = power.mul(&base, work)?;
+            }
+            exponent /= 2;
+            if exponent > 0 {
+                base = base.mul(&base, work)?;
+            }
+        }
+        Ok(power)
+    }
+
+    /// Reduces the square matrix to the identity by Gauss-Jordan elimination,
+    /// doing the same to `other`. Gives the determinant, and what `other`
+    /// became unless the matrix is singular.
+    fn eliminate(&self, other: Self, work: &mut Work) -> Result<(N, Option<Self>), String> {
+        self.check_square()?;
+        let size = self.rows;
+        let (mut a, mut b) = (self.clone(), other);
+        let scale = a.entries.iter().map(Scalar::magnitude).fold(0.0, f64::max);
+        let mut determinant = N::literal(1.0, "1")?;
+
+        for column in 0..size {
+            // the largest pivot, for floats to lose as little as possible
+            let pivot = (column..size)
+                .max_by(|&i, &j| {
+                    let (x, y) = (a.entries[i * size + column].magnitude(), a.entries[j * size + column].magnitude());
+                    x.partial_cmp(&y).unwrap_or(std::cmp::Ordering::Equal)
+                })
+                .unwrap_or(column);
+            if a.entries[pivot * size + column].is_negligible(scale) {
+                return Ok((N::literal(0.0, "0")?, None));
+            }
+            if pivot != column {
+                for j in 0..size {
+                    a.entries.swap(pivot * size + j, column * size + j);
+                }
+                for j in 0..b.columns {
+                    b.entries.swap(pivot * b.columns + j, column * b.columns + j);
+                }
+                determinant = determinant.negate();
+            }
+
+            let pivot = a.entries[column * size + column].clone();
+            determinant = work.binary(Op::Mul, determinant, pivot.clone())?;
+            for j in 0..size {
+                let entry = &mut a.entries[column * size + j];
+                *entry = work.binary(Op::Div, entry.clone(), pivot.clone())?;
+            }
+            for j in 0..b.columns {
+                let entry = &mut b.entries[column * b.columns + j];
+                *entry = work.binary(Op::Div, entry.clone(), pivot.clone())?;
+            }
+
+            for i in (0..size).filter(|&i| i != column) {
+                let factor = a.entries[i * size + column].clone();
+                if factor.is_negligible(0.0) {
+                    continue;
+                }
+                for j in 0..size {
+                    let product = work.binary(Op::Mul, factor.clone(), a.entries[column * size + j].clone())?;
+                    a.entries[i * size + j] = work.binary(Op::Sub, a.entries[i * size + j].clone(), product)?;
+                }
+                for j in 0..b.columns {
+                    let product = work.binary(Op::Mul, factor.clone(), b.entries[column * b.columns + j].clone())?;
+                    b.entries[i * b.columns + j] = work.binary(Op::Sub, b.entries[i * b.columns + j].clone(), product)?;
+                }
+            }
+            a.check()?;
+            b.check()?;
+        }
+        Ok((determinant, Some(b)))
+    }
+
+    fn determinant(&self, work: &mut Work) -> Result<N, String> {
+        let nothing = Self { rows: self.rows, columns: 0, entries: Vec::new() };
+        Ok(self.eliminate(nothing, work)?.0)
+    }
+
+    fn inverse(&self, work: &mut Work) -> Result<Self, String> {
+        self.eliminate(Self::identity(self.rows)?, work)?.1
+            .ok_or_else(|| String::from("the matrix is not invertible"))
+    }
+
+    /// The `x` such that `self * x` is `b`.
+    fn solve(&self, b: Self, work: &mut Work) -> Result<Self, String> {
+        if b.rows != self.rows {
+            return Err(format!("cannot solve a system of a {} matrix with a {} one", self.shape(), b.shape()));
+        }
+        self.eliminate(b, work)?.1
+            .ok_or_else(|| String::from("the system has no single solution"))
+    }
+
+    fn check(&self) -> Result<(), String> {
+        self.entries.iter().try_for_each(N::check)
+    }
+}
+
+/// What expressions hold when they may hold matrices.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operand<N> {
+    Scalar(N),
+    Matrix(Matrix<N>),
+}
+
+impl<N: Scalar> Operand<N> {
+    fn is_scalar(&self) -> bool {
+        matches!(self, Operand::Scalar(_))
+    }
+
+    /// As a matrix, a number being one of a single entry.
+    fn into_matrix(self) -> Matrix<N> {
+        match self {
+            Operand::Scalar(value) => Matrix { rows: 1, columns: 1, entries: vec![value] },
+            Operand::Matrix(matrix) => matrix,
+        }
+    }
+
+    /// Converts the numbers, to keep them in variables.
+    pub fn into_number(self, number: fn(N) -> Number) -> Number {
+        match self {
+            Operand::Scalar(value) => number(value),
+            Operand::Matrix(matrix) => Number::Matrix(matrix.map(number)),
+        }
+    }
+}
+
+/// The operations of numbers, with the usual ones of matrices.
+impl<N: Scalar> Arithmetic for Operand<N> {
+    fn literal(value: f64, text: &str) -> Result<Self, String> {
+        N::literal(value, text).map(Operand::Scalar)
+    }
+
+    fn variable(value: &Number) -> Result<Self, String> {
+        match value {
+            Number::Matrix(matrix) => {
+                let entries = matrix.entries.iter().map(N::variable).collect::<Result<Vec<N>, String>>()?;
+                Ok(Operand::Matrix(Matrix { rows: matrix.rows, columns: matrix.columns, entries }))
+            },
+            value => N::variable(value).map(Operand::Scalar),
+        }
+    }
+
+    fn constant(constant: &Constant) -> Result<Self, String> {
+        N::constant(constant).map(Operand::Scalar)
+    }
+
+    fn matrix(rows: usize, columns: usize, entries: Vec<Self>) -> Result<Self, String> {
+        if rows > MAX_SIZE || columns > MAX_SIZE {
+            return Err(format!("at most {} rows and columns", MAX_SIZE));
+        }
+        let entries = entries.into_iter().map(|entry| match entry {
+            Operand::Scalar(value) => Ok(value),
+            Operand::Matrix(_) => Err(String::from("expected numbers in a matrix, not matrices")),
+        }).collect::<Result<Vec<N>, String>>()?;
+        Ok(Operand::Matrix(Matrix { rows, columns, entries }))
+    }
+
+    fn negate(self) -> Self {
+        match self {
+            Operand::Scalar(value) => Operand::Scalar(value.negate()),
+            Operand::Matrix(matrix) => Operand::Matrix(matrix.map(N::negate)),
+        }
+    }
+
+    fn binary(op: Op, left: Self, right: Self) -> Result<Self, String> {
+        use Operand::{Matrix as M, Scalar as S};
+        let work = &mut Work::default();
+        let matrix = match (op, left, right) {
+            (op, S(left), S(right)) => return N::binary(op, left, right).map(S),
+            (Op::Add, M(left), M(right)) | (Op::Sub, M(left), M(right)) => Matrix::entrywise(op, left, right)?,
+            (Op::Add, _, _) | (Op::Sub, _, _) => return Err(String::from("cannot add a number and a matrix")),
+            (Op::Mul, M(left), M(right)) => left.mul(&right, work)?,
+            (Op::Mul, S(scalar), M(matrix)) => matrix.scale(Op::Mul, &scalar, true)?,
+            (Op::Mul, M(matrix), S(scalar)) | (Op::Div, M(matrix), S(scalar)) => matrix.scale(op, &scalar, false)?,
+            (Op::Div, S(scalar), M(matrix)) => matrix.inverse(work)?.scale(Op::Mul, &scalar, true)?,
+            (Op::Div, M(left), M(right)) => left.mul(&right.inverse(work)?, work)?,
+            (Op::Rem, _, _) => return Err(String::from("no remainder of matrices")),
+            (Op::Pow, M(matrix), S(exponent)) => match exponent.to_integer() {
+                Some(exponent) => matrix.pow(exponent, work)?,
+                None => return Err(String::from("matrices can only be raised to integer powers")),
+            },
+            (Op::Pow, _, _) => return Err(String::from("an exponent cannot be a matrix")),
+            _ => return Err(String::from("bitwise operations take numbers, not matrices")),
+        };
+        // a row times a column being a number
+        Ok(if matrix.rows == 1 && matrix.columns == 1 { S(matrix.entries.into_iter().next().unwrap()) } else { M(matrix) })
+    }
+
+    fn to_integer(&self) -> Option<i64> {
+        match self {
+            Operand::Scalar(value) => value.to_integer(),
+            Operand::Matrix(_) => None,
+        }
+    }
+
+    fn call(function: &Function, args: &[Self]) -> Result<Self, String> {
+        if MATRIX_FUNCTIONS.contains(&function.name) {
+            let scalars = args.iter().all(Operand::is_scalar);
+            let mut matrices = args.iter().cloned().map(Operand::into_matrix);
+            let matrix = matrices.next().expect("Expected a matrix");
+            let work = &mut Work::default();
+            let mut result = match function.name {
+                "det" => return matrix.determinant(work).map(Operand::Scalar),
+                "inv" => matrix.inverse(work)?,
+                "solve" => matrix.solve(matrices.next().expect("Expected a second matrix"), work)?,
+                _ => matrix.transpose(),
+            };
+            return Ok(if scalars { Operand::Scalar(result.entries.remove(0)) } else { Operand::Matrix(result) });
+        }
+
+        let scalars = args.iter().map(|arg| match arg {
+            Operand::Scalar(value) => Ok(value.clone()),
+            Operand::Matrix(_) => Err(format!("`{}` takes numbers, not matrices", function.name)),
+        }).collect::<Result<Vec<N>, String>>()?;
+        N::call(function, &scalars).map(Operand::Scalar)
+    }
+
+    fn check(&self) -> Result<(), String> {
+        match self {
+            Operand::Scalar(value) => value.check(),
+            Operand::Matrix(matrix) => matrix.check(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::exact::Rational;
+    use super::super::functions::function;
+
+    fn matrix<N: Scalar>(rows: &[&[&str]]) -> Operand<N> {
+        let entries = rows.iter()
+            .flat_map(|row| row.iter())
+            .map(|entry| Operand::literal(entry.parse().unwrap(), entry).unwrap())
+            .collect();
+        Operand::matrix(rows.len(), rows[0].len(), entries).unwrap()
+    }
+
+    fn call<N: Scalar>(name: &str, args: &[Operand<N>]) -> Result<Operand<N>, String> {
+        Operand::call(function(name).unwrap(), args)
+    }
+
+    #[test]
+    fn test_operations() {
+        let a = matrix::<f64>(&[&["1", "2"], &["3", "4"]]);
+        let b = matrix::<f64>(&[&["5"], &["6"]]);
+        assert_eq!(Operand::binary(Op::Add, a.clone(), a.clone()), Ok(matrix(&[&["2", "4"], &["6", "8"]])));
+        assert_eq!(Operand::binary(Op::Mul, a.clone(), b.clone()), Ok(matrix(&[&["17"], &["39"]])));
+        assert_eq!(Operand::binary(Op::Mul, Operand::Scalar(2.0), b.clone()), Ok(matrix(&[&["10"], &["12"]])));
+        assert_eq!(Operand::binary(Op::Pow, a.clone(), Operand::Scalar(2.0)), Ok(matrix(&[&["7", "10"], &["15", "22"]])));
+        assert_eq!(Operand::binary(Op::Pow, a.clone(), Operand::Scalar(0.0)), Ok(matrix(&[&["1", "0"], &["0", "1"]])));
+        assert_eq!(call("transpose", &[b.clone()]), Ok(matrix(&[&["5", "6"]])));
+
+        assert_eq!(Operand::binary(Op::Mul, b.clone(), a.clone()), Err(String::from("cannot multiply a 2×1 matrix by a 2×2 one")));
+        assert_eq!(Operand::binary(Op::Add, a.clone(), b.clone()), Err(String::from("matrices of different sizes, 2×2 and 2×1")));
+        assert_eq!(Operand::binary(Op::Add, a.clone(), Operand::Scalar(1.0)), Err(String::from("cannot add a number and a matrix")));
+        assert_eq!(call("sqrt", &[a]), Err(String::from("`sqrt` takes numbers, not matrices")));
+    }
+
+    #[test]
+    fn test_linear_algebra() {
+        let a = matrix::<Rational>(&[&["2", "1"], &["1", "3"]]);
+        let b = matrix::<Rational>(&[&["3"], &["5"]]);
+        assert_eq!(call("det", &[a.clone()]), Ok(Operand::literal(5.0, "5").unwrap()));
+        assert_eq!(call("solve", &[a.clone(), b.clone()]), Ok(matrix(&[&["0.8"], &["1.4"]])));
+        let inverse = call("inv", &[a.clone()]).unwrap();
+        assert_eq!(inverse, matrix(&[&["0.6", "-0.2"], &["-0.2", "0.4"]]));
+        assert_eq!(Operand::binary(Op::Pow, a.clone(), Operand::literal(-1.0, "-1").unwrap()), Ok(inverse));
+        // needing rows to be swapped
+        let swapped = matrix::<Rational>(&[&["0", "1"], &["1", "0"]]);
+        assert_eq!(call("det", &[swapped]), Ok(Operand::literal(-1.0, "-1").unwrap()));
+
+        let singular = matrix::<f64>(&[&["1", "2", "3"], &["4", "5", "6"], &["7", "8", "9"]]);
+        assert_eq!(call("det", &[singular.clone()]), Ok(Operand::Scalar(0.0)));
+        assert_eq!(call("inv", &[singular]), Err(String::from("the matrix is not invertible")));
+        assert_eq!(call("det", &[matrix::<f64>(&[&["1", "2"]])]), Err(String::from("expected a square matrix, not a 1×2 one")));
+        // numbers as matrices of one entry
+        assert_eq!(call("inv", &[Operand::Scalar(4.0)]), Ok(Operand::Scalar(0.25)));
+        // stopped once it is too much work
+        let mut work = Work { done: MAX_WORK - 4 };
+        assert_eq!(work.binary(Op::Add, 1.0, 1.0), Ok(2.0));
+        assert_eq!(work.binary(Op::Add, 1.0, 1.0), Err(String::from("too long to compute exactly")));
+    }
+}
@@ -1,3 +1,22 @@
+pub mod csv;
+pub mod dice;
+pub mod exact;
+pub mod expr;
+pub mod font;
+pub mod functions;
+pub mod history;
+pub mod integer;
+pub mod matrix;
+pub mod player_state;
+pub mod playlists;
+pub mod plot;
+pub mod png;
+pub mod queue_rules;
+pub mod random;
+pub mod scope;
+pub mod stats;
+pub mod units;
+
 use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::str::FromStr;
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+use super::playlists::SavedTrack;
+
+/// A queued track, with the text channel it has been requested in and by whom.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueuedTrack {
+    pub channel_id: u64,
+    pub requester: u64,
+    pub track: SavedTrack,
+}
+
+/// What a guild was listening to, to resume it after a restart.
+#[derive(Clone, Debug, PartialEq)]
+pub struct GuildPlayer {
+    pub guild_id: u64,
+    pub voice_channel: u64,
+    /// Position in the current track, in milliseconds.
+    pub position: u64,
+    pub volume: u16,
+    pub looping: bool,
+    /// The current track first, then the rest of the queue.
+    pub queue: Vec<QueuedTrack>,
+}
+
+/// State of the players of every guild.
+pub struct PlayerStates {
+    pub players: Vec<GuildPlayer>,
+}
+
+impl PlayerStates {
+    pub fn new() -> Self {
+        Self {
+            players: Vec::new(),
+        }
+    }
+}
+
+/// One `guild=.. voice=.. position=.. volume=.. loop=..` line per guild,
+/// followed by its queue on lines starting with a tab.
+impl fmt::Display for PlayerStates {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for player in &self.players {
+            writeln!(f, "guild={} voice={} position={} volume={} loop={}",
+                     player.guild_id, player.voice_channel, player.position,
+                     player.volume, player.looping)?;
+            for queued in &player.queue {
+                writeln!(f, "\t{}\t{}\t{}", queued.channel_id, queued.requester, queued.track)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PlayerStates {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut states = PlayerStates::new();
+
+        for line in s.lines().filter(|line| !line.trim().is_empty()) {
+            if let Some(queued) = line.strip_prefix('\t') {
+                let player = states.players.last_mut()
+                    .ok_or_else(|| String::from("Track found before any guild"))?;
+                let mut parts = queued.splitn(3, '\t');
+                let invalid = || format!("Invalid queued track `{}`", queued);
+                player.queue.push(QueuedTrack {
+                    channel_id: parts.next().and_then(|id| id.parse().ok()).ok_or_else(invalid)?,
+                    requester: parts.next().and_then(|id| id.parse().ok()).ok_or_else(invalid)?,
+                    track: parts.next().ok_or_else(invalid)?.parse()?,
+                });
+            } else {
+                let fields: HashMap<&str, &str> = line.split_whitespace()
+                    .filter_map(|field| {
+                        let mut parts = field.splitn(2, '=');
+                        Some((parts.next()?, parts.next()?))
+                    })
+                    .collect();
+                let invalid = || format!("Invalid player state `{}`", line);
+                states.players.push(GuildPlayer {
+                    guild_id: field(&fields, "guild").ok_or_else(invalid)?,
+                    voice_channel: field(&fields, "voice").ok_or_else(invalid)?,
+                    position: field(&fields, "position").ok_or_else(invalid)?,
+                    volume: field(&fields, "volume").ok_or_else(invalid)?,
+                    looping: field(&fields, "loop").ok_or_else(invalid)?,
+                    queue: Vec::new(),
+                });
+            }
+        }
+
+        Ok(states)
+    }
+}
+
+fn field<T: FromStr>(fields: &HashMap<&str, &str>, key: &str) -> Option<T> {
+    fields.get(key)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() {
+        let track = SavedTrack {
+            track: String::from("QAAAjQIAJFJpY2sgQXN0bGV5"),
+            identifier: String::from("dQw4w9WgXcQ"),
+            title: String::from("Never Gonna Give You Up"),
+            author: String::from("Rick Astley"),
+            uri: String::from("https://www.youtube.com/watch?v=dQw4w9WgXcQ"),
+            length: 212_000,
+            is_stream: false,
+            is_seekable: true,
+        };
+        let mut states = PlayerStates::new();
+        states.players.push(GuildPlayer {
+            guild_id: 1,
+            voice_channel: 2,
+            position: 61_500,
+            volume: 80,
+            looping: true,
+            queue: vec![QueuedTrack { channel_id: 3, requester: 4, track }],
+        });
+        states.players.push(GuildPlayer {
+            guild_id: 5,
+            voice_channel: 6,
+            position: 0,
+            volume: 100,
+            looping: false,
+            queue: Vec::new(),
+        });
+
+        let parsed = PlayerStates::from_str(&states.to_string()).unwrap();
+        assert_eq!(parsed.players, states.players);
+    }
+
+    #[test]
+    fn test_invalid_state() {
+        assert!(PlayerStates::from_str("guild=1 voice=2 volume=100 loop=false").is_err());
+        assert!(PlayerStates::from_str("\t1\t2\tnot a track").is_err());
+    }
+}
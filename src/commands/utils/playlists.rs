@@ -0,0 +1,226 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Who a saved playlist belongs to, by discord id.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PlaylistOwner {
+    User(u64),
+    Guild(u64),
+}
+
+impl fmt::Display for PlaylistOwner {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PlaylistOwner::User(id) => write!(f, "user:{}", id),
+            PlaylistOwner::Guild(id) => write!(f, "guild:{}", id),
+        }
+    }
+}
+
+impl FromStr for PlaylistOwner {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, ':');
+        let kind = parts.next().unwrap_or("");
+        let id = parts.next()
+            .and_then(|id| id.parse::<u64>().ok())
+            .ok_or_else(|| format!("Invalid playlist owner `{}`", s))?;
+
+        match kind {
+            "user" => Ok(PlaylistOwner::User(id)),
+            "guild" => Ok(PlaylistOwner::Guild(id)),
+            _ => Err(format!("Invalid playlist owner `{}`", s)),
+        }
+    }
+}
+
+/// A track of a saved playlist, keeping the lavalink track and its info so that
+/// it can be queued again without searching it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SavedTrack {
+    pub track: String,
+    pub identifier: String,
+    pub title: String,
+    pub author: String,
+    pub uri: String,
+    pub length: u64,
+    pub is_stream: bool,
+    pub is_seekable: bool,
+}
+
+impl fmt::Display for SavedTrack {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+               clean(&self.track), clean(&self.identifier), self.length,
+               self.is_stream, self.is_seekable, clean(&self.title),
+               clean(&self.author), clean(&self.uri))
+    }
+}
+
+impl FromStr for SavedTrack {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split('\t').collect();
+        if parts.len() != 8 {
+            return Err(format!("Invalid saved track `{}`", s));
+        }
+
+        let invalid = || format!("Invalid saved track `{}`", s);
+        Ok(SavedTrack {
+            track: String::from(parts[0]),
+            identifier: String::from(parts[1]),
+            length: parts[2].parse().map_err(|_| invalid())?,
+            is_stream: parts[3].parse().map_err(|_| invalid())?,
+            is_seekable: parts[4].parse().map_err(|_| invalid())?,
+            title: String::from(parts[5]),
+            author: String::from(parts[6]),
+            uri: String::from(parts[7]),
+        })
+    }
+}
+
+/// Replaces the characters the file format uses as separators.
+fn clean(s: &str) -> String {
+    s.replace(|c| c == '\t' || c == '\n' || c == '\r', " ")
+}
+
+/// Saved playlists of every user and guild, by owner and name.
+pub struct PlaylistSet {
+    pub playlists: BTreeMap<(PlaylistOwner, String), Vec<SavedTrack>>,
+}
+
+impl PlaylistSet {
+    pub fn new() -> Self {
+        Self {
+            playlists: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the tracks of a playlist.
+    pub fn get(&self, owner: PlaylistOwner, name: &str) -> Option<&Vec<SavedTrack>> {
+        self.playlists.get(&(owner, String::from(name)))
+    }
+
+    /// Creates or replaces a playlist.
+    pub fn set(&mut self, owner: PlaylistOwner, name: &str, tracks: Vec<SavedTrack>) {
+        self.playlists.insert((owner, String::from(name)), tracks);
+    }
+
+    /// Appends tracks at the end of a playlist, creating it if needed.
+    pub fn extend(&mut self, owner: PlaylistOwner, name: &str, tracks: Vec<SavedTrack>) {
+        self.playlists.entry((owner, String::from(name)))
+            .or_insert_with(Vec::new)
+            .extend(tracks);
+    }
+
+    /// Removes the `index`-th track (starting at 0) of a playlist.
+    pub fn remove_track(&mut self, owner: PlaylistOwner, name: &str, index: usize) -> Option<SavedTrack> {
+        let tracks = self.playlists.get_mut(&(owner, String::from(name)))?;
+        if index < tracks.len() {
+            Some(tracks.remove(index))
+        } else {
+            None
+        }
+    }
+
+    /// Removes a whole playlist.
+    pub fn remove(&mut self, owner: PlaylistOwner, name: &str) -> Option<Vec<SavedTrack>> {
+        self.playlists.remove(&(owner, String::from(name)))
+    }
+
+    /// Returns the names of the playlists of `owner` with their length.
+    pub fn list(&self, owner: PlaylistOwner) -> Vec<(&str, usize)> {
+        self.playlists.iter()
+            .filter(|((o, _), _)| *o == owner)
+            .map(|((_, name), tracks)| (name.as_str(), tracks.len()))
+            .collect()
+    }
+}
+
+/// One `<owner> <name>` line per playlist, followed by its tracks on lines
+/// starting with a tab.
+impl fmt::Display for PlaylistSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for ((owner, name), tracks) in &self.playlists {
+            writeln!(f, "{} {}", owner, name)?;
+            for track in tracks {
+                writeln!(f, "\t{}", track)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for PlaylistSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut set = PlaylistSet::new();
+        let mut current: Option<(PlaylistOwner, String)> = None;
+
+        for line in s.lines().filter(|line| !line.trim().is_empty()) {
+            if let Some(track) = line.strip_prefix('\t') {
+                let key = current.clone()
+                    .ok_or_else(|| String::from("Track found before any playlist"))?;
+                set.playlists.entry(key).or_insert_with(Vec::new)
+                    .push(track.parse()?);
+            } else {
+                let mut parts = line.splitn(2, ' ');
+                let owner = parts.next().unwrap_or("").parse::<PlaylistOwner>()?;
+                let name = parts.next()
+                    .ok_or_else(|| format!("Missing playlist name in `{}`", line))?;
+                set.playlists.entry((owner, String::from(name))).or_insert_with(Vec::new);
+                current = Some((owner, String::from(name)));
+            }
+        }
+
+        Ok(set)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(title: &str) -> SavedTrack {
+        SavedTrack {
+            track: format!("QAAA{}==", title.len()),
+            identifier: String::from("dQw4w9WgXcQ"),
+            title: String::from(title),
+            author: String::from("Rick\tAstley"),
+            uri: String::from("https://www.youtube.com/watch?v=dQw4w9WgXcQ"),
+            length: 212_000,
+            is_stream: false,
+            is_seekable: true,
+        }
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut set = PlaylistSet::new();
+        set.set(PlaylistOwner::User(42), "weekly", vec![track("one"), track("two")]);
+        set.set(PlaylistOwner::Guild(7), "empty", Vec::new());
+
+        let parsed = PlaylistSet::from_str(&set.to_string()).unwrap();
+        let tracks = parsed.get(PlaylistOwner::User(42), "weekly").unwrap();
+        assert_eq!(tracks.len(), 2);
+        assert_eq!(tracks[1].title, "two");
+        assert_eq!(tracks[0].author, "Rick Astley");
+        assert_eq!(parsed.get(PlaylistOwner::Guild(7), "empty"), Some(&Vec::new()));
+    }
+
+    #[test]
+    fn test_owners_are_separated() {
+        let mut set = PlaylistSet::new();
+        set.extend(PlaylistOwner::User(1), "mix", vec![track("a")]);
+        set.extend(PlaylistOwner::Guild(1), "mix", vec![track("b"), track("c")]);
+
+        assert_eq!(set.list(PlaylistOwner::User(1)), vec![("mix", 1)]);
+        assert_eq!(set.list(PlaylistOwner::Guild(1)), vec![("mix", 2)]);
+        assert_eq!(set.remove_track(PlaylistOwner::Guild(1), "mix", 0).unwrap().title, "b");
+        assert!(set.remove_track(PlaylistOwner::User(1), "mix", 3).is_none());
+    }
+}
@@ -0,0 +1,485 @@
+use std::cmp::Ordering;
+
+use super::expr::{parse, Expr, ExprError};
+use super::font::{glyph, GLYPH_HEIGHT, GLYPH_WIDTH};
+use super::png;
+use super::scope::Scope;
+
+/// Size of the images, in pixels.
+const WIDTH: usize = 800;
+const HEIGHT: usize = 500;
+/// Room around the plot, for the labels of the axes.
+const LEFT: usize = 70;
+const RIGHT: usize = 15;
+const TOP: usize = 15;
+const BOTTOM: usize = 30;
+
+/// The variable of the plotted functions.
+pub const VARIABLE: &str = "x";
+/// What separates functions, commas separating arguments already.
+const SEPARATOR: char = ';';
+const DEFAULT_RANGE: (f64, f64) = (-10.0, 10.0);
+/// How many characters of each function the legend shows.
+const LEGEND_LENGTH: usize = 40;
+
+const BACKGROUND: u8 = 0;
+const GRID: u8 = 1;
+const INK: u8 = 2;
+/// The colors of the image, those of the functions after the others.
+const PALETTE: [[u8; 3]; 9] = [
+    [255, 255, 255], [226, 226, 226], [50, 50, 50],
+    [31, 119, 180], [214, 39, 40], [44, 160, 44], [255, 127, 14], [148, 103, 189], [23, 190, 207],
+];
+/// There is a color for each function.
+pub const MAX_FUNCTIONS: usize = PALETTE.len() - INK as usize - 1;
+
+/// A function to plot, as written at `position` in the request.
+struct Curve {
+    source: String,
+    expr: Expr,
+    position: usize,
+}
+
+/// Functions of `x` over a range.
+pub struct Plot {
+    curves: Vec<Curve>,
+    from: f64,
+    to: f64,
+}
+
+/// A rectangle of pixels, its bounds included.
+#[derive(Clone, Copy)]
+struct Area {
+    left: i64,
+    top: i64,
+    right: i64,
+    bottom: i64,
+}
+
+impl Area {
+    fn contains(&self, x: i64, y: i64) -> bool {
+        self.left <= x && x <= self.right && self.top <= y && y <= self.bottom
+    }
+}
+
+const IMAGE: Area = Area { left: 0, top: 0, right: WIDTH as i64 - 1, bottom: HEIGHT as i64 - 1 };
+const PLOT: Area = Area {
+    left: LEFT as i64,
+    top: TOP as i64,
+    right: (WIDTH - RIGHT) as i64 - 1,
+    bottom: (HEIGHT - BOTTOM) as i64 - 1,
+};
+
+/// The index in bytes of `word` standing alone in `s`.
+fn find_word(s: &str, word: &str) -> Option<usize> {
+    let mut start = 0;
+    for part in s.split(char::is_whitespace) {
+        if part == word {
+            return Some(start);
+        }
+        start += part.len() + 1;
+    }
+    None
+}
+
+/// Offsets the error of something found at `offset` characters in the request.
+fn shift(offset: usize) -> impl Fn(ExprError) -> ExprError {
+    move |why| ExprError { position: why.position + offset, ..why }
+}
+
+/// Evaluates a bound of the range, as `source` written at `offset` characters.
+fn bound(source: &str, offset: usize, scope: &Scope) -> Result<f64, ExprError> {
+    let trimmed = source.trim_start();
+    let offset = offset + source[..source.len() - trimmed.len()].chars().count();
+    if trimmed.trim().is_empty() {
+        return Err(ExprError { position: offset, message: String::from("expected a number") });
+    }
+    let quantity = parse(trimmed).and_then(|expr| expr.eval::<f64>(scope)).map_err(shift(offset))?;
+    if !quantity.dimension.is_none() {
+        return Err(ExprError { position: offset, message: String::from("expected a number without units") });
+    }
+    Ok(quantity.value)
+}
+
+impl Plot {
+    /// Parses functions separated by `;`, maybe followed by `from a to b`,
+    /// evaluating the range with `scope`.
+    pub fn parse(input: &str, scope: &Scope) -> Result<Self, ExprError> {
+        let chars = |bytes: usize| input[..bytes].chars().count();
+        let (functions, range) = match find_word(input, "from") {
+            Some(from) => (&input[..from], Some(from)),
+            None => (input, None),
+        };
+
+        let mut curves = Vec::new();
+        let mut start = 0;
+        for source in functions.split(SEPARATOR) {
+            let trimmed = source.trim_start();
+            let position = chars(start + source.len() - trimmed.len());
+            start += source.len() + 1;
+            let trimmed = trimmed.trim_end();
+            if trimmed.is_empty() {
+                return Err(ExprError { position, message: format!("expected a function of `{}`", VARIABLE) });
+            }
+            if curves.len() == MAX_FUNCTIONS {
+                return Err(ExprError { position, message: format!("at most {} functions at once", MAX_FUNCTIONS) });
+            }
+            let expr = parse(trimmed).map_err(shift(position))?;
+            curves.push(Curve { source: trimmed.to_string(), expr, position });
+        }
+
+        let (from, to) = match range {
+            None => DEFAULT_RANGE,
+            Some(from) => {
+                let bounds = &input[from + "from".len()..];
+                let start = from + "from".len();
+                let to = match find_word(bounds, "to") {
+                    Some(to) => to,
+                    None => return Err(ExprError {
+                        position: input.chars().count(),
+                        message: String::from("expected `to` and the end of the range"),
+                    }),
+                };
+                let low = bound(&bounds[..to], chars(start), scope)?;
+                let high = bound(&bounds[to + "to".len()..], chars(start + to + "to".len()), scope)?;
+                if low.partial_cmp(&high) != Some(Ordering::Less) {
+                    return Err(ExprError {
+                        position: chars(from),
+                        message: String::from("expected a range from a number to a greater one"),
+                    });
+                }
+                (low, high)
+            },
+        };
+
+        Ok(Self { curves, from, to })
+    }
+
+    /// Draws the functions as a PNG image, their names referring to those of
+    /// `scope`.
+    pub fn render(&self, scope: &Scope) -> Result<Vec<u8>, ExprError> {
+        let mut samples = Vec::with_capacity(self.curves.len());
+        for curve in &self.curves {
+            samples.push(self.sample(curve, scope)?);
+        }
+
+        let mut canvas = Canvas::new();
+        let (low, high) = y_range(&samples);
+        let column = |x: f64| PLOT.left as f64 + (x - self.from) / (self.to - self.from) * (PLOT.right - PLOT.left) as f64;
+        let row = |y: f64| PLOT.top as f64 + (high - y) / (high - low) * (PLOT.bottom - PLOT.top) as f64;
+
+        // the grid with its labels
+        let (x_ticks, x_labels) = ticks(self.from, self.to);
+        for (x, label) in x_ticks.into_iter().zip(x_labels) {
+            let x = column(x).round() as i64;
+            canvas.fill(Area { left: x, right: x, ..PLOT }, GRID);
+            let left = x - text_width(&label) / 2;
+            canvas.text(left.max(0).min(IMAGE.right - text_width(&label)), PLOT.bottom + 8, &label, INK);
+        }
+        let (y_ticks, y_labels) = ticks(low, high);
+        for (y, label) in y_ticks.into_iter().zip(y_labels) {
+            let y = row(y).round() as i64;
+            canvas.fill(Area { top: y, bottom: y, ..PLOT }, GRID);
+            canvas.text((PLOT.left - 6 - text_width(&label)).max(0), y - GLYPH_HEIGHT as i64 / 2, &label, INK);
+        }
+        if self.from <= 0.0 && 0.0 <= self.to {
+            let x = column(0.0).round() as i64;
+            canvas.fill(Area { left: x, right: x, ..PLOT }, INK);
+        }
+        if low <= 0.0 && 0.0 <= high {
+            let y = row(0.0).round() as i64;
+            canvas.fill(Area { top: y, bottom: y, ..PLOT }, INK);
+        }
+        canvas.frame(PLOT, INK);
+
+        for (i, values) in samples.iter().enumerate() {
+            let color = INK + 1 + i as u8;
+            // far enough not to be seen, near enough to be drawn quickly
+            let point = |(j, y): (usize, f64)| {
+                let y = row(y).max(-(HEIGHT as f64)).min(2.0 * HEIGHT as f64);
+                (PLOT.left + j as i64, y.round() as i64)
+            };
+            let mut previous: Option<(i64, i64)> = None;
+            for (j, value) in values.iter().enumerate() {
+                let current = value.map(|y| point((j, y)));
+                match (previous, current) {
+                    // an asymptote rather than a steep curve
+                    (Some((_, y0)), Some((_, y1))) if (y0 < PLOT.top && y1 > PLOT.bottom)
+                        || (y1 < PLOT.top && y0 > PLOT.bottom) => {},
+                    (Some(start), Some(end)) => canvas.thick_line(start, end, color),
+                    (None, Some(end)) => canvas.thick_line(end, end, color),
+                    _ => {},
+                }
+                previous = current;
+            }
+        }
+
+        canvas.legend(&self.curves);
+        Ok(png::encode(WIDTH, HEIGHT, &PALETTE, &canvas.pixels))
+    }
+
+    /// The values of the function at each column of the plot, none where it
+    /// is not defined, failing only if it is nowhere.
+    fn sample(&self, curve: &Curve, scope: &Scope) -> Result<Vec<Option<f64>>, ExprError> {
+        let count = (PLOT.right - PLOT.left + 1) as usize;
+        let mut first_error = None;
+        let values: Vec<Option<f64>> = (0..count).map(|i| {
+            let x = self.from + (self.to - self.from) * i as f64 / (count - 1) as f64;
+            match curve.expr.eval_at(scope, VARIABLE, x) {
+                Ok(quantity) => Some(quantity.value).filter(|y| y.is_finite()),
+                Err(why) => {
+                    first_error.get_or_insert(why);
+                    None
+                },
+            }
+        }).collect();
+
+        match first_error {
+            Some(why) if values.iter().all(Option::is_none) => Err(shift(curve.position)(why)),
+            _ => Ok(values),
+        }
+    }
+}
+
+/// The range of values to show, leaving out those near asymptotes.
+fn y_range(samples: &[Vec<Option<f64>>]) -> (f64, f64) {
+    let mut values: Vec<f64> = samples.iter().flatten().filter_map(|y| *y).collect();
+    if values.is_empty() {
+        return (-1.0, 1.0);
+    }
+    values.sort_by(|a, b| a.partial_cmp(b).expect("Expected finite values"));
+    let at = |part: f64| values[((values.len() - 1) as f64 * part).round() as usize];
+
+    let (mut low, mut high) = (values[0], values[values.len() - 1]);
+    // a few values much further than most
+    if high - low > 20.0 * (at(0.95) - at(0.05)) {
+        low = at(0.01);
+        high = at(0.99);
+    }
+    if high - low <= f64::EPSILON * high.abs().max(low.abs()).max(1.0) {
+        return (low - 1.0, high + 1.0);
+    }
+    let margin = (high - low) / 20.0;
+    (low - margin, high + margin)
+}
+
+/// Round values between `low` and `high` to put lines at, with their labels.
+fn ticks(low: f64, high: f64) -> (Vec<f64>, Vec<String>) {
+    let rough = (high - low) / 8.0;
+    let magnitude = 10f64.powf(rough.log10().floor());
+    let step = [1.0, 2.0, 5.0, 10.0].iter()
+        .map(|factor| factor * magnitude)
+        .find(|step| *step >= rough)
+        .unwrap_or(10.0 * magnitude);
+
+    let first = (low / step).ceil() as i64;
+    let last = (high / step).floor() as i64;
+    let values: Vec<f64> = (first..=last).map(|i| i as f64 * step).collect();
+    let labels = values.iter().map(|value| {
+        if !(1e-4..1e6).contains(&step) {
+            format!("{:.1e}", value)
+        } else {
+            let decimals = (-step.log10().floor()).max(0.0) as usize;
+            format!("{:.*}", decimals, value)
+        }
+    }).collect();
+    (values, labels)
+}
+
+/// The width of `text` in pixels.
+fn text_width(text: &str) -> i64 {
+    (text.chars().count() * (GLYPH_WIDTH + 1)) as i64 - 1
+}
+
+/// An image, as the indices of the colors of its pixels in the palette.
+struct Canvas {
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new() -> Self {
+        Self {
+            pixels: vec![BACKGROUND; WIDTH * HEIGHT],
+        }
+    }
+
+    fn set(&mut self, x: i64, y: i64, color: u8) {
+        if IMAGE.contains(x, y) {
+            self.pixels[y as usize * WIDTH + x as usize] = color;
+        }
+    }
+
+    fn fill(&mut self, area: Area, color: u8) {
+        for y in area.top..=area.bottom {
+            for x in area.left..=area.right {
+                self.set(x, y, color);
+            }
+        }
+    }
+
+    fn frame(&mut self, area: Area, color: u8) {
+        self.fill(Area { bottom: area.top, ..area }, color);
+        self.fill(Area { top: area.bottom, ..area }, color);
+        self.fill(Area { right: area.left, ..area }, color);
+        self.fill(Area { left: area.right, ..area }, color);
+    }
+
+    /// Draws a line within the plot.
+    fn line(&mut self, (mut x, mut y): (i64, i64), (x1, y1): (i64, i64), color: u8) {
+        let (dx, dy) = ((x1 - x).abs(), -(y1 - y).abs());
+        let (step_x, step_y) = ((x1 - x).signum(), (y1 - y).signum());
+        let mut error = dx + dy;
+        loop {
+            if PLOT.contains(x, y) {
+                self.set(x, y, color);
+            }
+            if x == x1 && y == y1 {
+                break;
+            }
+            let double = 2 * error;
+            if double >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if double <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+    }
+
+    /// Draws a line two pixels thick, to be seen well.
+    fn thick_line(&mut self, (x0, y0): (i64, i64), (x1, y1): (i64, i64), color: u8) {
+        self.line((x0, y0), (x1, y1), color);
+        self.line((x0, y0 + 1), (x1, y1 + 1), color);
+    }
+
+    /// Writes `text` from its top left corner.
+    fn text(&mut self, x: i64, y: i64, text: &str, color: u8) {
+        for (i, c) in text.chars().enumerate() {
+            let left = x + (i * (GLYPH_WIDTH + 1)) as i64;
+            for (dx, column) in glyph(c).iter().enumerate() {
+                for dy in 0..GLYPH_HEIGHT {
+                    if column >> dy & 1 == 1 {
+                        self.set(left + dx as i64, y + dy as i64, color);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Names the curves with their colors in the top right corner of the plot.
+    fn legend(&mut self, curves: &[Curve]) {
+        let names: Vec<String> = curves.iter().map(|curve| {
+            if curve.source.chars().count() > LEGEND_LENGTH {
+                let start: String = curve.source.chars().take(LEGEND_LENGTH - 3).collect();
+                format!("{}...", start)
+            } else {
+                curve.source.clone()
+            }
+        }).collect();
+        let line_height = GLYPH_HEIGHT as i64 + 5;
+        let width = names.iter().map(|name| text_width(name)).max().unwrap_or(0) + 36;
+        let area = Area {
+            left: PLOT.right - 8 - width,
+            top: PLOT.top + 8,
+            right: PLOT.right - 8,
+            bottom: PLOT.top + 8 + names.len() as i64 * line_height + 5,
+        };
+        self.fill(area, BACKGROUND);
+        self.frame(area, INK);
+
+        for (i, name) in names.iter().enumerate() {
+            let top = area.top + 6 + i as i64 * line_height;
+            let middle = top + GLYPH_HEIGHT as i64 / 2;
+            self.fill(Area { left: area.left + 6, top: middle - 1, right: area.left + 24, bottom: middle }, INK + 1 + i as u8);
+            self.text(area.left + 30, top, name, INK);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_plot(input: &str) -> Result<Plot, ExprError> {
+        Plot::parse(input, &Scope::new())
+    }
+
+    #[test]
+    fn test_parse() {
+        let plot = parse_plot("sin(x)/x from -10 to 10").unwrap();
+        assert_eq!(plot.curves.len(), 1);
+        assert_eq!(plot.curves[0].source, "sin(x)/x");
+        assert_eq!((plot.from, plot.to), (-10.0, 10.0));
+
+        let plot = parse_plot("x^2;  max(x, 1) from -pi to 2*pi").unwrap();
+        let sources: Vec<&str> = plot.curves.iter().map(|curve| curve.source.as_str()).collect();
+        assert_eq!(sources, ["x^2", "max(x, 1)"]);
+        assert_eq!(plot.curves[1].position, 6);
+        assert_eq!((plot.from, plot.to), (-std::f64::consts::PI, 2.0 * std::f64::consts::PI));
+
+        let plot = parse_plot("x").unwrap();
+        assert_eq!((plot.from, plot.to), DEFAULT_RANGE);
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        let error = |input: &str| parse_plot(input).err().unwrap();
+        assert_eq!(error("x; x +"), ExprError { position: 6, message: String::from("expected a number") });
+        assert_eq!(error("x;; x").position, 2);
+        assert_eq!(error("x from 1").message, "expected `to` and the end of the range");
+        assert_eq!(error("x from 2 to 1").position, 2);
+        assert_eq!(error("x from 0 to 2 m").position, 12);
+        assert_eq!(error("x from to 1"), ExprError { position: 7, message: String::from("expected a number") });
+        assert_eq!(error("x; x; x; x; x; x; x").position, 18);
+    }
+
+    #[test]
+    fn test_sample() {
+        let scope = Scope::new();
+        let plot = parse_plot("1/x; y; sqrt(x) from -1 to 1").unwrap();
+        let values = plot.sample(&plot.curves[0], &scope).unwrap();
+        assert_eq!(values.len(), (PLOT.right - PLOT.left + 1) as usize);
+        assert_eq!(values[0], Some(-1.0));
+        // a gap where it is not defined
+        let gaps: Vec<usize> = (0..values.len()).filter(|i| values[*i].is_none()).collect();
+        assert_eq!(gaps, [values.len() / 2]);
+        // nowhere defined
+        assert_eq!(plot.sample(&plot.curves[1], &scope).err().unwrap(),
+                   ExprError { position: 5, message: String::from("unknown variable `y`") });
+        // only on one half
+        let values = plot.sample(&plot.curves[2], &scope).unwrap();
+        assert_eq!(values[0], None);
+        assert_eq!(values[values.len() - 1], Some(1.0));
+    }
+
+    #[test]
+    fn test_ranges_and_ticks() {
+        assert_eq!(y_range(&[vec![Some(0.0), None, Some(10.0)]]), (-0.5, 10.5));
+        assert_eq!(y_range(&[vec![Some(2.0)]]), (1.0, 3.0));
+        let mut asymptote: Vec<Option<f64>> = (0..100).map(|i| Some(i as f64 / 100.0)).collect();
+        asymptote.push(Some(1e9));
+        assert!(y_range(&[asymptote]).1 < 2.0);
+
+        assert_eq!(ticks(-10.0, 10.0), (vec![-10.0, -5.0, 0.0, 5.0, 10.0],
+                                         ["-10", "-5", "0", "5", "10"].iter().map(|s| s.to_string()).collect()));
+        assert_eq!(ticks(0.05, 0.5).1, ["0.1", "0.2", "0.3", "0.4", "0.5"]);
+        assert_eq!(ticks(0.0, 2e7).1[1], "5.0e6");
+    }
+
+    #[test]
+    fn test_render() {
+        let scope = Scope::new();
+        let png = parse_plot("sin(x)/x; tan(x)").unwrap().render(&scope).unwrap();
+        assert!(png.starts_with(&[0x89, b'P', b'N', b'G']));
+
+        let mut canvas = Canvas::new();
+        canvas.line((PLOT.left, PLOT.top), (PLOT.left + 3, PLOT.top + 1), 3);
+        let set: Vec<usize> = (0..5).filter(|x| canvas.pixels[TOP * WIDTH + LEFT + x] == 3).collect();
+        assert_eq!(set, [0, 1]);
+        // not out of the plot
+        canvas.line((0, 0), (3, 0), 3);
+        assert_eq!(canvas.pixels[0], BACKGROUND);
+    }
+}
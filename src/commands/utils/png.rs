@@ -0,0 +1,71 @@
+use std::io::Write;
+
+use flate2::{write::ZlibEncoder, Compression};
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1a, b'\n'];
+
+/// Encodes an image of `width` by `height` pixels, given row after row as the
+/// indices of their colors in `palette`, as a PNG file.
+pub fn encode(width: usize, height: usize, palette: &[[u8; 3]], pixels: &[u8]) -> Vec<u8> {
+    assert_eq!(pixels.len(), width * height, "Expected a pixel per point of the image");
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&(width as u32).to_be_bytes());
+    header.extend_from_slice(&(height as u32).to_be_bytes());
+    // a byte per pixel indexing the palette, the default compression and filters, no interlacing
+    header.extend_from_slice(&[8, 3, 0, 0, 0]);
+
+    // each row starts with its filter, none there
+    let mut rows = Vec::with_capacity(pixels.len() + height);
+    for row in pixels.chunks(width) {
+        rows.push(0);
+        rows.extend_from_slice(row);
+    }
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::best());
+    encoder.write_all(&rows).expect("Writing to memory cannot fail");
+    let data = encoder.finish().expect("Writing to memory cannot fail");
+
+    let mut png = SIGNATURE.to_vec();
+    chunk(&mut png, b"IHDR", &header);
+    chunk(&mut png, b"PLTE", &palette.concat());
+    chunk(&mut png, b"IDAT", &data);
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+/// Appends a chunk of `kind` to `png`.
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    // over the kind and the data
+    let crc = crc32fast::hash(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chunk() {
+        let mut png = Vec::new();
+        chunk(&mut png, b"IEND", &[]);
+        assert_eq!(png, [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]);
+    }
+
+    #[test]
+    fn test_encode() {
+        let palette = [[255, 255, 255], [0, 0, 0]];
+        let png = encode(3, 2, &palette, &[0, 1, 0, 1, 0, 1]);
+        assert_eq!(png[..8], SIGNATURE);
+        // the header first, with the size
+        assert_eq!(png[8..16], [0, 0, 0, 13, b'I', b'H', b'D', b'R']);
+        assert_eq!(png[16..29], [0, 0, 0, 3, 0, 0, 0, 2, 8, 3, 0, 0, 0]);
+        // then the palette
+        assert_eq!(png[33..41], [0, 0, 0, 6, b'P', b'L', b'T', b'E']);
+        assert_eq!(png[41..47], [255, 255, 255, 0, 0, 0]);
+        assert!(png.ends_with(&[0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xae, 0x42, 0x60, 0x82]));
+    }
+}
@@ -0,0 +1,165 @@
+use std::collections::{HashMap, VecDeque};
+
+/// What to do with the tracks someone asks to queue.
+#[derive(Debug, PartialEq)]
+pub struct Admission {
+    /// Indices of the tracks to queue.
+    pub accepted: Vec<usize>,
+    /// Number of tracks refused because they are too long.
+    pub too_long: usize,
+    /// Number of tracks refused because the requester queued too many.
+    pub over_limit: usize,
+}
+
+/// Decides which of the tracks of `lengths`, in milliseconds, a requester who
+/// already has `queued` tracks in the queue may add, `None` limits meaning no
+/// limit.
+pub fn admit(lengths: &[u64], queued: usize, user_limit: Option<usize>, max_length: Option<u64>) -> Admission {
+    let mut admission = Admission {
+        accepted: Vec::new(),
+        too_long: 0,
+        over_limit: 0,
+    };
+    let room = user_limit.map_or(usize::MAX, |limit| limit.saturating_sub(queued));
+
+    for (i, &length) in lengths.iter().enumerate() {
+        if max_length.map_or(false, |max| length > max) {
+            admission.too_long += 1;
+        } else if admission.accepted.len() >= room {
+            admission.over_limit += 1;
+        } else {
+            admission.accepted.push(i);
+        }
+    }
+
+    admission
+}
+
+/// Returns the order in which to play a queue for its requesters to take turns:
+/// the first track of each requester, then their second ones, and so on. The
+/// requesters keep the order of their first track in the queue.
+pub fn fair_order<T: PartialEq>(requesters: &[T]) -> Vec<usize> {
+    // round of each track, the first track of a requester being in round 0
+    let mut rounds: Vec<(usize, usize, usize)> = Vec::with_capacity(requesters.len());
+    let mut firsts: Vec<&T> = Vec::new();
+
+    for (i, requester) in requesters.iter().enumerate() {
+        let rank = match firsts.iter().position(|&first| first == requester) {
+            Some(rank) => rank,
+            None => {
+                firsts.push(requester);
+                firsts.len() - 1
+            },
+        };
+        let round = requesters[..i].iter().filter(|&other| other == requester).count();
+        rounds.push((round, rank, i));
+    }
+
+    rounds.sort();
+    rounds.into_iter().map(|(_, _, i)| i).collect()
+}
+
+/// The requests of the tracks of a queue, by track, a track queued several
+/// times having as many requests, in the order of the queue.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueueRequests<R> {
+    tracks: HashMap<String, VecDeque<R>>,
+}
+
+impl<R> Default for QueueRequests<R> {
+    fn default() -> Self {
+        Self {
+            tracks: HashMap::new(),
+        }
+    }
+}
+
+impl<R: Copy> QueueRequests<R> {
+    /// Adds the request of `track`, queued after the others.
+    pub fn push(&mut self, track: &str, request: R) {
+        self.tracks.entry(track.to_string()).or_default().push_back(request);
+    }
+
+    /// The request of the first `track` of the queue, the one playing if it is.
+    pub fn first(&self, track: &str) -> Option<R> {
+        self.tracks.get(track)?.front().copied()
+    }
+
+    /// Forgets the request of the first `track` of the queue, which left it.
+    pub fn pop(&mut self, track: &str) -> Option<R> {
+        let requests = self.tracks.get_mut(track)?;
+        let request = requests.pop_front();
+        if requests.is_empty() {
+            self.tracks.remove(track);
+        }
+        request
+    }
+
+    /// The requests of the tracks of `queue`, the n-th time a track is queued
+    /// having its n-th request.
+    pub fn of_queue<'a>(&self, queue: impl IntoIterator<Item = &'a str>) -> Vec<Option<R>> {
+        let mut seen: HashMap<&str, usize> = HashMap::new();
+        queue.into_iter()
+            .map(|track| {
+                let n = seen.entry(track).or_insert(0);
+                *n += 1;
+                self.tracks.get(track).and_then(|requests| requests.get(*n - 1)).copied()
+            })
+            .collect()
+    }
+
+    /// Takes the requests of `queue` as those of the tracks it holds, once it
+    /// has been reordered.
+    pub fn reorder<'a>(&mut self, queue: impl IntoIterator<Item = (&'a str, Option<R>)>) {
+        let mut reordered: HashMap<String, VecDeque<R>> = HashMap::new();
+        for (track, request) in queue {
+            reordered.entry(track.to_string()).or_default().extend(request);
+        }
+        self.tracks.extend(reordered.into_iter().filter(|(_, requests)| !requests.is_empty()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_admit() {
+        let lengths = [60_000, 600_000, 120_000, 180_000, 240_000];
+
+        assert_eq!(admit(&lengths, 0, None, None).accepted, vec![0, 1, 2, 3, 4]);
+        assert_eq!(admit(&lengths, 3, Some(5), Some(300_000)), Admission {
+            accepted: vec![0, 2],
+            too_long: 1,
+            over_limit: 2,
+        });
+        assert_eq!(admit(&lengths, 7, Some(5), None).over_limit, 5);
+    }
+
+    #[test]
+    fn test_fair_order() {
+        assert_eq!(fair_order(&['a', 'a', 'a', 'b', 'c', 'b']), vec![0, 3, 4, 1, 5, 2]);
+        assert_eq!(fair_order(&['a', 'b', 'a']), vec![0, 1, 2]);
+        assert!(fair_order::<u64>(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_queue_requests() {
+        let mut requests = QueueRequests::default();
+        requests.push("a", 1);
+        requests.push("b", 2);
+        requests.push("a", 3);
+        assert_eq!(requests.of_queue(vec!["a", "b", "a", "c"]), vec![Some(1), Some(2), Some(3), None]);
+
+        // the second `a` moved before `b`
+        requests.reorder(vec![("a", Some(1)), ("a", Some(3)), ("b", Some(2))]);
+        assert_eq!(requests.of_queue(vec!["a", "a", "b"]), vec![Some(1), Some(3), Some(2)]);
+
+        assert_eq!(requests.first("a"), Some(1));
+        assert_eq!(requests.pop("a"), Some(1));
+        assert_eq!(requests.first("a"), Some(3));
+        assert_eq!(requests.pop("a"), Some(3));
+        assert_eq!(requests.pop("a"), None);
+        assert_eq!(requests, QueueRequests { tracks: vec![(String::from("b"), vec![2].into_iter().collect())].into_iter().collect() });
+    }
+}
@@ -0,0 +1,130 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// How many generators were seeded with the time, so that two seeded at once
+/// still differ.
+static SEEDED: AtomicU64 = AtomicU64::new(0);
+
+/// Spreads the bits of `state`, moving it on, to seed generators.
+fn split_mix(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    let mut z = *state;
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// A xoshiro256** pseudo-random generator, fine for games but not for
+/// secrets.
+#[derive(Clone, Debug)]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    /// A generator always giving the same numbers for the same `seed`.
+    pub fn new(seed: u64) -> Self {
+        let mut seed = seed;
+        Self {
+            state: [split_mix(&mut seed), split_mix(&mut seed), split_mix(&mut seed), split_mix(&mut seed)],
+        }
+    }
+
+    /// A generator seeded with the time.
+    pub fn from_time() -> Self {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_nanos() as u64)
+            .unwrap_or(0);
+        Self::new(nanos ^ SEEDED.fetch_add(1, Ordering::Relaxed).rotate_left(32))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let result = self.state[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.state[1] << 17;
+        self.state[2] ^= self.state[0];
+        self.state[3] ^= self.state[1];
+        self.state[1] ^= self.state[2];
+        self.state[0] ^= self.state[3];
+        self.state[2] ^= t;
+        self.state[3] = self.state[3].rotate_left(45);
+        result
+    }
+
+    /// A number below `n`, all of them being as likely.
+    pub fn below(&mut self, n: u64) -> u64 {
+        assert!(n > 0, "Expected a number to draw below");
+        // leaving out the few first numbers, so that as many are left for each result
+        let rejected = n.wrapping_neg() % n;
+        loop {
+            let x = self.next_u64();
+            if x >= rejected {
+                return x % n;
+            }
+        }
+    }
+
+    /// A number from `low` to `high`, both included.
+    pub fn between(&mut self, low: u64, high: u64) -> u64 {
+        low + self.below(high - low + 1)
+    }
+
+    /// One of `items`, none if there is none.
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> Option<&'a T> {
+        if items.is_empty() {
+            None
+        } else {
+            Some(&items[self.below(items.len() as u64) as usize])
+        }
+    }
+
+    /// Puts `items` in an order as likely as any other.
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.below(i as u64 + 1) as usize;
+            items.swap(i, j);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seeds() {
+        let draws = |seed| {
+            let mut rng = Rng::new(seed);
+            (0..8).map(|_| rng.next_u64()).collect::<Vec<u64>>()
+        };
+        assert_eq!(draws(42), draws(42));
+        assert_ne!(draws(42), draws(43));
+        assert_ne!(Rng::from_time().next_u64(), Rng::from_time().next_u64());
+    }
+
+    #[test]
+    fn test_ranges() {
+        let mut rng = Rng::new(7);
+        let mut counts = [0; 6];
+        for _ in 0..6000 {
+            let x = rng.between(1, 6);
+            assert!((1..=6).contains(&x));
+            counts[x as usize - 1] += 1;
+        }
+        assert!(counts.iter().all(|count| (850..1150).contains(count)), "{:?}", counts);
+        assert_eq!(rng.below(1), 0);
+        assert!(rng.below(u64::MAX) < u64::MAX);
+    }
+
+    #[test]
+    fn test_pick_and_shuffle() {
+        let mut rng = Rng::new(1);
+        assert_eq!(rng.pick::<u8>(&[]), None);
+        assert_eq!(rng.pick(&["only"]), Some(&"only"));
+
+        let mut items: Vec<u32> = (0..50).collect();
+        rng.shuffle(&mut items);
+        assert_ne!(items, (0..50).collect::<Vec<u32>>());
+        items.sort();
+        assert_eq!(items, (0..50).collect::<Vec<u32>>());
+    }
+}
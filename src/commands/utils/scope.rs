@@ -0,0 +1,565 @@
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use super::exact::Rational;
+use super::expr::{parse_statement, Arithmetic, Expr, ExprError, Statement, Target, CONVERT};
+use super::functions::{constant, function};
+use super::integer::{Integer, Width, I64, U32, U64};
+use super::matrix::{Matrix, Operand};
+use super::units::Quantity;
+
+/// Name of the variable holding the previous result.
+pub const ANS: &str = "ans";
+
+/// Ends the functions written in and out of programmer mode when it is not
+/// the mode of their scope, `#` being in no expression.
+const PROGRAMMER_ON: &str = " # programmer";
+const PROGRAMMER_OFF: &str = " # programmer off";
+
+/// A function defined by a user.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UserFunction {
+    pub params: Vec<String>,
+    pub body: Expr,
+    /// The body as the user wrote it.
+    pub source: String,
+    /// Whether the body was written in programmer mode, `^` being an
+    /// exclusive or there.
+    pub programmer: bool,
+}
+
+/// The variables and functions of a user.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Scope {
+    /// The values of the variables, in base units.
+    pub variables: BTreeMap<String, Quantity<Number>>,
+    pub functions: BTreeMap<String, UserFunction>,
+    /// Whether to compute exactly whenever it is possible.
+    pub prefers_exact: bool,
+    /// The integers to compute with in programmer mode, if it is on.
+    pub programmer: Option<Width>,
+}
+
+/// A number, exact if it could be computed so, or a matrix of them.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Number {
+    Float(f64),
+    Exact(Rational),
+    Matrix(Matrix<Number>),
+}
+
+impl Number {
+    pub fn is_matrix(&self) -> bool {
+        matches!(self, Number::Matrix(_))
+    }
+
+    /// The number as expressions read it back, matrices as `[[1, 2], [3, 4]]`.
+    fn source(&self) -> String {
+        match self {
+            Number::Float(value) => value.to_string(),
+            Number::Exact(value) => value.to_string(),
+            Number::Matrix(matrix) => {
+                let rows: Vec<String> = (0..matrix.rows)
+                    .map(|i| format!("[{}]", matrix.row(i).iter().map(Number::source).collect::<Vec<String>>().join(", ")))
+                    .collect();
+                format!("[{}]", rows.join(", "))
+            },
+        }
+    }
+}
+
+/// Matrices are shown a row per line, their columns aligned, the exact
+/// entries without a short decimal form being fractions.
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Number::Float(value) => write!(f, "{}", value),
+            Number::Exact(value) => write!(f, "{}", value.pretty()),
+            Number::Matrix(matrix) => {
+                let cells: Vec<String> = matrix.entries.iter().map(|entry| match entry {
+                    Number::Exact(value) if value.pretty().contains('≈') => value.to_string(),
+                    entry => entry.to_string(),
+                }).collect();
+                let widths: Vec<usize> = (0..matrix.columns)
+                    .map(|j| (0..matrix.rows).map(|i| cells[i * matrix.columns + j].chars().count()).max().unwrap_or(0))
+                    .collect();
+                for i in 0..matrix.rows {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "[")?;
+                    for (j, width) in widths.iter().enumerate() {
+                        let space = if j > 0 { "  " } else { " " };
+                        write!(f, "{}{:>width$}", space, cells[i * matrix.columns + j], width = width)?;
+                    }
+                    write!(f, " ]")?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+/// A result, with the units it is shown in, none for a number.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Value {
+    pub number: Number,
+    pub units: String,
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.units.is_empty() {
+            write!(f, "{}", self.number)
+        } else {
+            write!(f, "{} {}", self.number, self.units)
+        }
+    }
+}
+
+/// What executing a statement did.
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Value(Value),
+    Assigned(String, Value),
+    Defined(String),
+}
+
+impl Scope {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Evaluates an expression, assigns a variable or defines a function, the
+    /// value of the first two becoming `ans`. The values are computed exactly
+    /// if `exact`, see `evaluate`.
+    pub fn execute(&mut self, input: &str, exact: bool) -> Result<Outcome, ExprError> {
+        match parse_statement(input, self.programmer.is_some())? {
+            Statement::Eval(expr, target) => {
+                let (quantity, shown) = self.evaluate(&expr, target.as_ref(), exact)?;
+                self.set(ANS, quantity);
+                Ok(Outcome::Value(shown))
+            },
+            Statement::Assign(name, expr, position) => {
+                check_name(&name, position)?;
+                let (quantity, shown) = self.evaluate(&expr, None, exact)?;
+                self.set(ANS, quantity.clone());
+                self.set(&name, quantity);
+                Ok(Outcome::Assigned(name, shown))
+            },
+            Statement::Define { name, params, body, source, position } => {
+                check_name(&name, position)?;
+                let programmer = self.programmer.is_some();
+                self.functions.insert(name.clone(), UserFunction { params, body, source, programmer });
+                Ok(Outcome::Defined(name))
+            },
+        }
+    }
+
+    /// Evaluates `expr` with the integers of programmer mode if it is on,
+    /// else exactly if `exact`, or else with floats unless the user prefers
+    /// exact values or the expression is written with integers only, and it
+    /// can be computed exactly. Gives the value in base units, and as shown,
+    /// in the `target` units if any.
+    fn evaluate(&self, expr: &Expr, target: Option<&Target>, exact: bool) -> Result<(Quantity<Number>, Value), ExprError> {
+        match self.programmer {
+            Some(Width::U32) => return self.evaluate_with(expr, target, Integer::<U32>::into_number),
+            Some(Width::U64) => return self.evaluate_with(expr, target, Integer::<U64>::into_number),
+            Some(Width::I64) => return self.evaluate_with(expr, target, Integer::<I64>::into_number),
+            None => (),
+        }
+        if exact {
+            return self.evaluate_with(expr, target, exact_number);
+        }
+        if self.prefers_exact || expr.is_integral() {
+            if let Ok(evaluated) = self.evaluate_with(expr, target, exact_number) {
+                return Ok(evaluated);
+            }
+        }
+        self.evaluate_with(expr, target, float_number)
+    }
+
+    fn evaluate_with<N: Arithmetic>(&self, expr: &Expr, target: Option<&Target>, number: fn(N) -> Number)
+        -> Result<(Quantity<Number>, Value), ExprError> {
+        let quantity = expr.eval::<N>(self)?;
+        let shown = match target {
+            Some(target) => Value {
+                number: number(target.convert(quantity.clone())?),
+                units: target.source.clone(),
+            },
+            None => Value {
+                number: number(quantity.value.clone()),
+                units: quantity.dimension.to_string(),
+            },
+        };
+        Ok((Quantity { value: number(quantity.value), dimension: quantity.dimension }, shown))
+    }
+
+    fn set(&mut self, name: &str, quantity: Quantity<Number>) {
+        self.variables.insert(name.to_string(), quantity);
+    }
+
+    /// Forgets the variable or the function `name`, returning whether there
+    /// was one.
+    pub fn forget(&mut self, name: &str) -> bool {
+        let variable = self.variables.remove(name).is_some();
+        let function = self.functions.remove(name).is_some();
+        variable || function
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.variables.is_empty() && self.functions.is_empty()
+    }
+}
+
+fn exact_number(value: Operand<Rational>) -> Number {
+    value.into_number(Number::Exact)
+}
+
+fn float_number(value: Operand<f64>) -> Number {
+    value.into_number(Number::Float)
+}
+
+/// Checks that `name` is not a built-in one, `ans` or `to`.
+fn check_name(name: &str, position: usize) -> Result<(), ExprError> {
+    if name == ANS || name == CONVERT || constant(name).is_some() {
+        Err(ExprError { position, message: format!("`{}` cannot be assigned", name) })
+    } else if function(name).is_some() {
+        Err(ExprError { position, message: format!("`{}` is a built-in function", name) })
+    } else {
+        Ok(())
+    }
+}
+
+/// One `name = value` line per variable, `name = value * units` for those
+/// with units, matrices being written in brackets, and one
+/// `name(params) = body` line per function, followed by `# programmer` or
+/// `# programmer off` for those written in another mode than the current one.
+impl fmt::Display for Scope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, quantity) in &self.variables {
+            write!(f, "{} = {}", name, quantity.value.source())?;
+            if quantity.dimension.is_none() {
+                writeln!(f)?;
+            } else {
+                writeln!(f, " * {}", quantity.dimension)?;
+            }
+        }
+        for (name, function) in &self.functions {
+            write!(f, "{}({}) = {}", name, function.params.join(", "), function.source)?;
+            match (function.programmer, self.programmer.is_some()) {
+                (true, false) => writeln!(f, "{}", PROGRAMMER_ON)?,
+                (false, true) => writeln!(f, "{}", PROGRAMMER_OFF)?,
+                _ => writeln!(f)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The scopes of every user.
+pub struct Scopes {
+    pub users: HashMap<u64, Scope>,
+}
+
+impl Scopes {
+    pub fn new() -> Self {
+        Self {
+            users: HashMap::new(),
+        }
+    }
+
+    pub fn get_mut(&mut self, user_id: u64) -> &mut Scope {
+        self.users.entry(user_id).or_default()
+    }
+}
+
+/// One `user=..` line per user, followed by ` exact` if they prefer exact
+/// values and by their integer type in programmer mode, as ` u32`, and by the
+/// lines of their scope starting with a tab.
+impl fmt::Display for Scopes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut users: Vec<&u64> = self.users.keys().collect();
+        users.sort();
+
+        for user_id in users {
+            let scope = &self.users[user_id];
+            write!(f, "user={}", user_id)?;
+            if scope.prefers_exact {
+                write!(f, " exact")?;
+            }
+            if let Some(width) = scope.programmer {
+                write!(f, " {}", width)?;
+            }
+            writeln!(f)?;
+            for line in scope.to_string().lines() {
+                writeln!(f, "\t{}", line)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Scopes {
+    /// Reads the scopes of the users, skipping the invalid lines, and the
+    /// definitions of an invalid user, which are given back with why.
+    pub fn read(s: &str) -> (Self, Vec<String>) {
+        let mut scopes = Scopes::new();
+        let mut errors = Vec::new();
+        let mut user = Ok(None);
+
+        for line in s.lines().filter(|line| !line.trim().is_empty()) {
+            if let Some(definition) = line.strip_prefix('\t') {
+                match user {
+                    Ok(Some(user_id)) => {
+                        if let Err(why) = scopes.get_mut(user_id).read_definition(definition) {
+                            errors.push(why);
+                        }
+                    },
+                    Ok(None) => errors.push(format!("Definition `{}` found before any user", definition)),
+                    // already reported with the user
+                    Err(()) => (),
+                }
+            } else {
+                user = match scopes.read_user(line) {
+                    Ok(user_id) => Ok(Some(user_id)),
+                    Err(why) => {
+                        errors.push(why);
+                        Err(())
+                    },
+                };
+            }
+        }
+
+        (scopes, errors)
+    }
+
+    /// Reads a `user=..` line, returning the id of the user.
+    fn read_user(&mut self, line: &str) -> Result<u64, String> {
+        let invalid = || format!("Invalid user `{}`", line);
+        let mut words = line.trim().strip_prefix("user=").ok_or_else(invalid)?.split(' ');
+        let user_id = words.next().and_then(|id| id.parse().ok()).ok_or_else(invalid)?;
+        let (mut prefers_exact, mut programmer) = (false, None);
+        for word in words {
+            match word {
+                "exact" => prefers_exact = true,
+                width => programmer = Some(width.parse().map_err(|_| invalid())?),
+            }
+        }
+        let scope = self.get_mut(user_id);
+        scope.prefers_exact = prefers_exact;
+        scope.programmer = programmer;
+        Ok(user_id)
+    }
+}
+
+impl Scope {
+    /// Restores a variable or a function as is, `ans` included, the exact
+    /// values being written with integers only, and the functions read in
+    /// the mode they were written in.
+    fn read_definition(&mut self, definition: &str) -> Result<(), String> {
+        let invalid = |why: ExprError| format!("Invalid definition `{}`: {}", definition, why);
+        let (statement, programmer) = if let Some(statement) = definition.strip_suffix(PROGRAMMER_ON) {
+            (statement, true)
+        } else if let Some(statement) = definition.strip_suffix(PROGRAMMER_OFF) {
+            (statement, false)
+        } else {
+            (definition, self.programmer.is_some())
+        };
+        // the variables are written out of programmer mode, their units as `m^2`
+        let parsed = match parse_statement(statement, programmer).map_err(invalid)? {
+            Statement::Assign(..) if programmer => parse_statement(statement, false).map_err(invalid)?,
+            parsed => parsed,
+        };
+        match parsed {
+            Statement::Assign(name, expr, _) => {
+                let quantity = Scope::new().evaluate(&expr, None, false).map_err(invalid)?.0;
+                self.set(&name, quantity);
+            },
+            Statement::Define { name, params, body, source, .. } => {
+                self.functions.insert(name, UserFunction { params, body, source, programmer });
+            },
+            Statement::Eval(..) => return Err(format!("Invalid definition `{}`", definition)),
+        }
+        Ok(())
+    }
+}
+
+/// Fails on the first invalid line, see `Scopes::read` to skip them instead.
+impl FromStr for Scopes {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (scopes, mut errors) = Scopes::read(s);
+        if errors.is_empty() {
+            Ok(scopes)
+        } else {
+            Err(errors.remove(0))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Executes `input`, showing its value.
+    fn run(scope: &mut Scope, input: &str, exact: bool) -> String {
+        match scope.execute(input, exact) {
+            Ok(Outcome::Value(value)) | Ok(Outcome::Assigned(_, value)) => value.to_string(),
+            Ok(Outcome::Defined(name)) => name,
+            Err(why) => why.message,
+        }
+    }
+
+    #[test]
+    fn test_execute() {
+        let mut scope = Scope::new();
+        let value = Value { number: Number::Float(3.5), units: String::new() };
+        assert_eq!(scope.execute("x = 3.5", false), Ok(Outcome::Assigned(String::from("x"), value)));
+        assert_eq!(scope.execute("f(a, b) = a^2 + b", false), Ok(Outcome::Defined(String::from("f"))));
+        assert_eq!(run(&mut scope, "f(2, x)", false), "7.5");
+        assert_eq!(run(&mut scope, "ans * 2", false), "15");
+        assert_eq!(run(&mut scope, "g(n) = n * f(n, 0)", false), "g");
+        assert_eq!(run(&mut scope, "g(3)", false), "27");
+        assert_eq!(run(&mut scope, "f(1)", false), "`f` takes 2 arguments");
+    }
+
+    #[test]
+    fn test_exact() {
+        let mut scope = Scope::new();
+        assert_eq!(run(&mut scope, "0.1 + 0.2", false), "0.30000000000000004");
+        assert_eq!(run(&mut scope, "0.1 + 0.2", true), "0.3");
+        assert_eq!(run(&mut scope, "2^64", false), "18446744073709551616");
+        assert_eq!(run(&mut scope, "x = 1/3", false), "1/3 ≈ 0.333333333333333333333333333333");
+        assert_eq!(run(&mut scope, "x * 3", false), "1");
+        assert_eq!(run(&mut scope, "sqrt(16)", false), "4");
+        assert_eq!(run(&mut scope, "sqrt(16)", true), "`sqrt` has no exact value");
+        assert_eq!(run(&mut scope, "1 / 0", false), "division by zero");
+        assert_eq!(run(&mut scope, "0^-1", false), "division by zero");
+
+        scope.prefers_exact = true;
+        assert_eq!(run(&mut scope, "0.1 + 0.2", false), "0.3");
+        assert_eq!(run(&mut scope, "cos(pi)", false), "-1");
+    }
+
+    #[test]
+    fn test_units() {
+        let mut scope = Scope::new();
+        assert_eq!(run(&mut scope, "d = 42.195 km", false), "42195 m");
+        assert_eq!(run(&mut scope, "d / (2.5 h) to km/h", false), "16.878 km/h");
+        assert_eq!(run(&mut scope, "ans * 1 h to km", false), "16.878 km");
+        assert_eq!(run(&mut scope, "d / (2 h + 1 min) to km/h", false), "20.923140495867766 km/h");
+        assert_eq!(run(&mut scope, "42195 m / (2 h + 1 min) to km/h", false), "25317/1210 ≈ 20.923140495867768595041322314049 km/h");
+        assert_eq!(run(&mut scope, "100 °C to F", false), "212 F");
+        assert_eq!(run(&mut scope, "5 km/h to m/s", true), "25/18 ≈ 1.388888888888888888888888888888 m/s");
+        assert_eq!(run(&mut scope, "d to kg", false), "cannot convert `m` to `kg`");
+        assert!(scope.execute("to = 2", false).is_err());
+    }
+
+    #[test]
+    fn test_matrices() {
+        let mut scope = Scope::new();
+        assert_eq!(run(&mut scope, "A = [[2, 1], [1, 3]]", false), "[ 2  1 ]\n[ 1  3 ]");
+        assert_eq!(run(&mut scope, "A * [1, -10]", false), "[  -8 ]\n[ -29 ]");
+        assert_eq!(run(&mut scope, "inv(A)", false), "[  0.6  -0.2 ]\n[ -0.2   0.4 ]");
+        assert_eq!(run(&mut scope, "A^-1 * 3", false), "[  1.8  -0.6 ]\n[ -0.6   1.2 ]");
+        assert_eq!(run(&mut scope, "[[1, 2]] / 3", false), "[ 1/3  2/3 ]");
+        assert_eq!(run(&mut scope, "[[0.5, 1]] * 2", false), "[ 1  2 ]");
+        assert_eq!(run(&mut scope, "solve(A, [3, 5])", false), "[ 0.8 ]\n[ 1.4 ]");
+        assert_eq!(run(&mut scope, "det(A) + transpose([1, 2]) * [3, 4]", false), "16");
+        assert_eq!(run(&mut scope, "[[1 m, 2 m]] to cm", false), "[ 100  200 ] cm");
+        assert_eq!(run(&mut scope, "[1, 2 m]", false), "entries of a matrix must have the same units");
+        assert_eq!(run(&mut scope, "[[1, 2], [3]]", false), "expected rows of the same length");
+        assert_eq!(run(&mut scope, "sqrt(A)", false), "`sqrt` takes numbers, not matrices");
+        assert_eq!(run(&mut scope, "inv([[1, 2], [2, 4]])", false), "the matrix is not invertible");
+    }
+
+    #[test]
+    fn test_programmer() {
+        let mut scope = Scope::new();
+        assert_eq!(run(&mut scope, "0xff & 0b1010 | 1 << 4", false), "26");
+        assert_eq!(run(&mut scope, "2^10", false), "1024");
+        assert_eq!(run(&mut scope, "~5", false), "-6");
+        assert_eq!(run(&mut scope, "1.5 | 1", false), "bitwise operations take integers");
+        assert_eq!(run(&mut scope, "1 m << 1", false), "bitwise operations take numbers without units");
+
+        scope.programmer = Some(Width::U32);
+        assert_eq!(run(&mut scope, "x = 2^10", false), "8");
+        assert_eq!(run(&mut scope, "x ** 4 - 1", false), "4095");
+        assert_eq!(run(&mut scope, "ans + 0xfffff001", false), "0");
+        assert_eq!(run(&mut scope, "~0", false), "4294967295");
+        assert_eq!(run(&mut scope, "pi", false), "`pi` is not an integer");
+        scope.programmer = Some(Width::I64);
+        assert_eq!(run(&mut scope, "~0", false), "-1");
+    }
+
+    #[test]
+    fn test_invalid_names() {
+        let mut scope = Scope::new();
+        assert!(scope.execute("pi = 3", false).is_err());
+        assert!(scope.execute("ans = 3", false).is_err());
+        assert!(scope.execute("sqrt(x) = x", false).is_err());
+        assert!(scope.is_empty());
+    }
+
+    #[test]
+    fn test_recursion() {
+        let mut scope = Scope::new();
+        scope.execute("loop(n) = loop(n + 1)", false).unwrap();
+        assert_eq!(run(&mut scope, "loop(0)", false), "in `loop`: too many nested calls");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let mut scopes = Scopes::new();
+        let scope = scopes.get_mut(42);
+        scope.execute("x = -1 / 3", false).unwrap();
+        scope.execute("y = 0.1 + 0.2", false).unwrap();
+        scope.execute("speed = 3 km / 1 h", false).unwrap();
+        scope.execute("area = 1/3 m^2", false).unwrap();
+        scope.execute("f(a) = a * x + 1", false).unwrap();
+        scope.execute("f(3)", false).unwrap();
+        scope.execute("g(a) = a +\n 1", false).unwrap();
+        scope.execute("M = [[1/3, 1/2], [-2, 4]]", false).unwrap();
+        scope.execute("v = [0.1 + 0.2, 1] * 1 s", false).unwrap();
+        scopes.get_mut(7).prefers_exact = true;
+        let programmer = scopes.get_mut(9);
+        programmer.programmer = Some(Width::U32);
+        programmer.execute("mask(n) = n ^ 0xff", false).unwrap();
+
+        let parsed = Scopes::from_str(&scopes.to_string()).unwrap();
+        assert_eq!(parsed.users, scopes.users);
+        let variables = &parsed.users[&42].variables;
+        assert_eq!(variables["y"].value, Number::Float(0.1 + 0.2));
+        assert_eq!(variables["area"].value.to_string(), "1/3 ≈ 0.333333333333333333333333333333");
+        assert!(!variables["speed"].dimension.is_none());
+        assert_eq!(variables["M"].value.to_string(), "[ 1/3  0.5 ]\n[  -2    4 ]");
+        assert!(variables["v"].value.is_matrix());
+        assert!(Scopes::from_str("\tx = 1").is_err());
+        assert!(Scopes::from_str("user=1\n\t1 + 1").is_err());
+        assert!(Scopes::from_str("user=1 fast").is_err());
+        let (read, errors) = Scopes::read("user=1\n\tx = 1\n\t1 +\n\ty = 2\nuser=2 fast\n\tz = 3\nuser=3\n\tw = 4");
+        assert_eq!(errors.len(), 2);
+        assert_eq!(read.users[&1].variables.keys().collect::<Vec<&String>>(), vec!["x", "y"]);
+        assert!(!read.users.contains_key(&2));
+        assert!(read.users[&3].variables.contains_key("w"));
+        let programmer = &mut Scopes::from_str(&scopes.to_string()).unwrap().users[&9].clone();
+        assert_eq!(programmer.programmer, Some(Width::U32));
+        assert_eq!(run(programmer, "mask(0x0f)", false), "240");
+
+        // functions keep the meaning of `^` they were written with
+        let scope = scopes.get_mut(42);
+        scope.execute("square(n) = n ^ 2", false).unwrap();
+        scope.programmer = Some(Width::U32);
+        assert!(scope.to_string().contains("square(n) = n ^ 2 # programmer off"));
+        let programmer = scopes.get_mut(9);
+        programmer.programmer = None;
+        assert!(programmer.to_string().contains("mask(n) = n ^ 0xff # programmer\n"));
+        let mut parsed = Scopes::from_str(&scopes.to_string()).unwrap();
+        assert_eq!(parsed.users, scopes.users);
+        assert_eq!(run(parsed.get_mut(42), "square(3)", false), "9");
+        assert_eq!(run(parsed.get_mut(9), "mask(0x0f)", false), "240");
+    }
+}
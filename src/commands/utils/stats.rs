@@ -0,0 +1,145 @@
+use std::fmt;
+
+/// How many modes are shown at most.
+const MAX_MODES: usize = 5;
+
+/// Statistics of a list of numbers, the variance being the one of a sample.
+#[derive(Debug, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub sum: f64,
+    pub mean: f64,
+    pub median: f64,
+    /// The values found most often, with how often, none if all are unique.
+    pub modes: (Vec<f64>, usize),
+    pub variance: f64,
+    pub std_dev: f64,
+    pub min: f64,
+    pub max: f64,
+    pub quartiles: (f64, f64),
+}
+
+/// The value a part `p` of the way through `sorted`, interpolated between
+/// the nearest ones.
+fn quantile(sorted: &[f64], p: f64) -> f64 {
+    let rank = (sorted.len() - 1) as f64 * p;
+    let (below, above) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[below] + (sorted[above] - sorted[below]) * (rank - below as f64)
+}
+
+impl Summary {
+    /// Summarizes `values`, failing if there is none or one is not finite.
+    pub fn new(values: &[f64]) -> Result<Self, String> {
+        if values.is_empty() {
+            return Err(String::from("no numbers"));
+        }
+        if let Some(value) = values.iter().find(|value| !value.is_finite()) {
+            return Err(format!("`{}` is not a finite number", value));
+        }
+        let mut sorted = values.to_vec();
+        sorted.sort_by(|a, b| a.partial_cmp(b).expect("Expected finite values"));
+
+        let count = values.len();
+        let sum: f64 = values.iter().sum();
+        let mean = sum / count as f64;
+        let variance = if count > 1 {
+            values.iter().map(|value| (value - mean).powi(2)).sum::<f64>() / (count - 1) as f64
+        } else {
+            0.0
+        };
+
+        // the runs of equal values
+        let mut runs: Vec<(f64, usize)> = Vec::new();
+        for value in &sorted {
+            match runs.last_mut() {
+                Some((last, run)) if last == value => *run += 1,
+                _ => runs.push((*value, 1)),
+            }
+        }
+        let longest = runs.iter().map(|(_, run)| *run).max().unwrap_or(0);
+        let modes = if longest > 1 {
+            (runs.iter().filter(|(_, run)| *run == longest).map(|(value, _)| *value).collect(), longest)
+        } else {
+            (Vec::new(), 1)
+        };
+
+        Ok(Self {
+            count,
+            sum,
+            mean,
+            median: quantile(&sorted, 0.5),
+            modes,
+            variance,
+            std_dev: variance.sqrt(),
+            min: sorted[0],
+            max: sorted[count - 1],
+            quartiles: (quantile(&sorted, 0.25), quantile(&sorted, 0.75)),
+        })
+    }
+}
+
+/// A line per statistic, aligned.
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let modes = match &self.modes {
+            (modes, _) if modes.is_empty() => String::from("none"),
+            (modes, count) => {
+                let shown: Vec<String> = modes.iter().take(MAX_MODES).map(|value| value.to_string()).collect();
+                let more = if modes.len() > MAX_MODES { ", ..." } else { "" };
+                format!("{}{} ({} times)", shown.join(", "), more, count)
+            },
+        };
+        writeln!(f, "count     {}", self.count)?;
+        writeln!(f, "sum       {}", self.sum)?;
+        writeln!(f, "mean      {}", self.mean)?;
+        writeln!(f, "median    {}", self.median)?;
+        writeln!(f, "mode      {}", modes)?;
+        writeln!(f, "variance  {}", self.variance)?;
+        writeln!(f, "std dev   {}", self.std_dev)?;
+        writeln!(f, "min       {}", self.min)?;
+        writeln!(f, "Q1        {}", self.quartiles.0)?;
+        writeln!(f, "Q3        {}", self.quartiles.1)?;
+        write!(f, "max       {}", self.max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary() {
+        let summary = Summary::new(&[4.0, 1.0, 3.0, 2.0]).unwrap();
+        assert_eq!(summary.count, 4);
+        assert_eq!(summary.sum, 10.0);
+        assert_eq!(summary.mean, 2.5);
+        assert_eq!(summary.median, 2.5);
+        assert_eq!(summary.modes, (vec![], 1));
+        assert!((summary.variance - 5.0 / 3.0).abs() < 1e-12);
+        assert_eq!((summary.min, summary.max), (1.0, 4.0));
+        assert_eq!(summary.quartiles, (1.75, 3.25));
+
+        let summary = Summary::new(&[2.0, 7.0, 2.0, 7.0, 5.0]).unwrap();
+        assert_eq!(summary.median, 5.0);
+        assert_eq!(summary.modes, (vec![2.0, 7.0], 2));
+        assert_eq!(summary.quartiles, (2.0, 7.0));
+
+        let summary = Summary::new(&[-3.5]).unwrap();
+        assert_eq!((summary.variance, summary.median, summary.quartiles), (0.0, -3.5, (-3.5, -3.5)));
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(Summary::new(&[]), Err(String::from("no numbers")));
+        assert_eq!(Summary::new(&[1.0, f64::INFINITY]), Err(String::from("`inf` is not a finite number")));
+    }
+
+    #[test]
+    fn test_display() {
+        let summary = Summary::new(&[1.0, 1.0, 3.0]).unwrap();
+        let lines: Vec<String> = summary.to_string().lines().map(String::from).collect();
+        assert_eq!(lines.len(), 11);
+        assert_eq!(lines[4], "mode      1 (2 times)");
+        assert_eq!(lines[10], "max       3");
+    }
+}
@@ -0,0 +1,441 @@
+use std::fmt;
+
+use super::expr::{Arithmetic, Op};
+use super::functions::Function;
+
+/// Number of base dimensions, which are those of `BASE_UNITS`.
+const DIMENSIONS: usize = 8;
+
+/// The units of the base dimensions, in the order they are written.
+const BASE_UNITS: [&str; DIMENSIONS] = ["kg", "m", "s", "A", "K", "mol", "cd", "B"];
+
+const MASS: usize = 0;
+const LENGTH: usize = 1;
+const TIME: usize = 2;
+const CURRENT: usize = 3;
+const TEMPERATURE: usize = 4;
+const AMOUNT: usize = 5;
+const LUMINOSITY: usize = 6;
+const INFORMATION: usize = 7;
+
+/// Powers of the base dimensions of a quantity, none for a number.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Dimension([i8; DIMENSIONS]);
+
+impl Dimension {
+    /// The dimension with the given powers of base dimensions.
+    const fn of(powers: &[(usize, i8)]) -> Self {
+        let mut dimension = [0; DIMENSIONS];
+        let mut i = 0;
+        while i < powers.len() {
+            dimension[powers[i].0] = powers[i].1;
+            i += 1;
+        }
+        Dimension(dimension)
+    }
+
+    pub fn is_none(&self) -> bool {
+        self.0.iter().all(|&power| power == 0)
+    }
+
+    fn combine(&self, other: &Self, sign: i8) -> Option<Self> {
+        let mut dimension = self.0;
+        for (power, other) in dimension.iter_mut().zip(&other.0) {
+            *power = power.checked_add(other.checked_mul(sign)?)?;
+        }
+        Some(Dimension(dimension))
+    }
+
+    fn pow(&self, exponent: i64) -> Option<Self> {
+        let mut dimension = self.0;
+        for power in dimension.iter_mut() {
+            let raised = *power as i64 * exponent;
+            if raised < i8::MIN as i64 || raised > i8::MAX as i64 {
+                return None;
+            }
+            *power = raised as i8;
+        }
+        Some(Dimension(dimension))
+    }
+
+    /// The `n`-th root, if the powers are all multiples of `n`.
+    fn root(&self, n: i8) -> Option<Self> {
+        if self.0.iter().any(|power| power % n != 0) {
+            return None;
+        }
+        let mut dimension = self.0;
+        for power in dimension.iter_mut() {
+            *power /= n;
+        }
+        Some(Dimension(dimension))
+    }
+
+    /// The dimension in backticks, for errors.
+    fn describe(&self) -> String {
+        if self.is_none() {
+            String::from("no unit")
+        } else {
+            format!("`{}`", self)
+        }
+    }
+}
+
+/// In base units, as `kg*m^2/s^2`, which expressions read back.
+impl fmt::Display for Dimension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let positive = self.0.iter().any(|&power| power > 0);
+        let mut first = true;
+        for (unit, &power) in BASE_UNITS.iter().zip(&self.0) {
+            // with only negative powers, they are written as such
+            if power == 0 || (power < 0 && positive) {
+                continue;
+            }
+            if !first {
+                write!(f, "*")?;
+            }
+            first = false;
+            write!(f, "{}", unit)?;
+            if power != 1 {
+                write!(f, "^{}", power)?;
+            }
+        }
+        if positive {
+            for (unit, &power) in BASE_UNITS.iter().zip(&self.0) {
+                match power {
+                    -1 => write!(f, "/{}", unit)?,
+                    power if power < 0 => write!(f, "/{}^{}", unit, -power)?,
+                    _ => (),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A number with a dimension, its value being in base units.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Quantity<N> {
+    pub value: N,
+    pub dimension: Dimension,
+}
+
+impl<N: Arithmetic> Quantity<N> {
+    pub fn number(value: N) -> Self {
+        Self {
+            value,
+            dimension: Dimension::default(),
+        }
+    }
+
+    pub fn negate(self) -> Self {
+        Self {
+            value: self.value.negate(),
+            dimension: self.dimension,
+        }
+    }
+
+    pub fn binary(op: Op, left: Self, right: Self) -> Result<Self, String> {
+        let dimension = match op {
+            Op::Add | Op::Sub | Op::Rem if left.dimension != right.dimension => {
+                return Err(format!("incompatible units, {} and {}", left.dimension.describe(), right.dimension.describe()));
+            },
+            Op::Add | Op::Sub | Op::Rem => left.dimension,
+            Op::Mul => left.dimension.combine(&right.dimension, 1).ok_or_else(too_large)?,
+            Op::Div => left.dimension.combine(&right.dimension, -1).ok_or_else(too_large)?,
+            Op::Pow if !right.dimension.is_none() => return Err(String::from("an exponent cannot have units")),
+            Op::Pow if left.dimension.is_none() => left.dimension,
+            Op::Pow => match right.value.to_integer() {
+                Some(exponent) => left.dimension.pow(exponent).ok_or_else(too_large)?,
+                None => return Err(String::from("units can only be raised to integer powers")),
+            },
+            _ if !left.dimension.is_none() || !right.dimension.is_none() => {
+                return Err(String::from("bitwise operations take numbers without units"));
+            },
+            _ => left.dimension,
+        };
+        Ok(Self {
+            value: N::binary(op, left.value, right.value)?,
+            dimension,
+        })
+    }
+
+    /// Calls a built-in function, those not simply rounding or comparing
+    /// quantities taking numbers only, but for `sqrt`.
+    pub fn call(function: &Function, args: Vec<Self>) -> Result<Self, String> {
+        let dimension = args[0].dimension;
+        let dimension = match function.name {
+            "abs" | "floor" | "ceil" | "round" | "min" | "max" => {
+                if let Some(other) = args.iter().find(|arg| arg.dimension != dimension) {
+                    return Err(format!("incompatible units, {} and {}", dimension.describe(), other.dimension.describe()));
+                }
+                dimension
+            },
+            "sqrt" => dimension.root(2)
+                .ok_or_else(|| format!("no square root of {}", dimension.describe()))?,
+            "transpose" => dimension,
+            "inv" => Dimension::default().combine(&dimension, -1).ok_or_else(too_large)?,
+            "solve" => args[1].dimension.combine(&dimension, -1).ok_or_else(too_large)?,
+            name if args.iter().any(|arg| !arg.dimension.is_none()) => {
+                return Err(format!("`{}` takes numbers without units", name));
+            },
+            _ => dimension,
+        };
+        let values: Vec<N> = args.into_iter().map(|arg| arg.value).collect();
+        Ok(Self {
+            value: N::call(function, &values)?,
+            dimension,
+        })
+    }
+
+    pub fn check(&self) -> Result<(), String> {
+        self.value.check()
+    }
+}
+
+fn too_large() -> String {
+    String::from("result too large")
+}
+
+/// Which prefixes a unit takes.
+#[derive(Clone, Copy, PartialEq)]
+enum Prefixes {
+    None,
+    Si,
+    /// Both the SI and the binary ones.
+    Binary,
+}
+
+/// A unit, worth `factor` base units, written as a decimal number or a
+/// fraction of two.
+pub struct Unit {
+    pub names: &'static [&'static str],
+    /// What it measures, to group units in the help.
+    pub kind: &'static str,
+    factor: &'static str,
+    /// For temperatures not starting at the absolute zero, what to add to get
+    /// the value from there.
+    offset: Option<&'static str>,
+    dimension: Dimension,
+    prefixes: Prefixes,
+}
+
+const SI_PREFIXES: &[(&str, &str)] = &[
+    ("Y", "1e24"), ("Z", "1e21"), ("E", "1e18"), ("P", "1e15"), ("T", "1e12"), ("G", "1e9"),
+    ("M", "1e6"), ("k", "1e3"), ("h", "1e2"), ("da", "1e1"), ("d", "1e-1"), ("c", "1e-2"),
+    ("m", "1e-3"), ("u", "1e-6"), ("µ", "1e-6"), ("n", "1e-9"), ("p", "1e-12"), ("f", "1e-15"),
+    ("a", "1e-18"),
+];
+
+const BINARY_PREFIXES: &[(&str, &str)] = &[
+    ("Ki", "1024"), ("Mi", "1048576"), ("Gi", "1073741824"), ("Ti", "1099511627776"),
+    ("Pi", "1125899906842624"), ("Ei", "1152921504606846976"),
+];
+
+const NONE: Dimension = Dimension::of(&[]);
+const MASS_UNIT: Dimension = Dimension::of(&[(MASS, 1)]);
+const LENGTH_UNIT: Dimension = Dimension::of(&[(LENGTH, 1)]);
+const TIME_UNIT: Dimension = Dimension::of(&[(TIME, 1)]);
+const TEMPERATURE_UNIT: Dimension = Dimension::of(&[(TEMPERATURE, 1)]);
+const INFORMATION_UNIT: Dimension = Dimension::of(&[(INFORMATION, 1)]);
+const AREA: Dimension = Dimension::of(&[(LENGTH, 2)]);
+const VOLUME: Dimension = Dimension::of(&[(LENGTH, 3)]);
+const SPEED: Dimension = Dimension::of(&[(LENGTH, 1), (TIME, -1)]);
+const ENERGY: Dimension = Dimension::of(&[(MASS, 1), (LENGTH, 2), (TIME, -2)]);
+const PRESSURE: Dimension = Dimension::of(&[(MASS, 1), (LENGTH, -1), (TIME, -2)]);
+
+/// The units of expressions. To add one, add it here, a name being looked up
+/// as is before being looked up as a prefix followed by a unit.
+pub const UNITS: &[Unit] = &[
+    Unit { names: &["m"], kind: "length", factor: "1", offset: None, dimension: LENGTH_UNIT, prefixes: Prefixes::Si },
+    Unit { names: &["in", "inch"], kind: "length", factor: "0.0254", offset: None, dimension: LENGTH_UNIT, prefixes: Prefixes::None },
+    Unit { names: &["ft", "foot"], kind: "length", factor: "0.3048", offset: None, dimension: LENGTH_UNIT, prefixes: Prefixes::None },
+    Unit { names: &["yd"], kind: "length", factor: "0.9144", offset: None, dimension: LENGTH_UNIT, prefixes: Prefixes::None },
+    Unit { names: &["mi", "mile"], kind: "length", factor: "1609.344", offset: None, dimension: LENGTH_UNIT, prefixes: Prefixes::None },
+    Unit { names: &["nmi"], kind: "length", factor: "1852", offset: None, dimension: LENGTH_UNIT, prefixes: Prefixes::None },
+    Unit { names: &["au"], kind: "length", factor: "149597870700", offset: None, dimension: LENGTH_UNIT, prefixes: Prefixes::None },
+    Unit { names: &["ly"], kind: "length", factor: "9460730472580800", offset: None, dimension: LENGTH_UNIT, prefixes: Prefixes::None },
+    Unit { names: &["g"], kind: "mass", factor: "0.001", offset: None, dimension: MASS_UNIT, prefixes: Prefixes::Si },
+    Unit { names: &["t", "tonne"], kind: "mass", factor: "1000", offset: None, dimension: MASS_UNIT, prefixes: Prefixes::None },
+    Unit { names: &["lb"], kind: "mass", factor: "0.45359237", offset: None, dimension: MASS_UNIT, prefixes: Prefixes::None },
+    Unit { names: &["oz"], kind: "mass", factor: "0.028349523125", offset: None, dimension: MASS_UNIT, prefixes: Prefixes::None },
+    Unit { names: &["s"], kind: "time", factor: "1", offset: None, dimension: TIME_UNIT, prefixes: Prefixes::Si },
+    Unit { names: &["min"], kind: "time", factor: "60", offset: None, dimension: TIME_UNIT, prefixes: Prefixes::None },
+    Unit { names: &["h", "hour"], kind: "time", factor: "3600", offset: None, dimension: TIME_UNIT, prefixes: Prefixes::None },
+    Unit { names: &["day"], kind: "time", factor: "86400", offset: None, dimension: TIME_UNIT, prefixes: Prefixes::None },
+    Unit { names: &["week"], kind: "time", factor: "604800", offset: None, dimension: TIME_UNIT, prefixes: Prefixes::None },
+    Unit { names: &["yr", "year"], kind: "time", factor: "31557600", offset: None, dimension: TIME_UNIT, prefixes: Prefixes::None },
+    Unit { names: &["Hz"], kind: "frequency", factor: "1", offset: None, dimension: Dimension::of(&[(TIME, -1)]), prefixes: Prefixes::Si },
+    Unit { names: &["ha"], kind: "area", factor: "10000", offset: None, dimension: AREA, prefixes: Prefixes::None },
+    Unit { names: &["acre"], kind: "area", factor: "4046.8564224", offset: None, dimension: AREA, prefixes: Prefixes::None },
+    Unit { names: &["L", "l"], kind: "volume", factor: "0.001", offset: None, dimension: VOLUME, prefixes: Prefixes::Si },
+    Unit { names: &["gal"], kind: "volume", factor: "0.003785411784", offset: None, dimension: VOLUME, prefixes: Prefixes::None },
+    Unit { names: &["mph"], kind: "speed", factor: "0.44704", offset: None, dimension: SPEED, prefixes: Prefixes::None },
+    Unit { names: &["kn", "knot"], kind: "speed", factor: "1852/3600", offset: None, dimension: SPEED, prefixes: Prefixes::None },
+    Unit { names: &["N"], kind: "force", factor: "1", offset: None, dimension: Dimension::of(&[(MASS, 1), (LENGTH, 1), (TIME, -2)]), prefixes: Prefixes::Si },
+    Unit { names: &["J"], kind: "energy", factor: "1", offset: None, dimension: ENERGY, prefixes: Prefixes::Si },
+    Unit { names: &["Wh"], kind: "energy", factor: "3600", offset: None, dimension: ENERGY, prefixes: Prefixes::Si },
+    Unit { names: &["cal"], kind: "energy", factor: "4.184", offset: None, dimension: ENERGY, prefixes: Prefixes::Si },
+    Unit { names: &["eV"], kind: "energy", factor: "1.602176634e-19", offset: None, dimension: ENERGY, prefixes: Prefixes::Si },
+    Unit { names: &["W"], kind: "power", factor: "1", offset: None, dimension: Dimension::of(&[(MASS, 1), (LENGTH, 2), (TIME, -3)]), prefixes: Prefixes::Si },
+    Unit { names: &["Pa"], kind: "pressure", factor: "1", offset: None, dimension: PRESSURE, prefixes: Prefixes::Si },
+    Unit { names: &["bar"], kind: "pressure", factor: "100000", offset: None, dimension: PRESSURE, prefixes: Prefixes::Si },
+    Unit { names: &["atm"], kind: "pressure", factor: "101325", offset: None, dimension: PRESSURE, prefixes: Prefixes::None },
+    Unit { names: &["psi"], kind: "pressure", factor: "4.4482216152605/0.00064516", offset: None, dimension: PRESSURE, prefixes: Prefixes::None },
+    Unit { names: &["A"], kind: "current", factor: "1", offset: None, dimension: Dimension::of(&[(CURRENT, 1)]), prefixes: Prefixes::Si },
+    Unit { names: &["V"], kind: "voltage", factor: "1", offset: None, dimension: Dimension::of(&[(MASS, 1), (LENGTH, 2), (TIME, -3), (CURRENT, -1)]), prefixes: Prefixes::Si },
+    Unit { names: &["ohm"], kind: "resistance", factor: "1", offset: None, dimension: Dimension::of(&[(MASS, 1), (LENGTH, 2), (TIME, -3), (CURRENT, -2)]), prefixes: Prefixes::Si },
+    Unit { names: &["K"], kind: "temperature", factor: "1", offset: None, dimension: TEMPERATURE_UNIT, prefixes: Prefixes::Si },
+    Unit { names: &["C", "°C"], kind: "temperature", factor: "1", offset: Some("273.15"), dimension: TEMPERATURE_UNIT, prefixes: Prefixes::None },
+    Unit { names: &["F", "°F"], kind: "temperature", factor: "5/9", offset: Some("459.67"), dimension: TEMPERATURE_UNIT, prefixes: Prefixes::None },
+    Unit { names: &["mol"], kind: "amount", factor: "1", offset: None, dimension: Dimension::of(&[(AMOUNT, 1)]), prefixes: Prefixes::Si },
+    Unit { names: &["cd"], kind: "luminous intensity", factor: "1", offset: None, dimension: Dimension::of(&[(LUMINOSITY, 1)]), prefixes: Prefixes::Si },
+    Unit { names: &["B", "byte"], kind: "information", factor: "1", offset: None, dimension: INFORMATION_UNIT, prefixes: Prefixes::Binary },
+    Unit { names: &["b", "bit"], kind: "information", factor: "0.125", offset: None, dimension: INFORMATION_UNIT, prefixes: Prefixes::Binary },
+    Unit { names: &["percent"], kind: "ratio", factor: "0.01", offset: None, dimension: NONE, prefixes: Prefixes::None },
+];
+
+impl Unit {
+    pub fn takes_prefixes(&self) -> bool {
+        self.prefixes != Prefixes::None
+    }
+}
+
+/// A unit, maybe with a prefix.
+pub struct FoundUnit {
+    prefix: Option<&'static str>,
+    pub unit: &'static Unit,
+}
+
+/// Looks `name` up among the units, and then among the prefixed ones.
+pub fn unit(name: &str) -> Option<FoundUnit> {
+    if let Some(unit) = UNITS.iter().find(|unit| unit.names.contains(&name)) {
+        return Some(FoundUnit { prefix: None, unit });
+    }
+
+    let prefixes = BINARY_PREFIXES.iter().map(|prefix| (prefix, Prefixes::Binary))
+        .chain(SI_PREFIXES.iter().map(|prefix| (prefix, Prefixes::Si)));
+    for (&(prefix, factor), kind) in prefixes {
+        let rest = match name.strip_prefix(prefix) {
+            Some(rest) => rest,
+            None => continue,
+        };
+        let found = UNITS.iter().find(|unit| {
+            unit.names.contains(&rest) && matches!((unit.prefixes, kind), (Prefixes::Binary, _) | (Prefixes::Si, Prefixes::Si))
+        });
+        if let Some(unit) = found {
+            return Some(FoundUnit { prefix: Some(factor), unit });
+        }
+    }
+    None
+}
+
+/// Reads a decimal number or a fraction of two.
+fn factor<N: Arithmetic>(text: &str) -> Result<N, String> {
+    let decimal = |text: &str| N::literal(text.parse().unwrap_or(f64::NAN), text);
+    match text.find('/') {
+        Some(slash) => N::binary(Op::Div, decimal(&text[..slash])?, decimal(&text[slash + 1..])?),
+        None => decimal(text),
+    }
+}
+
+impl FoundUnit {
+    /// Whether it is a temperature not starting at the absolute zero.
+    pub fn has_offset(&self) -> bool {
+        self.unit.offset.is_some()
+    }
+
+    fn offset<N: Arithmetic>(&self) -> Result<N, String> {
+        match self.unit.offset {
+            Some(offset) => factor(offset),
+            None => N::literal(0.0, "0"),
+        }
+    }
+
+    /// What the unit is worth, temperatures being differences.
+    pub fn quantity<N: Arithmetic>(&self) -> Result<Quantity<N>, String> {
+        let mut value = factor(self.unit.factor)?;
+        if let Some(prefix) = self.prefix {
+            value = N::binary(Op::Mul, factor(prefix)?, value)?;
+        }
+        Ok(Quantity { value, dimension: self.unit.dimension })
+    }
+
+    /// The temperature `value` in this unit, from the absolute zero.
+    pub fn absolute<N: Arithmetic>(&self, value: N) -> Result<Quantity<N>, String> {
+        let offset = self.offset()?;
+        let unit = self.quantity()?;
+        Ok(Quantity {
+            value: N::binary(Op::Mul, N::binary(Op::Add, value, offset)?, unit.value)?,
+            dimension: unit.dimension,
+        })
+    }
+
+    /// The value in this unit of the temperature `quantity`, from the
+    /// absolute zero.
+    pub fn relative<N: Arithmetic>(&self, quantity: Quantity<N>) -> Result<N, String> {
+        let offset = self.offset()?;
+        let unit = self.quantity()?;
+        N::binary(Op::Sub, N::binary(Op::Div, quantity.value, unit.value)?, offset)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(name: &str) -> f64 {
+        unit(name).unwrap().quantity::<f64>().unwrap().value
+    }
+
+    #[test]
+    fn test_lookup() {
+        assert_eq!(value("km"), 1000.0);
+        assert_eq!(value("min"), 60.0);
+        assert_eq!(value("mm"), 0.001);
+        assert_eq!(value("dam"), 10.0);
+        assert_eq!(value("GiB"), 1073741824.0);
+        assert_eq!(value("Mb"), 125000.0);
+        assert_eq!(value("kWh"), 3600000.0);
+        assert_eq!(value("kg"), 1.0);
+        assert!(unit("Kim").is_none());
+        assert!(unit("kft").is_none());
+        assert!(unit("kC").is_none());
+        assert!(unit("x").is_none());
+    }
+
+    #[test]
+    fn test_unique_names() {
+        for (i, unit) in UNITS.iter().enumerate() {
+            for name in unit.names {
+                assert!(UNITS[i + 1..].iter().all(|other| !other.names.contains(name)), "{} twice", name);
+            }
+        }
+    }
+
+    #[test]
+    fn test_dimensions() {
+        let speed = Quantity::binary(Op::Div, unit("km").unwrap().quantity::<f64>().unwrap(), unit("h").unwrap().quantity().unwrap()).unwrap();
+        assert_eq!(speed.dimension, SPEED);
+        assert_eq!(speed.dimension.to_string(), "m/s");
+        assert_eq!(ENERGY.to_string(), "kg*m^2/s^2");
+        assert_eq!(Dimension::of(&[(TIME, -1)]).to_string(), "s^-1");
+        assert_eq!(NONE.to_string(), "");
+
+        let metre = unit("m").unwrap().quantity::<f64>().unwrap();
+        let second = unit("s").unwrap().quantity::<f64>().unwrap();
+        assert_eq!(Quantity::binary(Op::Add, metre.clone(), second).unwrap_err(), "incompatible units, `m` and `s`");
+        let area = Quantity::binary(Op::Pow, metre.clone(), Quantity::number(2.0)).unwrap();
+        assert_eq!(area.dimension, AREA);
+        assert!(Quantity::binary(Op::Pow, metre.clone(), Quantity::number(0.5)).is_err());
+        assert!(Quantity::binary(Op::Pow, Quantity::number(2.0), metre).is_err());
+    }
+
+    #[test]
+    fn test_temperatures() {
+        let fahrenheit = unit("F").unwrap();
+        let celsius = unit("°C").unwrap();
+        let boiling = fahrenheit.absolute(212.0).unwrap();
+        assert!((celsius.relative(boiling).unwrap() - 100.0).abs() < 1e-9);
+        assert!((unit("K").unwrap().relative(celsius.absolute(0.0).unwrap()).unwrap() - 273.15).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,182 @@
+use std::{
+    env,
+    fmt,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+use serenity::prelude::TypeMapKey;
+
+/// Settings of the bot, read from the environment (and so from `.env`).
+pub struct Config {
+    /// Maximum number of tracks a single `play` queues from a playlist.
+    pub playlist_limit: usize,
+    /// Time after which the bot leaves a voice channel where nothing plays,
+    /// `None` to stay forever.
+    pub idle_timeout: Option<Duration>,
+    /// Time after which the bot leaves a voice channel where nobody listens,
+    /// `None` to stay forever.
+    pub alone_timeout: Option<Duration>,
+    /// Period at which the state of the players is saved, `None` to save it
+    /// only when shutting down.
+    pub state_save_period: Option<Duration>,
+    /// Lavalink nodes to play through.
+    pub lavalink_nodes: Vec<NodeConfig>,
+    /// Name of the role allowed to stop or skip everyone's music, `None` to
+    /// allow everyone.
+    pub dj_role: Option<String>,
+    /// Fraction of the listeners who must vote to skip a track, in `(0, 1]`.
+    pub vote_skip_ratio: f64,
+    /// Maximum number of tracks a user may have in the queue of a guild, `None`
+    /// for no limit.
+    pub user_queue_limit: Option<usize>,
+    /// Maximum length of a queued track, `None` for no limit.
+    pub max_track_length: Option<Duration>,
+    /// Whether the requesters take turns in the queue, rather than their tracks
+    /// playing in the order they have been queued.
+    pub fair_queue: bool,
+    /// Folder of the lyrics files of `lyrics`.
+    pub lyrics_folder: String,
+}
+
+/// How to connect to a lavalink node.
+#[derive(Clone, Debug)]
+pub struct NodeConfig {
+    pub host: String,
+    pub port: u16,
+    pub password: String,
+    pub tls: bool,
+    /// Region of the node, guilds of the same voice region preferring it.
+    pub region: Option<String>,
+}
+
+impl fmt::Display for NodeConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let scheme = if self.tls { "wss" } else { "ws" };
+        write!(f, "{}://{}:{}", scheme, self.host, self.port)
+    }
+}
+
+impl Config {
+    /// Reads the settings, failing with a message naming the variable at fault
+    /// if one is invalid.
+    pub fn from_env() -> Result<Self, String> {
+        Ok(Self {
+            playlist_limit: playlist_limit()?,
+            idle_timeout: timeout(env_parse::<u64>("IOLAA_IDLE_MINUTES")?.unwrap_or(5).saturating_mul(60)),
+            alone_timeout: timeout(env_parse("IOLAA_ALONE_SECONDS")?.unwrap_or(30)),
+            state_save_period: timeout(env_parse("IOLAA_STATE_SAVE_SECONDS")?.unwrap_or(60)),
+            lavalink_nodes: lavalink_nodes()?,
+            dj_role: env::var("IOLAA_DJ_ROLE").ok()
+                .map(|role| role.trim().to_string())
+                .filter(|role| !role.is_empty()),
+            vote_skip_ratio: vote_skip_ratio()?,
+            user_queue_limit: Some(env_parse::<usize>("IOLAA_USER_QUEUE_LIMIT")?.unwrap_or(50))
+                .filter(|&limit| limit > 0),
+            max_track_length: timeout(env_parse::<u64>("IOLAA_MAX_TRACK_MINUTES")?.unwrap_or(0).saturating_mul(60)),
+            fair_queue: env_parse("IOLAA_FAIR_QUEUE")?.unwrap_or(false),
+            lyrics_folder: env::var("IOLAA_LYRICS_FOLDER").unwrap_or_else(|_| String::from("data/lyrics")),
+        })
+    }
+}
+
+/// Reads how many tracks of a playlist a `play` queues, at least one.
+fn playlist_limit() -> Result<usize, String> {
+    let limit = env_parse::<usize>("IOLAA_PLAYLIST_LIMIT")?.unwrap_or(100);
+    if limit == 0 {
+        return Err(String::from("IOLAA_PLAYLIST_LIMIT cannot be 0"));
+    }
+    Ok(limit)
+}
+
+/// Reads the percentage of listeners needed to skip a track by vote.
+fn vote_skip_ratio() -> Result<f64, String> {
+    let percent = env_parse::<u8>("IOLAA_VOTE_SKIP_PERCENT")?.unwrap_or(50);
+    if percent == 0 || percent > 100 {
+        return Err(format!("IOLAA_VOTE_SKIP_PERCENT must be between 1 and 100, got {}", percent));
+    }
+    Ok(f64::from(percent) / 100.0)
+}
+
+/// Reads the nodes from the `IOLAA_LAVALINK_<n>_*` variables, numbered from 1,
+/// or else the single node of the `IOLAA_LAVALINK_*` ones, defaulting to the
+/// local node of `application.yml`.
+fn lavalink_nodes() -> Result<Vec<NodeConfig>, String> {
+    let mut nodes = Vec::new();
+
+    for n in 1.. {
+        match node_config(&format!("IOLAA_LAVALINK_{}", n))? {
+            Some(node) => nodes.push(node),
+            None => break,
+        }
+    }
+
+    if nodes.is_empty() {
+        let node = node_config("IOLAA_LAVALINK")?.unwrap_or_else(|| NodeConfig {
+            host: String::from("127.0.0.1"),
+            port: 2333,
+            password: String::from("youshallnotpass"),
+            tls: false,
+            region: None,
+        });
+        nodes.push(node);
+    }
+
+    Ok(nodes)
+}
+
+/// Reads the `<prefix>_HOST`, `_PORT`, `_PASSWORD`, `_TLS` and `_REGION`
+/// variables of a node, `None` if its host is not set.
+fn node_config(prefix: &str) -> Result<Option<NodeConfig>, String> {
+    let host = match env::var(format!("{}_HOST", prefix)) {
+        Ok(host) => host.trim().to_string(),
+        Err(_) => return Ok(None),
+    };
+    if host.is_empty() || host.contains('/') {
+        return Err(format!("{}_HOST must be a host name or address, got `{}`", prefix, host));
+    }
+
+    let port = env_parse::<u16>(&format!("{}_PORT", prefix))?.unwrap_or(2333);
+    if port == 0 {
+        return Err(format!("{}_PORT cannot be 0", prefix));
+    }
+
+    let password = env::var(format!("{}_PASSWORD", prefix))
+        .unwrap_or_else(|_| String::from("youshallnotpass"));
+    if password.is_empty() {
+        return Err(format!("{}_PASSWORD cannot be empty", prefix));
+    }
+
+    Ok(Some(NodeConfig {
+        host,
+        port,
+        password,
+        tls: env_parse(&format!("{}_TLS", prefix))?.unwrap_or(false),
+        region: env::var(format!("{}_REGION", prefix)).ok(),
+    }))
+}
+
+pub struct ConfigContainer;
+
+impl TypeMapKey for ConfigContainer {
+    type Value = Arc<Config>;
+}
+
+/// Reads and parses the environment variable `key`, `None` when it is not set.
+fn env_parse<T: FromStr>(key: &str) -> Result<Option<T>, String> {
+    match env::var(key) {
+        Ok(value) => value.trim().parse::<T>()
+            .map(Some)
+            .map_err(|_| format!("Invalid value `{}` for {}", value, key)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Turns a number of seconds into a duration, `0` meaning never.
+fn timeout(secs: u64) -> Option<Duration> {
+    match secs {
+        0 => None,
+        secs => Some(Duration::from_secs(secs)),
+    }
+}
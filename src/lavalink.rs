@@ -0,0 +1,140 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use serenity::{
+    model::id::GuildId,
+    prelude::{Mutex, RwLock},
+};
+
+use lavalink_rs::{
+    LavalinkClient,
+    model::Stats,
+};
+
+use crate::config::NodeConfig;
+
+/// Time without stats from a node after which it is considered down, lavalink
+/// sending them every minute.
+pub const NODE_TIMEOUT: Duration = Duration::from_secs(90);
+
+/// What is known about the health and the load of a lavalink node.
+pub struct NodeStatus {
+    pub healthy: bool,
+    pub last_seen: Instant,
+    pub playing_players: u64,
+    /// Load of the cpu of the node, between 0 and 1.
+    pub system_load: f64,
+}
+
+impl NodeStatus {
+    pub fn new() -> Self {
+        Self {
+            healthy: true,
+            last_seen: Instant::now(),
+            playing_players: 0,
+            system_load: 0.0,
+        }
+    }
+
+    /// Records the stats the node just sent, which proves it is alive.
+    pub fn update(&mut self, stats: &Stats) {
+        self.healthy = true;
+        self.last_seen = Instant::now();
+        self.playing_players = stats.playing_players;
+        self.system_load = stats.cpu.system_load;
+    }
+}
+
+pub struct LavalinkNode {
+    pub config: NodeConfig,
+    pub client: Arc<Mutex<LavalinkClient>>,
+    pub status: Arc<RwLock<NodeStatus>>,
+}
+
+/// The lavalink nodes the bot plays through, and the node playing for each guild.
+pub struct LavalinkNodes {
+    pub nodes: Vec<LavalinkNode>,
+    pub guilds: HashMap<GuildId, usize>,
+}
+
+impl LavalinkNodes {
+    pub fn new() -> Self {
+        Self {
+            nodes: Vec::new(),
+            guilds: HashMap::new(),
+        }
+    }
+
+    pub fn add(&mut self, node: LavalinkNode) {
+        self.nodes.push(node);
+    }
+
+    /// Returns the client of the node playing for `guild_id`, if any.
+    pub fn get(&self, guild_id: GuildId) -> Option<Arc<Mutex<LavalinkClient>>> {
+        self.guilds.get(&guild_id)
+            .map(|&index| Arc::clone(&self.nodes[index].client))
+    }
+
+    /// Returns the client of the node playing for `guild_id`, assigning the
+    /// guild to the least loaded healthy node first if it has none yet.
+    pub async fn assign(&mut self, guild_id: GuildId, region: Option<&str>) -> Option<Arc<Mutex<LavalinkClient>>> {
+        if let Some(client) = self.get(guild_id) {
+            return Some(client);
+        }
+
+        let index = self.least_loaded(region).await?;
+        self.guilds.insert(guild_id, index);
+        Some(Arc::clone(&self.nodes[index].client))
+    }
+
+    /// Forgets the node playing for `guild_id`.
+    pub fn release(&mut self, guild_id: GuildId) {
+        self.guilds.remove(&guild_id);
+    }
+
+    /// Whether at least one node is connected and healthy.
+    pub async fn is_available(&self) -> bool {
+        for node in &self.nodes {
+            if node.status.read().await.healthy {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Returns the index of the least loaded healthy node, preferring the nodes
+    /// of `region` if there are healthy ones.
+    pub async fn least_loaded(&self, region: Option<&str>) -> Option<usize> {
+        let mut candidates: Vec<(usize, f64, bool)> = Vec::new();
+
+        for (index, node) in self.nodes.iter().enumerate() {
+            let status = node.status.read().await;
+            if !status.healthy {
+                continue;
+            }
+
+            // the stats lag behind the players the bot just assigned
+            let assigned = self.guilds_on(index).len() as u64;
+            let load = assigned.max(status.playing_players) as f64 + status.system_load;
+            let in_region = region.is_some() && node.config.region.as_deref() == region;
+            candidates.push((index, load, in_region));
+        }
+
+        let any_in_region = candidates.iter().any(|&(_, _, in_region)| in_region);
+        candidates.into_iter()
+            .filter(|&(_, _, in_region)| in_region || !any_in_region)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(index, _, _)| index)
+    }
+
+    /// Returns the guilds the node `index` plays for.
+    pub fn guilds_on(&self, index: usize) -> Vec<GuildId> {
+        self.guilds.iter()
+            .filter(|&(_, &node)| node == index)
+            .map(|(&guild_id, _)| guild_id)
+            .collect()
+    }
+}
@@ -0,0 +1,187 @@
+use std::{
+    fs,
+    path::PathBuf,
+    sync::Arc,
+};
+
+use serenity::{
+    async_trait,
+    prelude::TypeMapKey,
+};
+
+/// The lyrics of a song.
+pub struct Lyrics {
+    pub title: String,
+    pub text: String,
+}
+
+/// Somewhere to find the lyrics of songs.
+#[async_trait]
+pub trait LyricsProvider: Send + Sync {
+    /// Name of the provider, to credit it.
+    fn name(&self) -> &str;
+
+    /// Looks for the lyrics of the song best matching `query`, usually a
+    /// track title. `Ok(None)` if there are none.
+    async fn search(&self, query: &str) -> Result<Option<Lyrics>, String>;
+}
+
+pub struct LyricsContainer;
+
+impl TypeMapKey for LyricsContainer {
+    type Value = Arc<dyn LyricsProvider>;
+}
+
+/// Lyrics read from the `.txt` files of a folder, named after their song.
+pub struct LocalLyrics {
+    folder: PathBuf,
+}
+
+impl LocalLyrics {
+    pub fn new(folder: impl Into<PathBuf>) -> Self {
+        Self {
+            folder: folder.into(),
+        }
+    }
+}
+
+#[async_trait]
+impl LyricsProvider for LocalLyrics {
+    fn name(&self) -> &str {
+        "local lyrics"
+    }
+
+    async fn search(&self, query: &str) -> Result<Option<Lyrics>, String> {
+        // no folder, no lyrics
+        let entries = match fs::read_dir(&self.folder) {
+            Ok(entries) => entries,
+            Err(_) => return Ok(None),
+        };
+
+        let titles: Vec<String> = entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().map_or(false, |ext| ext == "txt"))
+            .filter_map(|path| path.file_stem().and_then(|stem| stem.to_str()).map(String::from))
+            .collect();
+
+        let title = match best_match(&titles, query) {
+            Some(title) => title,
+            None => return Ok(None),
+        };
+        let text = fs::read_to_string(self.folder.join(format!("{}.txt", title)))
+            .map_err(|why| format!("Could not read the lyrics of {}: {}", title, why))?;
+
+        Ok(Some(Lyrics {
+            title: title.clone(),
+            text,
+        }))
+    }
+}
+
+/// Lowercases `s` and keeps only its words, surrounded by spaces, for titles to
+/// match whole words whatever their punctuation.
+fn normalize(s: &str) -> String {
+    let words: Vec<String> = s.to_lowercase()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| format!(" {} ", word))
+        .collect();
+    words.concat().replace("  ", " ")
+}
+
+/// Returns the title matching `query` the best: the longest one contained in
+/// it, as track titles often add `(Official Video)` and the like, or else the
+/// shortest one containing it.
+pub fn best_match<'a>(titles: &'a [String], query: &str) -> Option<&'a String> {
+    let query = normalize(query);
+    if query.trim().is_empty() {
+        return None;
+    }
+
+    let normalized: Vec<(&String, String)> = titles.iter()
+        .map(|title| (title, normalize(title)))
+        .filter(|(_, normalized)| !normalized.trim().is_empty())
+        .collect();
+
+    normalized.iter()
+        .filter(|(_, title)| query.contains(title.as_str()))
+        .max_by_key(|(_, title)| title.len())
+        .or_else(|| normalized.iter()
+                     .filter(|(_, title)| title.contains(query.as_str()))
+                     .min_by_key(|(_, title)| title.len()))
+        .map(|&(title, _)| title)
+}
+
+/// Splits `text` into pages of at most `max_len` characters, between lines when
+/// possible.
+pub fn paginate(text: &str, max_len: usize) -> Vec<String> {
+    let mut pages = Vec::new();
+    let mut page = String::new();
+
+    for line in text.trim().lines() {
+        let mut line = line.trim_end();
+
+        // a line too long for any page is cut
+        while line.chars().count() > max_len {
+            push_page(&mut pages, &mut page);
+            let cut = line.char_indices().nth(max_len).map_or(line.len(), |(i, _)| i);
+            pages.push(line[..cut].to_string());
+            line = &line[cut..];
+        }
+
+        if page.chars().count() + line.chars().count() + 1 > max_len {
+            push_page(&mut pages, &mut page);
+        }
+        // pages do not start with blank lines
+        if page.is_empty() && line.is_empty() {
+            continue;
+        }
+        if !page.is_empty() {
+            page.push('\n');
+        }
+        page.push_str(line);
+    }
+
+    push_page(&mut pages, &mut page);
+    pages
+}
+
+/// Ends the current page, unless it is blank.
+fn push_page(pages: &mut Vec<String>, page: &mut String) {
+    let text = std::mem::take(page);
+    if !text.trim().is_empty() {
+        pages.push(text.trim_end().to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_best_match() {
+        let titles = vec![
+            String::from("Never Gonna Give You Up"),
+            String::from("Bohemian Rhapsody"),
+            String::from("Rhapsody"),
+        ];
+
+        let found = best_match(&titles, "Rick Astley - Never Gonna Give You Up (Official Music Video)");
+        assert_eq!(found, Some(&titles[0]));
+        assert_eq!(best_match(&titles, "Queen – Bohemian Rhapsody"), Some(&titles[1]));
+        assert_eq!(best_match(&titles, "bohemian"), Some(&titles[1]));
+        assert_eq!(best_match(&titles, "Despacito"), None);
+        assert_eq!(best_match(&titles, "Never Gonna Give You Upside Down"), None);
+        assert_eq!(best_match(&titles, "!!"), None);
+    }
+
+    #[test]
+    fn test_paginate() {
+        let text = "first line\nsecond line\n\nthird line";
+        assert_eq!(paginate(text, 100), vec![text]);
+        assert_eq!(paginate(text, 23), vec!["first line\nsecond line", "third line"]);
+        assert_eq!(paginate("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+        assert!(paginate("  \n ", 10).is_empty());
+    }
+}
@@ -1,9 +1,15 @@
 mod commands;
+mod config;
+mod lavalink;
+mod lyrics;
 
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
     env,
-    sync::Arc,
+    sync::{
+        Arc,
+        atomic::{AtomicBool, Ordering},
+    },
 };
 
 use serenity::{
@@ -17,25 +23,31 @@ use serenity::{
     model::{
         event::VoiceServerUpdateEvent, 
         gateway::Ready,
+        id::GuildId,
+        voice::VoiceState,
     },
     prelude::{Mutex, TypeMapKey, Client, Context, EventHandler, RwLock},
 };
 
-use lavalink_rs::{
-    LavalinkClient,
-};
-
 use tracing::{error, info};
 use tracing_subscriber::{
     FmtSubscriber,
     EnvFilter,
 };
 
+use config::{Config, ConfigContainer};
+use lavalink::LavalinkNodes;
+use lyrics::{LocalLyrics, LyricsContainer};
+
 use commands::{
     math::*,
     meta::*,
     urls::*,
     player::*,
+    playlist::*,
+    lyrics::*,
+    history::*,
+    games::*,
 };
 
 struct ShardManagerContainer;
@@ -44,20 +56,52 @@ impl TypeMapKey for ShardManagerContainer {
     type Value = Arc<Mutex<ShardManager>>;
 }
 
-struct Handler;
+struct Handler {
+    /// Whether the idle players and lavalink nodes watchers have been started,
+    /// as `ready` is sent again on every reconnection.
+    watching: AtomicBool,
+    /// Whether the players of the previous run have been restored.
+    restored: AtomicBool,
+}
 
 #[async_trait]
 impl EventHandler for Handler {
-    async fn ready(&self, _: Context, ready: Ready) {
+    async fn ready(&self, ctx: Context, ready: Ready) {
         info!("Connected as {}", ready.user.name);
+
+        if !self.watching.swap(true, Ordering::SeqCst) {
+            tokio::spawn(watch_idle_players(ctx.clone()));
+            tokio::spawn(watch_lavalink_nodes(ctx));
+        }
+    }
+
+    async fn cache_ready(&self, ctx: Context, _guilds: Vec<GuildId>) {
+        if !self.restored.swap(true, Ordering::SeqCst) {
+            // only save once restored, not to overwrite the previous state
+            tokio::spawn(async move {
+                restore_players(&ctx).await;
+                persist_players(Arc::clone(&ctx.data)).await;
+            });
+        }
     }
 
     async fn voice_server_update(&self, ctx: Context, voice: VoiceServerUpdateEvent) {
         if let Some(guild_id) = voice.guild_id {
-            let data = ctx.data.read().await;
-            let voice_server_lock = data.get::<VoiceGuildUpdate>().unwrap();
-            let mut voice_server = voice_server_lock.write().await;
-            voice_server.insert(guild_id);
+            let waiters_lock = ctx.data.read().await.get::<VoiceServerWaiters>().cloned().unwrap();
+            if let Some(waiter) = waiters_lock.lock().await.remove(&guild_id) {
+                // the join may have timed out in the meantime
+                let _ = waiter.send(());
+            }
+        }
+    }
+
+    async fn voice_state_update(&self, ctx: Context, guild_id: Option<GuildId>, old: Option<VoiceState>, new: VoiceState) {
+        // someone left or moved out of a channel, which may be the bot's one
+        let left_channel = old.map_or(false, |voice_state| voice_state.channel_id.is_some()
+                                                            && voice_state.channel_id != new.channel_id);
+
+        if let (Some(guild_id), true) = (guild_id, left_channel) {
+            tokio::spawn(leave_if_alone(ctx, guild_id));
         }
     }
 
@@ -68,7 +112,7 @@ impl EventHandler for Handler {
 struct General;
 
 #[group]
-#[commands(multiply, add, compute)]
+#[commands(multiply, add, sum, product, stats, compute, convert, plot, base, exact, programmer, vars, forget)]
 struct Math;
 
 #[group]
@@ -76,9 +120,26 @@ struct Math;
 struct UrlSet;
 
 #[group]
-#[commands(join, leave, play, stop)]
+#[checks(Music)]
+#[commands(join, leave, play, skip, voteskip, stop, autoplay)]
 struct Player;
 
+#[group]
+#[commands(playlist)]
+struct Playlist;
+
+#[group]
+#[commands(lyrics)]
+struct Lyrics;
+
+#[group]
+#[commands(history, replay, mostplayed)]
+struct History;
+
+#[group]
+#[commands(roll, pick, shuffle, coin)]
+struct Games;
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     // This will load the environment variables located at `./.env`, relative to
@@ -99,6 +160,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let token = env::var("IOLAA_DISCORD_TOKEN")
                     .expect("Expected a token in the environment");
 
+    let config = match Config::from_env() {
+        Ok(config) => Arc::new(config),
+        Err(why) => {
+            error!("Invalid configuration: {}", why);
+            return Err(why.into());
+        },
+    };
+
     let http = Http::new_with_token(&token);
 
     // We will fetch your bot's owners and id
@@ -119,14 +188,22 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .configure(|c| c
                    .owners(owners)
                    .prefix("&"))
+        .help(&HELP)
         .group(&GENERAL_GROUP)
         .group(&MATH_GROUP)
         .group(&PLAYER_GROUP)
+        .group(&PLAYLIST_GROUP)
+        .group(&LYRICS_GROUP)
+        .group(&HISTORY_GROUP)
+        .group(&GAMES_GROUP)
         .group(&URLSET_GROUP);
 
     let mut client = Client::builder(&token)
         .framework(framework)
-        .event_handler(Handler)
+        .event_handler(Handler {
+            watching: AtomicBool::new(false),
+            restored: AtomicBool::new(false),
+        })
         // .intents(GatewayIntents::all())
         .intents(   GatewayIntents::DIRECT_MESSAGES
                   | GatewayIntents::GUILDS 
@@ -141,28 +218,70 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     {
         let mut data = client.data.write().await;
         data.insert::<ShardManagerContainer>(client.shard_manager.clone());
+        data.insert::<ConfigContainer>(Arc::clone(&config));
 
         data.insert::<VoiceManager>(Arc::clone(&client.voice_manager));
-        data.insert::<VoiceGuildUpdate>(Arc::new(RwLock::new(HashSet::new())));
+        data.insert::<VoiceServerWaiters>(Arc::new(Mutex::new(HashMap::new())));
 
         let fs = load_urls(DATA_FILE_PATH)
             .expect(&format!("Could not read and parse file {}.", DATA_FILE_PATH));
         data.insert::<UrlsFolder>(Arc::new(RwLock::new(fs)));
         data.insert::<CurDir>(Arc::new(RwLock::new(DEF_FOLDER_NAME.to_string())));
 
-        let mut lava_client = LavalinkClient::new(bot_id);
-
-        lava_client.set_host("127.0.0.1");
+        let playlists = load_playlists(PLAYLISTS_FILE_PATH)
+            .expect(&format!("Could not read and parse file {}.", PLAYLISTS_FILE_PATH));
+        data.insert::<Playlists>(Arc::new(RwLock::new(playlists)));
+        let scopes = load_scopes(SCOPES_FILE_PATH)
+            .expect(&format!("Could not read and parse file {}.", SCOPES_FILE_PATH));
+        data.insert::<CalcScopes>(Arc::new(RwLock::new(scopes)));
+
+        let history = load_history(HISTORY_FILE_PATH)
+            .expect(&format!("Could not read and parse file {}.", HISTORY_FILE_PATH));
+        data.insert::<HistoryContainer>(Arc::new(RwLock::new(history)));
+
+        data.insert::<LyricsContainer>(Arc::new(LocalLyrics::new(&config.lyrics_folder)));
+
+        data.insert::<TrackRequests>(Arc::new(RwLock::new(HashMap::new())));
+        data.insert::<PlayerPositions>(Arc::new(RwLock::new(HashMap::new())));
+        data.insert::<SkipVotes>(Arc::new(RwLock::new(HashMap::new())));
+        data.insert::<Autoplay>(Arc::new(RwLock::new(HashSet::new())));
+        data.insert::<PlayedTracks>(Arc::new(RwLock::new(HashMap::new())));
+        data.insert::<Lavalink>(Arc::new(RwLock::new(LavalinkNodes::new())));
+    }
 
-        let lava = lava_client.initialize(LavalinkHandler).await?;
-        data.insert::<Lavalink>(lava);
+    // the bot starts without the unreachable nodes, the music being unavailable
+    // until one of them can be reached
+    let mut unreachable = Vec::new();
+    for node_config in &config.lavalink_nodes {
+        let http = Arc::clone(&client.cache_and_http.http);
+        match connect_node(&client.data, http, bot_id, node_config).await {
+            Ok(node) => {
+                let data = client.data.read().await;
+                data.get::<Lavalink>().unwrap().write().await.add(node);
+            },
+            Err(why) => {
+                error!("Could not connect to lavalink node {} ({}). Is Lavalink running \
+                        there, with the password of its application.yml?", node_config, why);
+                unreachable.push(node_config.clone());
+            },
+        }
+    }
 
+    if unreachable.len() == config.lavalink_nodes.len() {
+        error!("None of the {} lavalink nodes is reachable, see the IOLAA_LAVALINK_* variables of .env. \
+                The music is unavailable until one is.", config.lavalink_nodes.len());
+    }
+    if !unreachable.is_empty() {
+        let http = Arc::clone(&client.cache_and_http.http);
+        tokio::spawn(reconnect_nodes(Arc::clone(&client.data), http, bot_id, unreachable));
     }
 
     let shard_manager = client.shard_manager.clone();
+    let data = Arc::clone(&client.data);
 
     tokio::spawn(async move {
         tokio::signal::ctrl_c().await.expect("Could not register ctrl+c handler");
+        save_players(&data).await;
         shard_manager.lock().await.shutdown_all().await;
     });
 